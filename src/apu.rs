@@ -0,0 +1,342 @@
+mod noise;
+mod pulse;
+mod wave;
+
+use self::noise::Noise;
+use self::pulse::Pulse;
+use self::wave::Wave;
+
+pub const NR10_ADDR: u16 = 0xFF10;
+pub const NR11_ADDR: u16 = 0xFF11;
+pub const NR12_ADDR: u16 = 0xFF12;
+pub const NR13_ADDR: u16 = 0xFF13;
+pub const NR14_ADDR: u16 = 0xFF14;
+pub const NR21_ADDR: u16 = 0xFF16;
+pub const NR24_ADDR: u16 = 0xFF19;
+pub const NR30_ADDR: u16 = 0xFF1A;
+pub const NR34_ADDR: u16 = 0xFF1E;
+pub const NR41_ADDR: u16 = 0xFF20;
+pub const NR44_ADDR: u16 = 0xFF23;
+pub const NR50_ADDR: u16 = 0xFF24;
+pub const NR51_ADDR: u16 = 0xFF25;
+pub const NR52_ADDR: u16 = 0xFF26;
+pub const WAVE_RAM_ADDR: u16 = 0xFF30;
+pub const WAVE_RAM_END: u16 = 0xFF3F;
+
+const NR52_POWER: u8 = 0x80;
+
+// The APU is clocked at 4194304 Hz, also in CGB double speed
+pub const CLOCK_RATE: u32 = 4_194_304;
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+// At most one sample is produced per machine cycle
+const MAX_SAMPLE_RATE: u32 = CLOCK_RATE / 4;
+
+// The capacitor removing the DC offset from the output charges by this factor every clock cycle
+const HIGH_PASS_CHARGE: f64 = 0.999958;
+
+// The registers as the DMG boot ROM leaves them, from NR10 to NR51.
+// NRx4 are written without the trigger bit, which reads as 1 anyway, not to restart the channels.
+const POST_BOOT: [(u16, u8); 19] = [
+    (0xFF10, 0x80), (0xFF11, 0xBF), (0xFF12, 0xF3), (0xFF13, 0xFF), (0xFF14, 0x3F),
+    (0xFF16, 0x3F), (0xFF18, 0xFF), (0xFF19, 0x3F), (0xFF1A, 0x7F), (0xFF1B, 0xFF),
+    (0xFF1C, 0x9F), (0xFF1D, 0xFF), (0xFF1E, 0x3F), (0xFF20, 0xFF), (0xFF21, 0x00),
+    (0xFF22, 0x00), (0xFF23, 0x3F), (0xFF24, 0x77), (0xFF25, 0xF3)
+];
+
+/// Disables its channel after 64 (256 for the wave channel) ticks at 256 Hz, if enabled
+struct Length {
+    max: u16,
+    counter: u16,
+    enabled: bool
+}
+
+impl Length {
+    fn new(max: u16) -> Length {
+        Length { max, counter: 0, enabled: false }
+    }
+
+    fn load(&mut self, value: u8) {
+        self.counter = self.max - value as u16;
+    }
+
+    fn trigger(&mut self) {
+        if self.counter == 0 {
+            self.counter = self.max;
+        }
+    }
+
+    /// Returns true when the counter runs out and the channel has to be disabled
+    fn clock(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            return self.counter == 0;
+        }
+        false
+    }
+}
+
+/// Raises or lowers the volume of its channel by 1 every `period` ticks at 64 Hz, NRx2 of pulse and noise channels
+#[derive(Default)]
+struct Envelope {
+    register: u8,
+    volume: u8,
+    timer: u8
+}
+
+impl Envelope {
+    fn period(&self) -> u8 {
+        self.register & 0x07
+    }
+
+    /// The upper 5 bits of the register power the DAC of the channel
+    fn dac_enabled(&self) -> bool {
+        self.register & 0xF8 != 0
+    }
+
+    fn trigger(&mut self) {
+        self.volume = self.register >> 4;
+        self.timer = self.period();
+    }
+
+    fn clock(&mut self) {
+        if self.period() == 0 {
+            return;
+        }
+
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = self.period();
+            if self.register & 0x08 != 0 && self.volume < 15 {
+                self.volume += 1;
+            } else if self.register & 0x08 == 0 && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+}
+
+/// The analog output of a channel DAC from its digital output (0-15), from -1.0 to 1.0 and 0.0 when off
+fn dac(enabled: bool, output: u8) -> f32 {
+    if enabled { output as f32 / 7.5 - 1.0 } else { 0.0 }
+}
+
+/**
+ * The audio processing unit: two pulse channels, the first with a frequency sweep, a wave channel
+ * playing 32 samples from wave RAM and a noise channel.
+ * Length counters, the sweep and envelopes are clocked by the frame sequencer, stepped at 512 Hz by DIV.
+ * NR51 routes each channel to the left and/or right output, NR50 sets the volume of each output.
+ *
+ * Output samples are stereo pairs from -1.0 to 1.0 at the configured sample rate, each averaging the output
+ * over its period and high-pass filtered like the hardware does to remove the DC offset.
+ */
+pub struct Apu {
+    powered: bool,
+    // The raw values written to NR10-NR51, channel registers are also decoded by the channels
+    registers: [u8; 0x16],
+    pulse1: Pulse,
+    pulse2: Pulse,
+    wave: Wave,
+    noise: Noise,
+    frame_step: u8,
+
+    sample_rate: u32,
+    // Clock cycles times the sample rate since the last sample, one is output for every CLOCK_RATE
+    sample_clock: u64,
+    sum: (f32, f32),
+    sum_cycles: u32,
+    capacitor: (f32, f32),
+    high_pass: f32,
+    samples: Vec<(f32, f32)>
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Apu::new()
+    }
+}
+
+impl Apu {
+    /// Creates an APU in the state the DMG boot ROM leaves it in, producing samples at DEFAULT_SAMPLE_RATE
+    pub fn new() -> Apu {
+        let mut apu = Apu {
+            powered: true,
+            registers: [0; 0x16],
+            pulse1: Pulse::with_sweep(),
+            pulse2: Pulse::new(),
+            wave: Wave::new(),
+            noise: Noise::new(),
+            frame_step: 0,
+            sample_rate: 0,
+            sample_clock: 0,
+            sum: (0.0, 0.0),
+            sum_cycles: 0,
+            capacitor: (0.0, 0.0),
+            high_pass: 0.0,
+            samples: Vec::new()
+        };
+        apu.set_sample_rate(DEFAULT_SAMPLE_RATE);
+
+        for (addr, value) in POST_BOOT {
+            apu.write(addr, value);
+        }
+        // The boot sound is over, its channel is still on with the volume down to 0
+        apu.pulse1.enabled = true;
+
+        apu
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Changes the rate of the output samples, up to one sample per machine cycle
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate.clamp(1, MAX_SAMPLE_RATE);
+        self.high_pass = HIGH_PASS_CHARGE.powf(CLOCK_RATE as f64 / self.sample_rate as f64) as f32;
+    }
+
+    /// The samples produced since the last call, as (left, right) pairs
+    pub fn take_samples(&mut self) -> Vec<(f32, f32)> {
+        std::mem::take(&mut self.samples)
+    }
+
+    /// Advances the APU by a number of clock cycles, 4 per machine cycle or 2 in CGB double speed
+    pub fn tick(&mut self, cycles: u32) {
+        if self.powered {
+            self.pulse1.tick(cycles);
+            self.pulse2.tick(cycles);
+            self.wave.tick(cycles);
+            self.noise.tick(cycles);
+        }
+
+        let (left, right) = self.mix();
+        self.sum.0 += left * cycles as f32;
+        self.sum.1 += right * cycles as f32;
+        self.sum_cycles += cycles;
+
+        self.sample_clock += cycles as u64 * self.sample_rate as u64;
+        if self.sample_clock < CLOCK_RATE as u64 {
+            return;
+        }
+
+        // Every sample due after a long tick gets the same average
+        let left = self.sum.0 / self.sum_cycles as f32;
+        let right = self.sum.1 / self.sum_cycles as f32;
+        self.sum = (0.0, 0.0);
+        self.sum_cycles = 0;
+
+        while self.sample_clock >= CLOCK_RATE as u64 {
+            self.sample_clock -= CLOCK_RATE as u64;
+
+            let left_out = left - self.capacitor.0;
+            let right_out = right - self.capacitor.1;
+            self.capacitor = (left - left_out * self.high_pass, right - right_out * self.high_pass);
+            self.samples.push((left_out, right_out));
+        }
+    }
+
+    /**
+     * Advances the frame sequencer, on a falling edge of DIV bit 4 (bit 5 in double speed).
+     * Out of its 8 steps length counters are clocked on the even ones, the sweep on 2 and 6, envelopes on 7.
+     */
+    pub fn step_frame_sequencer(&mut self) {
+        if !self.powered {
+            return;
+        }
+
+        if self.frame_step & 0x01 == 0 {
+            self.pulse1.clock_length();
+            self.pulse2.clock_length();
+            self.wave.clock_length();
+            self.noise.clock_length();
+        }
+        if self.frame_step == 2 || self.frame_step == 6 {
+            self.pulse1.clock_sweep();
+        }
+        if self.frame_step == 7 {
+            self.pulse1.clock_envelope();
+            self.pulse2.clock_envelope();
+            self.noise.clock_envelope();
+        }
+
+        self.frame_step = (self.frame_step + 1) % 8;
+    }
+
+    /// The left and right outputs, from -1.0 to 1.0
+    fn mix(&self) -> (f32, f32) {
+        let nr50 = self.registers[(NR50_ADDR - NR10_ADDR) as usize];
+        let nr51 = self.registers[(NR51_ADDR - NR10_ADDR) as usize];
+
+        let outputs = [
+            dac(self.pulse1.dac_enabled(), self.pulse1.output()),
+            dac(self.pulse2.dac_enabled(), self.pulse2.output()),
+            dac(self.wave.dac_enabled(), self.wave.output()),
+            dac(self.noise.dac_enabled(), self.noise.output())
+        ];
+
+        let (mut left, mut right) = (0.0, 0.0);
+        for (channel, output) in outputs.iter().enumerate() {
+            if nr51 & (0x10 << channel) != 0 {
+                left += output;
+            }
+            if nr51 & (0x01 << channel) != 0 {
+                right += output;
+            }
+        }
+
+        // Volumes go from 1 to 8
+        let left_volume = ((nr50 >> 4) & 0x07) as f32 + 1.0;
+        let right_volume = (nr50 & 0x07) as f32 + 1.0;
+        (left * left_volume / 32.0, right * right_volume / 32.0)
+    }
+
+    fn set_power(&mut self, on: bool) {
+        if self.powered && !on {
+            // Everything but wave RAM is cleared
+            for addr in NR10_ADDR..NR52_ADDR {
+                self.write(addr, 0);
+            }
+            self.pulse1 = Pulse::with_sweep();
+            self.pulse2 = Pulse::new();
+            self.wave.reset();
+            self.noise = Noise::new();
+        } else if !self.powered && on {
+            self.frame_step = 0;
+        }
+        self.powered = on;
+    }
+
+    /// Reads a register or wave RAM, the write-only bits are left to the bus
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            NR52_ADDR => {
+                let channels = [self.pulse1.enabled, self.pulse2.enabled, self.wave.enabled, self.noise.enabled];
+                let status = channels.iter().enumerate().fold(0, |status, (channel, &enabled)| status | (enabled as u8) << channel);
+                ((self.powered as u8) << 7) | status
+            },
+            NR10_ADDR..=NR51_ADDR => self.registers[(addr - NR10_ADDR) as usize],
+            WAVE_RAM_ADDR..=WAVE_RAM_END => self.wave.read_ram(addr - WAVE_RAM_ADDR),
+            _ => 0xFF
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            NR52_ADDR => self.set_power(value & NR52_POWER != 0),
+            WAVE_RAM_ADDR..=WAVE_RAM_END => self.wave.write_ram(addr - WAVE_RAM_ADDR, value),
+            // Registers cannot be written while powered off, except to clear them
+            NR10_ADDR..=NR51_ADDR if !self.powered && value != 0 => {},
+            NR10_ADDR..=NR51_ADDR => {
+                self.registers[(addr - NR10_ADDR) as usize] = value;
+                match addr {
+                    NR10_ADDR..=NR14_ADDR => self.pulse1.write(addr - NR10_ADDR, value),
+                    NR21_ADDR..=NR24_ADDR => self.pulse2.write(addr - NR21_ADDR + 1, value),
+                    NR30_ADDR..=NR34_ADDR => self.wave.write(addr - NR30_ADDR, value),
+                    NR41_ADDR..=NR44_ADDR => self.noise.write(addr - NR41_ADDR + 1, value),
+                    _ => {}
+                }
+            },
+            _ => {}
+        }
+    }
+}
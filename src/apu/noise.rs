@@ -0,0 +1,114 @@
+use super::{Envelope, Length};
+
+// The base periods in clock cycles for the divisor codes in NR43
+const DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+/**
+ * The noise channel, NR41-NR44, outputting the inverted lowest bit of a 15-bit LFSR.
+ * Every period the XOR of its lowest 2 bits is shifted in at the top, and also into bit 6 in 7-bit mode.
+ */
+pub(super) struct Noise {
+    pub enabled: bool,
+    length: Length,
+    envelope: Envelope,
+    shift: u8,
+    short: bool,
+    divisor: u8,
+    // Clock cycles until the next shift of the LFSR
+    timer: u32,
+    lfsr: u16
+}
+
+impl Noise {
+    pub fn new() -> Noise {
+        Noise {
+            enabled: false,
+            length: Length::new(64),
+            envelope: Envelope::default(),
+            shift: 0,
+            short: false,
+            divisor: 0,
+            timer: 0,
+            lfsr: 0x7FFF
+        }
+    }
+
+    fn period(&self) -> u32 {
+        DIVISORS[self.divisor as usize] << self.shift
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+
+    /// The digital output, from 0 to 15
+    pub fn output(&self) -> u8 {
+        if self.enabled && self.lfsr & 0x01 == 0 {
+            self.envelope.volume
+        } else {
+            0
+        }
+    }
+
+    pub fn tick(&mut self, mut cycles: u32) {
+        // The LFSR is not clocked at all with shifts of 14 and 15
+        if !self.enabled || self.shift >= 14 {
+            return;
+        }
+
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+
+            let bit = (self.lfsr ^ (self.lfsr >> 1)) & 0x01;
+            self.lfsr = (self.lfsr >> 1) | bit << 14;
+            if self.short {
+                self.lfsr = (self.lfsr & !0x40) | bit << 6;
+            }
+        }
+        self.timer -= cycles;
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled();
+        self.length.trigger();
+        self.envelope.trigger();
+        self.timer = self.period();
+        self.lfsr = 0x7FFF;
+    }
+
+    /// Writes the register at `index`, from NRx1 to NRx4
+    pub fn write(&mut self, index: u16, value: u8) {
+        match index {
+            1 => self.length.load(value & 0x3F),
+            2 => {
+                self.envelope.register = value;
+                if !self.dac_enabled() {
+                    self.enabled = false;
+                }
+            },
+            3 => {
+                self.shift = value >> 4;
+                self.short = value & 0x08 != 0;
+                self.divisor = value & 0x07;
+            },
+            4 => {
+                self.length.enabled = value & 0x40 != 0;
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            },
+            _ => {}
+        }
+    }
+}
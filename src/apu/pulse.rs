@@ -0,0 +1,192 @@
+use super::{Envelope, Length};
+
+// The waveforms for the 4 duty cycles in NRx1: 12.5%, 25%, 50% and 75%
+const DUTY_WAVEFORMS: [[u8; 8]; 4] = [
+    [0, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 1, 1, 1],
+    [0, 1, 1, 1, 1, 1, 1, 0]
+];
+
+/**
+ * The frequency sweep of the first pulse channel, NR10.
+ * Every `period` ticks at 128 Hz the frequency is shifted right by `shift` and added to or subtracted from itself,
+ * the channel is disabled when it would overflow 11 bits.
+ */
+#[derive(Default)]
+struct Sweep {
+    period: u8,
+    negate: bool,
+    shift: u8,
+    timer: u8,
+    enabled: bool,
+    shadow: u16,
+    // A subtraction was done since the last trigger, clearing negate afterwards disables the channel
+    negated: bool
+}
+
+impl Sweep {
+    fn next_frequency(&mut self) -> u16 {
+        let delta = self.shadow >> self.shift;
+        if self.negate {
+            self.negated = true;
+            self.shadow - delta
+        } else {
+            self.shadow + delta
+        }
+    }
+
+    fn reload_timer(&mut self) {
+        // A period of 0 is treated as 8
+        self.timer = if self.period == 0 { 8 } else { self.period };
+    }
+}
+
+/// A square wave channel, NR10-NR14 for the first one with the sweep and NR21-NR24 for the second one
+pub(super) struct Pulse {
+    pub enabled: bool,
+    sweep: Option<Sweep>,
+    length: Length,
+    envelope: Envelope,
+    duty: u8,
+    frequency: u16,
+    // Clock cycles until the next step of the waveform
+    timer: u32,
+    position: u8
+}
+
+impl Pulse {
+    pub fn new() -> Pulse {
+        Pulse {
+            enabled: false,
+            sweep: None,
+            length: Length::new(64),
+            envelope: Envelope::default(),
+            duty: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0
+        }
+    }
+
+    pub fn with_sweep() -> Pulse {
+        Pulse { sweep: Some(Sweep::default()), ..Pulse::new() }
+    }
+
+    fn period(&self) -> u32 {
+        (2048 - self.frequency as u32) * 4
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+
+    /// The digital output, from 0 to 15
+    pub fn output(&self) -> u8 {
+        if self.enabled {
+            DUTY_WAVEFORMS[self.duty as usize][self.position as usize] * self.envelope.volume
+        } else {
+            0
+        }
+    }
+
+    pub fn tick(&mut self, mut cycles: u32) {
+        if self.timer == 0 {
+            self.timer = self.period();
+        }
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.position = (self.position + 1) % 8;
+        }
+        self.timer -= cycles;
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_sweep(&mut self) {
+        let sweep = match &mut self.sweep {
+            Some(sweep) => sweep,
+            None => return
+        };
+
+        sweep.timer = sweep.timer.saturating_sub(1);
+        if sweep.timer > 0 {
+            return;
+        }
+        sweep.reload_timer();
+
+        if sweep.enabled && sweep.period != 0 {
+            let frequency = sweep.next_frequency();
+            if frequency > 0x7FF {
+                self.enabled = false;
+            } else if sweep.shift != 0 {
+                sweep.shadow = frequency;
+                self.frequency = frequency;
+                // The new frequency is checked for overflow again right away
+                if sweep.next_frequency() > 0x7FF {
+                    self.enabled = false;
+                }
+            }
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled();
+        self.length.trigger();
+        self.envelope.trigger();
+        self.timer = self.period();
+
+        if let Some(sweep) = &mut self.sweep {
+            sweep.shadow = self.frequency;
+            sweep.negated = false;
+            sweep.reload_timer();
+            sweep.enabled = sweep.period != 0 || sweep.shift != 0;
+            if sweep.shift != 0 && sweep.next_frequency() > 0x7FF {
+                self.enabled = false;
+            }
+        }
+    }
+
+    /// Writes the register at `index`, from NRx0 to NRx4
+    pub fn write(&mut self, index: u16, value: u8) {
+        match index {
+            0 => if let Some(sweep) = &mut self.sweep {
+                sweep.period = (value >> 4) & 0x07;
+                sweep.shift = value & 0x07;
+                let negate = value & 0x08 != 0;
+                if sweep.negate && !negate && sweep.negated {
+                    self.enabled = false;
+                }
+                sweep.negate = negate;
+            },
+            1 => {
+                self.duty = value >> 6;
+                self.length.load(value & 0x3F);
+            },
+            2 => {
+                self.envelope.register = value;
+                if !self.dac_enabled() {
+                    self.enabled = false;
+                }
+            },
+            3 => self.frequency = (self.frequency & 0x700) | value as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | ((value & 0x07) as u16) << 8;
+                self.length.enabled = value & 0x40 != 0;
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            },
+            _ => {}
+        }
+    }
+}
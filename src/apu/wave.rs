@@ -0,0 +1,129 @@
+use super::Length;
+
+pub const WAVE_RAM_SIZE: usize = 0x10;
+
+/**
+ * The wave channel, NR30-NR34, playing the 32 4-bit samples in wave RAM, upper nibble first.
+ * The samples are shifted right according to the output level in NR32: muted, 100%, 50% or 25%.
+ */
+pub(super) struct Wave {
+    pub enabled: bool,
+    dac: bool,
+    length: Length,
+    level: u8,
+    frequency: u16,
+    // Clock cycles until the next sample
+    timer: u32,
+    position: u8,
+    // The sample last read from wave RAM
+    sample: u8,
+    ram: [u8; WAVE_RAM_SIZE]
+}
+
+impl Wave {
+    pub fn new() -> Wave {
+        Wave {
+            enabled: false,
+            dac: false,
+            length: Length::new(256),
+            level: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            sample: 0,
+            ram: [0; WAVE_RAM_SIZE]
+        }
+    }
+
+    /// Resets everything but wave RAM, when the APU is powered off
+    pub fn reset(&mut self) {
+        *self = Wave { ram: self.ram, ..Wave::new() };
+    }
+
+    fn period(&self) -> u32 {
+        (2048 - self.frequency as u32) * 2
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.dac
+    }
+
+    /// The digital output, from 0 to 15
+    pub fn output(&self) -> u8 {
+        if self.enabled && self.level != 0 {
+            self.sample >> (self.level - 1)
+        } else {
+            0
+        }
+    }
+
+    pub fn tick(&mut self, mut cycles: u32) {
+        if !self.enabled {
+            return;
+        }
+
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.position = (self.position + 1) % 32;
+
+            let byte = self.ram[self.position as usize / 2];
+            self.sample = if self.position & 0x01 == 0 { byte >> 4 } else { byte & 0x0F };
+        }
+        self.timer -= cycles;
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    /// Wave RAM can only be accessed while the channel is playing at the byte it is reading
+    pub fn read_ram(&self, offset: u16) -> u8 {
+        if self.enabled {
+            self.ram[self.position as usize / 2]
+        } else {
+            self.ram[offset as usize]
+        }
+    }
+
+    pub fn write_ram(&mut self, offset: u16, value: u8) {
+        if self.enabled {
+            self.ram[self.position as usize / 2] = value;
+        } else {
+            self.ram[offset as usize] = value;
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac;
+        self.length.trigger();
+        // The first sample is played after a short delay, the one in the buffer is kept until then
+        self.timer = self.period() + 6;
+        self.position = 0;
+    }
+
+    /// Writes the register at `index`, from NRx0 to NRx4
+    pub fn write(&mut self, index: u16, value: u8) {
+        match index {
+            0 => {
+                self.dac = value & 0x80 != 0;
+                if !self.dac {
+                    self.enabled = false;
+                }
+            },
+            1 => self.length.load(value),
+            2 => self.level = (value >> 5) & 0x03,
+            3 => self.frequency = (self.frequency & 0x700) | value as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | ((value & 0x07) as u16) << 8;
+                self.length.enabled = value & 0x40 != 0;
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            },
+            _ => {}
+        }
+    }
+}
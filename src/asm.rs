@@ -0,0 +1,600 @@
+mod expr;
+
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::mbc::ROM_BANK_SIZE;
+use crate::opcode::{OPCODE_NAMES, OPCODE_SIZES};
+
+#[derive(Debug)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// An assembled ROM image along with the values of its labels and constants
+pub struct Program {
+    pub rom: Vec<u8>,
+    symbols: HashMap<String, i64>
+}
+
+impl Program {
+    pub fn symbol(&self, name: &str) -> Option<i64> {
+        self.symbols.get(name).copied()
+    }
+}
+
+/**
+ * Assembles RGBDS style source into a ROM image.
+ * Supported are labels (global, exported with `::` and `.local`), `SECTION` with fixed or floating
+ * addresses, `db`/`dw`/`ds`, `EQU` constants, expressions and every SM83 instruction.
+ * Code outside of any section starts at 0x0000 in ROM0.
+ * The image is padded with zeros to whole banks, at least 32 KiB, so it can be loaded as a cartridge.
+ */
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    let opcodes: HashMap<&str, usize> = OPCODE_NAMES.iter()
+        .enumerate()
+        .map(|(i, &name)| (name, i))
+        .collect();
+
+    // The first pass only finds out where labels are, the second one has all of them available
+    let mut asm = Assembler::new(&opcodes, HashMap::new(), false);
+    asm.run(source)?;
+
+    let mut asm = Assembler::new(&opcodes, asm.symbols, true);
+    asm.run(source)?;
+
+    let banks = asm.rom.len().div_ceil(ROM_BANK_SIZE).max(2);
+    asm.rom.resize(banks * ROM_BANK_SIZE, 0);
+
+    Ok(Program { rom: asm.rom, symbols: asm.symbols })
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+enum Region {
+    Rom0,
+    RomX,
+    Vram,
+    Sram,
+    Wram0,
+    WramX,
+    Oam,
+    Hram
+}
+
+impl Region {
+    fn parse(name: &str) -> Option<Region> {
+        match name.to_ascii_uppercase().as_str() {
+            "ROM0"  => Some(Region::Rom0),
+            "ROMX"  => Some(Region::RomX),
+            "VRAM"  => Some(Region::Vram),
+            "SRAM"  => Some(Region::Sram),
+            "WRAM0" => Some(Region::Wram0),
+            "WRAMX" => Some(Region::WramX),
+            "OAM"   => Some(Region::Oam),
+            "HRAM"  => Some(Region::Hram),
+            _ => None
+        }
+    }
+
+    /// The address range of the region, end excluded
+    fn range(&self) -> (u32, u32) {
+        match self {
+            Region::Rom0  => (0x0000, 0x4000),
+            Region::RomX  => (0x4000, 0x8000),
+            Region::Vram  => (0x8000, 0xA000),
+            Region::Sram  => (0xA000, 0xC000),
+            Region::Wram0 => (0xC000, 0xD000),
+            Region::WramX => (0xD000, 0xE000),
+            Region::Oam   => (0xFE00, 0xFEA0),
+            Region::Hram  => (0xFF80, 0xFFFF)
+        }
+    }
+
+    fn is_rom(&self) -> bool {
+        matches!(self, Region::Rom0 | Region::RomX)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Operand {
+    Name(String),       // Register or condition, upper case
+    Indirect(String),   // [BC], [DE], [HL] or [C], named like the opcode names: BCa, DEa, HLa, Ca
+    HlIncrement,
+    HlDecrement,
+    Immediate(String),
+    Memory(String),
+    SpOffset(String)
+}
+
+const NAMES: [&str; 15] = ["A", "B", "C", "D", "E", "H", "L", "AF", "BC", "DE", "HL", "SP", "NZ", "Z", "NC"];
+
+/// Splits on commas which are not part of a string, character or bracketed expression
+fn split_operands(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut start = 0;
+
+    for (i, c) in s.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => {},
+            ('(' | '[', None) => depth += 1,
+            (')' | ']', None) => depth -= 1,
+            (',', None) if depth == 0 => {
+                parts.push(s[start..i].trim());
+                start = i + 1;
+            },
+            _ => {}
+        }
+    }
+
+    let last = s[start..].trim();
+    if !last.is_empty() || !parts.is_empty() {
+        parts.push(last);
+    }
+    parts
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (';', None) => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_operand(s: &str) -> Operand {
+    let compact: String = s.chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_uppercase();
+
+    if let Some(inner) = compact.strip_prefix('[').and_then(|i| i.strip_suffix(']')) {
+        return match inner {
+            "HL" | "BC" | "DE" => Operand::Indirect(format!("{}a", inner)),
+            "HL+" | "HLI" => Operand::HlIncrement,
+            "HL-" | "HLD" => Operand::HlDecrement,
+            "C" | "$FF00+C" | "0XFF00+C" => Operand::Indirect(String::from("Ca")),
+            _ => {
+                let s = s.trim();
+                Operand::Memory(s[1..s.len() - 1].to_string())
+            }
+        };
+    }
+
+    if NAMES.contains(&compact.as_str()) {
+        return Operand::Name(compact);
+    }
+
+    if compact.starts_with("SP+") || compact.starts_with("SP-") {
+        return Operand::SpOffset(compact[2..].to_string());
+    }
+
+    Operand::Immediate(s.trim().to_string())
+}
+
+/// How the immediate operand of an instruction is encoded
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Encoding {
+    Byte,           // n
+    Word,           // nn and nna
+    Signed,         // d
+    HighPage,       // na, the low byte of an address in 0xFF00-0xFFFF
+    Relative        // n of the relative jumps
+}
+
+struct Assembler<'a> {
+    opcodes: &'a HashMap<&'static str, usize>,
+    symbols: HashMap<String, i64>,
+    final_pass: bool,
+
+    line: usize,
+    scope: String,
+    region: Region,
+    bank: usize,
+    addr: u32,
+    // Where floating sections continue in each region and bank
+    next: HashMap<(Region, usize), u32>,
+
+    rom: Vec<u8>
+}
+
+impl<'a> Assembler<'a> {
+    fn new(opcodes: &'a HashMap<&'static str, usize>, symbols: HashMap<String, i64>, final_pass: bool) -> Assembler<'a> {
+        Assembler {
+            opcodes,
+            symbols,
+            final_pass,
+            line: 0,
+            scope: String::new(),
+            region: Region::Rom0,
+            bank: 0,
+            addr: 0,
+            next: HashMap::new(),
+            rom: Vec::new()
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> AsmError {
+        AsmError { line: self.line, message: message.into() }
+    }
+
+    fn run(&mut self, source: &str) -> Result<(), AsmError> {
+        for (i, line) in source.lines().enumerate() {
+            self.line = i + 1;
+            self.statement(strip_comment(line).trim())
+                .map_err(|message| self.error(message))?;
+        }
+        Ok(())
+    }
+
+    fn full_name(&self, name: &str) -> String {
+        if name.starts_with('.') {
+            format!("{}{}", self.scope, name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn eval(&self, expr: &str) -> Result<i64, String> {
+        expr::evaluate(expr, &mut |name| {
+            if name == "@" {
+                return Ok(self.addr as i64);
+            }
+            match self.symbols.get(&self.full_name(name)) {
+                Some(&value) => Ok(value),
+                // Labels defined further down are only known in the final pass
+                None if !self.final_pass => Ok(0),
+                None => Err(format!("unknown symbol '{}'", name))
+            }
+        })
+    }
+
+    /// Evaluates an expression which must be known in the first pass already
+    fn eval_constant(&self, expr: &str) -> Result<i64, String> {
+        expr::evaluate(expr, &mut |name| {
+            if name == "@" {
+                return Ok(self.addr as i64);
+            }
+            self.symbols.get(&self.full_name(name))
+                .copied()
+                .ok_or_else(|| format!("'{}' must be defined before it is used here", name))
+        })
+    }
+
+    fn define(&mut self, name: &str, value: i64) -> Result<(), String> {
+        let name = self.full_name(name);
+        if !self.final_pass && self.symbols.contains_key(&name) {
+            return Err(format!("'{}' is already defined", name));
+        }
+        self.symbols.insert(name, value);
+        Ok(())
+    }
+
+    fn statement(&mut self, line: &str) -> Result<(), String> {
+        if line.is_empty() {
+            return Ok(());
+        }
+
+        // Labels
+        if let Some(colon) = line.find(':') {
+            let name = &line[..colon];
+            if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || "_.#@".contains(c)) {
+                if !name.starts_with('.') {
+                    self.scope = name.to_string();
+                }
+                self.define(name, self.addr as i64)?;
+
+                let rest = line[colon + 1..].trim_start_matches(':');
+                return self.statement(rest.trim());
+            }
+        }
+
+        let (word, rest) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, "")
+        };
+
+        // Constants: "NAME EQU value" or "DEF NAME EQU value"
+        if word.eq_ignore_ascii_case("DEF") {
+            return self.statement(rest);
+        }
+        let (second, value) = match rest.find(char::is_whitespace) {
+            Some(i) => (&rest[..i], rest[i..].trim()),
+            None => (rest, "")
+        };
+        if second.eq_ignore_ascii_case("EQU") || second == "=" {
+            let value = self.eval_constant(value)?;
+            return self.define(word, value);
+        }
+
+        match word.to_ascii_uppercase().as_str() {
+            "SECTION" => self.section(rest),
+            "DB" => self.data(rest, 1),
+            "DW" => self.data(rest, 2),
+            "DS" => self.reserve(rest),
+            mnemonic => self.instruction(mnemonic, rest)
+        }
+    }
+
+    fn section(&mut self, args: &str) -> Result<(), String> {
+        let args = split_operands(args);
+        if args.len() < 2 || !args[0].starts_with('"') {
+            return Err(String::from("expected SECTION \"name\", TYPE[address], BANK[n]"));
+        }
+
+        // Remember where the current section ended, for floating sections following it
+        self.next.insert((self.region, self.bank), self.addr);
+
+        let (ty, addr) = match args[1].find('[') {
+            Some(i) => (&args[1][..i], Some(args[1][i + 1..].trim_end_matches(']'))),
+            None => (args[1], None)
+        };
+        let region = Region::parse(ty.trim()).ok_or_else(|| format!("unknown section type '{}'", ty))?;
+
+        let mut bank = match region {
+            Region::RomX | Region::WramX => 1,
+            _ => 0
+        };
+        for option in &args[2..] {
+            let upper = option.to_ascii_uppercase();
+            match upper.strip_prefix("BANK[").and_then(|b| b.strip_suffix(']')) {
+                Some(_) => bank = self.eval_constant(&option[5..option.len() - 1])? as usize,
+                None => return Err(format!("unsupported section option '{}'", option))
+            }
+        }
+
+        let (start, end) = region.range();
+        self.region = region;
+        self.bank = bank;
+        self.addr = match addr {
+            Some(addr) => self.eval_constant(addr)? as u32,
+            None => self.next.get(&(region, bank)).copied().unwrap_or(start)
+        };
+
+        if self.addr < start || self.addr >= end {
+            return Err(format!("section address ${:04X} is outside of {:?}", self.addr, region));
+        }
+        if region == Region::Rom0 && bank != 0 {
+            return Err(String::from("ROM0 sections have no bank"));
+        }
+        if region == Region::RomX && bank == 0 {
+            return Err(String::from("ROMX sections start at bank 1"));
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, bytes: &[u8]) -> Result<(), String> {
+        if !self.region.is_rom() {
+            return Err(format!("cannot store data in a {:?} section", self.region));
+        }
+        self.skip(bytes.len() as u32)?;
+
+        let start = match self.region {
+            Region::Rom0 => self.addr as usize,
+            _ => self.bank * ROM_BANK_SIZE + (self.addr as usize - 0x4000)
+        } - bytes.len();
+
+        if self.rom.len() < start + bytes.len() {
+            self.rom.resize(start + bytes.len(), 0);
+        }
+        self.rom[start..start + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn skip(&mut self, count: u32) -> Result<(), String> {
+        self.addr += count;
+        if self.addr > self.region.range().1 {
+            return Err(format!("{:?} section overflows past ${:04X}", self.region, self.region.range().1 - 1));
+        }
+        Ok(())
+    }
+
+    fn check_range(&self, value: i64, min: i64, max: i64) -> Result<(), String> {
+        if self.final_pass && (value < min || value > max) {
+            return Err(format!("value {} is out of range {}..={}", value, min, max));
+        }
+        Ok(())
+    }
+
+    fn data(&mut self, args: &str, size: usize) -> Result<(), String> {
+        for item in split_operands(args) {
+            // Strings are emitted one character per byte or word
+            if item.len() >= 2 && item.starts_with('"') && item.ends_with('"') {
+                for &c in &item.as_bytes()[1..item.len() - 1] {
+                    self.emit(&(c as u16).to_le_bytes()[..size])?;
+                }
+                continue;
+            }
+
+            let value = self.eval(item)?;
+            if size == 1 {
+                self.check_range(value, -0x80, 0xFF)?;
+                self.emit(&[value as u8])?;
+            } else {
+                self.check_range(value, -0x8000, 0xFFFF)?;
+                self.emit(&(value as u16).to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn reserve(&mut self, args: &str) -> Result<(), String> {
+        let args = split_operands(args);
+        let count = self.eval_constant(args.first().ok_or("expected a size")?)? as u32;
+
+        if self.region.is_rom() {
+            let fill = match args.get(1) {
+                Some(fill) => self.eval(fill)? as u8,
+                None => 0
+            };
+            self.emit(&vec![fill; count as usize])
+        } else {
+            self.skip(count)
+        }
+    }
+
+    /// Translates the mnemonic and operands to one of the opcode names, e.g. `ld [hl+], a` to `LDI_HLa_A`
+    fn resolve(&self, mnemonic: &str, operands: &[Operand]) -> Result<(String, Option<(Encoding, String)>), String> {
+        use Operand::*;
+
+        let name = |s: &str| Name(s.to_string());
+        let mut operands = operands.to_vec();
+        let mut mnemonic = mnemonic.to_string();
+
+        // Aliases and forms with an implied operand
+        match (mnemonic.as_str(), operands.as_slice()) {
+            ("RLCA", []) => return Ok((String::from("RLC_A"), None)),
+            ("RRCA", []) => return Ok((String::from("RRC_A"), None)),
+            ("RLA", [])  => return Ok((String::from("RL_A"), None)),
+            ("RRA", [])  => return Ok((String::from("RR_A"), None)),
+            ("RLC" | "RRC" | "RL" | "RR", [Name(a)]) if a == "A" => {
+                return Ok((format!("r{}_A", mnemonic), None));
+            },
+            ("JP", [Name(hl)]) if hl == "HL" => return Ok((String::from("JP_HLa"), None)),
+            ("JP", [Indirect(hl)]) if hl == "HLa" => return Ok((String::from("JP_HLa"), None)),
+            ("STOP", [Immediate(padding)]) => return Ok((String::from("STOP"), Some((Encoding::Byte, padding.clone())))),
+            ("LDI", [Indirect(hl), a]) if hl == "HLa" => operands = vec![HlIncrement, a.clone()],
+            ("LDI", [a, Indirect(hl)]) if hl == "HLa" => operands = vec![a.clone(), HlIncrement],
+            ("LDD", [Indirect(hl), a]) if hl == "HLa" => operands = vec![HlDecrement, a.clone()],
+            ("LDD", [a, Indirect(hl)]) if hl == "HLa" => operands = vec![a.clone(), HlDecrement],
+            ("ADD" | "ADC" | "SUB" | "SBC", [value]) => operands = vec![name("A"), value.clone()],
+            ("AND" | "XOR" | "OR" | "CP", [Name(a), value]) if a == "A" => operands = vec![value.clone()],
+            ("LDHL", [Name(sp), Immediate(d)]) if sp == "SP" => operands = vec![name("HL"), SpOffset(format!("+{}", d))],
+            _ => {}
+        }
+        if mnemonic == "LDI" || mnemonic == "LDD" {
+            mnemonic = String::from("LD");
+        }
+
+        let mut tokens = vec![mnemonic.clone()];
+        let mut immediate = None;
+
+        for operand in &operands {
+            match operand {
+                Name(n) => tokens.push(n.clone()),
+                Indirect(n) if n == "Ca" => {
+                    mnemonic = String::from("LDH");
+                    tokens[0] = mnemonic.clone();
+                    tokens.push(n.clone());
+                },
+                Indirect(n) => tokens.push(n.clone()),
+                HlIncrement | HlDecrement => {
+                    tokens[0] = if let HlIncrement = operand { "LDI" } else { "LDD" }.to_string();
+                    tokens.push(String::from("HLa"));
+                },
+                Memory(expr) if mnemonic == "LDH" => {
+                    tokens.push(String::from("na"));
+                    immediate = Some((Encoding::HighPage, expr.clone()));
+                },
+                Memory(expr) => {
+                    tokens.push(String::from("nna"));
+                    immediate = Some((Encoding::Word, expr.clone()));
+                },
+                SpOffset(expr) => {
+                    // Only "ld hl, sp+d" takes this form
+                    tokens = vec![String::from("LDHL"), String::from("SP"), String::from("d")];
+                    immediate = Some((Encoding::Signed, expr.clone()));
+                },
+                Immediate(expr) => match mnemonic.as_str() {
+                    "RST" => {
+                        let vector = self.eval_constant(expr)?;
+                        tokens.push(format!("{:X}", vector));
+                    },
+                    "BIT" | "RES" | "SET" if immediate.is_none() && tokens.len() == 1 => {
+                        let bit = self.eval_constant(expr)?;
+                        if !(0..8).contains(&bit) {
+                            return Err(format!("bit number {} is out of range 0..=7", bit));
+                        }
+                        tokens.push(bit.to_string());
+                    },
+                    "JR" => {
+                        tokens.push(String::from("n"));
+                        immediate = Some((Encoding::Relative, expr.clone()));
+                    },
+                    _ => {
+                        // The width of the immediate depends on the instruction, try all of them
+                        tokens.push(String::from("?"));
+                        immediate = Some((Encoding::Byte, expr.clone()));
+                    }
+                }
+            }
+        }
+
+        let name = tokens.join("_");
+        if let Some((Encoding::Byte, expr)) = &immediate {
+            for (token, encoding) in [("n", Encoding::Byte), ("nn", Encoding::Word), ("d", Encoding::Signed)] {
+                let candidate = name.replace('?', token);
+                if self.opcodes.contains_key(candidate.as_str()) {
+                    return Ok((candidate, Some((encoding, expr.clone()))));
+                }
+            }
+        }
+
+        Ok((name, immediate))
+    }
+
+    fn instruction(&mut self, mnemonic: &str, args: &str) -> Result<(), String> {
+        let operands: Vec<Operand> = split_operands(args).into_iter().map(parse_operand).collect();
+
+        let (name, immediate) = self.resolve(mnemonic, &operands)?;
+        let &index = self.opcodes.get(name.as_str())
+            .ok_or_else(|| format!("invalid instruction '{} {}'", mnemonic.to_ascii_lowercase(), args))?;
+
+        let mut bytes = if index > 0xFF {
+            vec![0xCB, (index - 0x100) as u8]
+        } else {
+            vec![index as u8]
+        };
+
+        if let Some((encoding, expr)) = immediate {
+            let value = self.eval(&expr)?;
+            match encoding {
+                Encoding::Byte => {
+                    self.check_range(value, -0x80, 0xFF)?;
+                    bytes.push(value as u8);
+                },
+                Encoding::Word => {
+                    self.check_range(value, -0x8000, 0xFFFF)?;
+                    bytes.extend_from_slice(&(value as u16).to_le_bytes());
+                },
+                Encoding::Signed => {
+                    self.check_range(value, -0x80, 0x7F)?;
+                    bytes.push(value as u8);
+                },
+                Encoding::HighPage => {
+                    if self.final_pass && !(0xFF00..=0xFFFF).contains(&value) && !(0x00..=0xFF).contains(&value) {
+                        return Err(format!("ldh address ${:X} is not in $FF00-$FFFF", value));
+                    }
+                    bytes.push(value as u8);
+                },
+                Encoding::Relative => {
+                    let offset = value - (self.addr as i64 + 2);
+                    self.check_range(offset, -0x80, 0x7F)
+                        .map_err(|_| format!("jr target is {} bytes away, out of range", offset))?;
+                    bytes.push(offset as u8);
+                }
+            }
+        }
+
+        // STOP is followed by a padding byte, 0x00 unless given as operand
+        bytes.resize(OPCODE_SIZES[index], 0);
+        self.emit(&bytes)
+    }
+}
@@ -0,0 +1,173 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+/**
+ * Evaluates an RGBDS style expression.
+ * Numbers can be decimal, hexadecimal (`$FF`, `0xFF`), binary (`%1010`, `0b1010`) or characters (`'A'`),
+ * `@` is the current address and any other identifier is looked up with `symbol`.
+ * Supported operators, from lowest to highest precedence: `|`, `^`, `&`, `<< >>`, `+ -`, `* / %`,
+ * unary `- ~ !`, plus the `HIGH()` and `LOW()` functions.
+ */
+pub fn evaluate(expr: &str, symbol: &mut dyn FnMut(&str) -> Result<i64, String>) -> Result<i64, String> {
+    let mut parser = Parser { chars: expr.chars().peekable(), symbol };
+    let value = parser.binary(0)?;
+
+    parser.skip_whitespace();
+    match parser.chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected '{}' in expression", c))
+    }
+}
+
+struct Parser<'a, 'b> {
+    chars: Peekable<Chars<'a>>,
+    symbol: &'b mut dyn FnMut(&str) -> Result<i64, String>
+}
+
+const BINARY_OPERATORS: [&[&str]; 6] = [
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"]
+];
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '#'
+}
+
+impl<'a, 'b> Parser<'a, 'b> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn operator(&mut self, level: usize) -> Option<&'static str> {
+        self.skip_whitespace();
+        let rest: String = self.chars.clone().take(2).collect();
+
+        let op = BINARY_OPERATORS[level].iter()
+            .find(|op| rest.starts_with(**op))?;
+        for _ in 0..op.len() {
+            self.chars.next();
+        }
+        Some(op)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<i64, String> {
+        if level == BINARY_OPERATORS.len() {
+            return self.unary();
+        }
+
+        let mut value = self.binary(level + 1)?;
+        while let Some(op) = self.operator(level) {
+            let rhs = self.binary(level + 1)?;
+            value = match op {
+                "|"  => value | rhs,
+                "^"  => value ^ rhs,
+                "&"  => value & rhs,
+                "<<" => value.checked_shl(rhs as u32).unwrap_or(0),
+                ">>" => value.checked_shr(rhs as u32).unwrap_or(0),
+                "+"  => value.wrapping_add(rhs),
+                "-"  => value.wrapping_sub(rhs),
+                "*"  => value.wrapping_mul(rhs),
+                "/" | "%" if rhs == 0 => return Err(String::from("division by zero")),
+                "/"  => value / rhs,
+                _    => value % rhs
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('-') => { self.chars.next(); Ok(self.unary()?.wrapping_neg()) },
+            Some('+') => { self.chars.next(); self.unary() },
+            Some('~') => { self.chars.next(); Ok(!self.unary()?) },
+            Some('!') => { self.chars.next(); Ok((self.unary()? == 0) as i64) },
+            _ => self.primary()
+        }
+    }
+
+    fn number(&mut self, radix: u32) -> Result<i64, String> {
+        let mut digits = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_digit(radix) || c == '_' {
+                digits.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+
+        let digits = digits.replace('_', "");
+        i64::from_str_radix(&digits, radix).map_err(|_| format!("invalid number '{}'", digits))
+    }
+
+    fn primary(&mut self) -> Result<i64, String> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            Some('(') => {
+                self.chars.next();
+                let value = self.binary(0)?;
+                self.skip_whitespace();
+                match self.chars.next() {
+                    Some(')') => Ok(value),
+                    _ => Err(String::from("missing ')'"))
+                }
+            },
+            Some('$') => { self.chars.next(); self.number(16) },
+            Some('%') => { self.chars.next(); self.number(2) },
+            Some('@') => { self.chars.next(); (self.symbol)("@") },
+            Some('\'') => {
+                self.chars.next();
+                let c = self.chars.next().ok_or("unterminated character")?;
+                match self.chars.next() {
+                    Some('\'') => Ok(c as i64),
+                    _ => Err(String::from("unterminated character"))
+                }
+            },
+            Some('0') => {
+                let prefix: String = self.chars.clone().take(2).collect();
+                match prefix.to_ascii_lowercase().as_str() {
+                    "0x" => { self.chars.nth(1); self.number(16) },
+                    "0b" => { self.chars.nth(1); self.number(2) },
+                    _ => self.number(10)
+                }
+            },
+            Some(c) if c.is_ascii_digit() => self.number(10),
+            Some(c) if is_identifier_char(c) => {
+                let mut name = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if !is_identifier_char(c) {
+                        break;
+                    }
+                    name.push(c);
+                    self.chars.next();
+                }
+
+                self.skip_whitespace();
+                if self.chars.peek() == Some(&'(') {
+                    self.chars.next();
+                    let value = self.binary(0)?;
+                    self.skip_whitespace();
+                    if self.chars.next() != Some(')') {
+                        return Err(String::from("missing ')'"));
+                    }
+                    return match name.to_ascii_uppercase().as_str() {
+                        "HIGH" => Ok((value >> 8) & 0xFF),
+                        "LOW"  => Ok(value & 0xFF),
+                        _ => Err(format!("unknown function '{}'", name))
+                    };
+                }
+
+                (self.symbol)(&name)
+            },
+            Some(c) => Err(format!("unexpected '{}' in expression", c)),
+            None => Err(String::from("missing operand"))
+        }
+    }
+}
@@ -0,0 +1,34 @@
+/**
+ * Encodes stereo samples as a 16-bit PCM WAV file.
+ * Samples outside [-1, 1] are clamped, the full range maps to -32767..=32767.
+ */
+pub fn wav(samples: &[(f32, f32)], sample_rate: u32) -> Vec<u8> {
+    const CHANNELS: u16 = 2;
+    const BITS_PER_SAMPLE: u16 = 16;
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let data_size = samples.len() as u32 * block_align as u32;
+
+    let mut file = Vec::with_capacity(44 + data_size as usize);
+    file.extend_from_slice(b"RIFF");
+    file.extend_from_slice(&(36 + data_size).to_le_bytes());
+    file.extend_from_slice(b"WAVE");
+
+    file.extend_from_slice(b"fmt ");
+    file.extend_from_slice(&16u32.to_le_bytes());
+    file.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    file.extend_from_slice(&CHANNELS.to_le_bytes());
+    file.extend_from_slice(&sample_rate.to_le_bytes());
+    file.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    file.extend_from_slice(&block_align.to_le_bytes());
+    file.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+
+    file.extend_from_slice(b"data");
+    file.extend_from_slice(&data_size.to_le_bytes());
+    for (left, right) in samples {
+        for sample in [left, right] {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            file.extend_from_slice(&sample.to_le_bytes());
+        }
+    }
+    file
+}
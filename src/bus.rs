@@ -0,0 +1,332 @@
+use crate::apu::{Apu, NR10_ADDR, WAVE_RAM_END};
+use crate::cartridge::{Cartridge, CartridgeError};
+use crate::cpu::MemoryBus;
+use crate::dma::{OamDma, DMA_ADDR};
+use crate::hdma::{Hdma, HDMA1_ADDR, HDMA5_ADDR, HDMA_BLOCK_SIZE};
+use crate::interrupt::{Interrupt, Interrupts, IE_ADDR, IF_ADDR};
+use crate::mbc::Mapper;
+use crate::model::Model;
+use crate::ppu::{Ppu, BCPS_ADDR, LCDC_ADDR, OPRI_ADDR, VBK_ADDR, WX_ADDR};
+use crate::timer::{Timer, DIV_ADDR, TAC_ADDR};
+
+pub const WRAM_SIZE: usize = 0x2000;
+// 8 banks of 4 KiB, the first always at 0xC000 and any other at 0xD000
+pub const CGB_WRAM_SIZE: usize = 0x8000;
+const WRAM_BANK_SIZE: usize = 0x1000;
+pub const HRAM_SIZE: usize = 0x7F;
+
+/*
+    Bits of the I/O registers which do not exist and always read as 1, indexed by address - 0xFF00.
+    Addresses without any register read as 0xFF, as do the write-only sound registers, wave RAM has none.
+*/
+const IO_UNUSED_BITS: [u8; 0x80] = [
+//  0     1     2     3     4     5     6     7     8     9     A     B     C     D     E     F
+    0xC0, 0x00, 0x7E, 0xFF, 0x00, 0x00, 0x00, 0xF8, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xE0, // 0xFF00
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, 0xFF, 0x3F, 0x00, 0xFF, 0xBF, 0x7F, 0xFF, 0x9F, 0xFF, 0xBF, 0xFF, // 0xFF10
+    0xFF, 0x00, 0x00, 0xBF, 0x00, 0x00, 0x70, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // 0xFF20
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0xFF30
+    0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, // 0xFF40
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // 0xFF50
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // 0xFF60
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF  // 0xFF70
+];
+
+pub const P1_ADDR: u16 = 0xFF00;
+pub const KEY1_ADDR: u16 = 0xFF4D;
+pub const SVBK_ADDR: u16 = 0xFF70;
+
+// The I/O registers without their own device as the DMG boot ROM leaves them, the rest are 0
+const IO_POST_BOOT: [(u16, u8); 1] = [
+    (0xFF00, 0xCF)
+];
+
+/**
+ * Routes the CPU address space to the cartridge and the memories of the console:
+ *
+ * 0x0000-0x7FFF    Cartridge ROM
+ * 0x8000-0x9FFF    VRAM
+ * 0xA000-0xBFFF    Cartridge RAM
+ * 0xC000-0xDFFF    WRAM, in CGB mode 0xD000-0xDFFF maps one of banks 1-7 selected by SVBK
+ * 0xE000-0xFDFF    Echo of 0xC000-0xDDFF
+ * 0xFE00-0xFE9F    OAM
+ * 0xFEA0-0xFEFF    Unusable, reads 0x00 (0xFF while OAM is blocked) and ignores writes
+ * 0xFF00-0xFF7F    I/O registers
+ * 0xFF80-0xFFFE    HRAM
+ * 0xFFFF           IE
+ */
+pub struct Bus {
+    model: Model,
+    mapper: Box<dyn Mapper>,
+    wram: Vec<u8>,
+    wram_bank: u8,
+    io: [u8; 0x80],
+    hram: [u8; HRAM_SIZE],
+    interrupts: Interrupts,
+    timer: Timer,
+    ppu: Ppu,
+    apu: Apu,
+    oam_dma: OamDma,
+    hdma: Hdma,
+
+    // KEY1, in double speed the CPU and everything clocked by it run twice as fast, the PPU and APU do not
+    double_speed: bool,
+    speed_switch_armed: bool
+}
+
+impl Bus {
+    pub fn new(cart: &Cartridge, model: Model) -> Result<Bus, CartridgeError> {
+        Ok(Bus::with_model(cart.mapper()?, model))
+    }
+
+    /// Creates a bus in the state the DMG boot ROM leaves it in
+    pub fn with_mapper(mapper: Box<dyn Mapper>) -> Bus {
+        Bus::with_model(mapper, Model::Dmg)
+    }
+
+    /// Creates a bus in the state the boot ROM of the model leaves it in
+    pub fn with_model(mapper: Box<dyn Mapper>, model: Model) -> Bus {
+        let mut io = [0; 0x80];
+        for (addr, value) in IO_POST_BOOT {
+            io[(addr - 0xFF00) as usize] = value;
+        }
+
+        Bus {
+            model,
+            mapper,
+            wram: vec![0; if model.is_cgb() { CGB_WRAM_SIZE } else { WRAM_SIZE }],
+            wram_bank: 1,
+            io,
+            hram: [0; HRAM_SIZE],
+            // The VBlank interrupt of the last boot ROM frame is still requested
+            interrupts: Interrupts { enable: 0, flags: Interrupt::VBlank.bit() },
+            timer: Timer::new(),
+            ppu: Ppu::with_model(model),
+            apu: Apu::new(),
+            oam_dma: OamDma::new(),
+            hdma: Hdma::new(),
+            double_speed: false,
+            speed_switch_armed: false
+        }
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    pub fn mapper(&self) -> &dyn Mapper {
+        self.mapper.as_ref()
+    }
+
+    pub fn mapper_mut(&mut self) -> &mut dyn Mapper {
+        self.mapper.as_mut()
+    }
+
+    pub fn interrupts(&self) -> &Interrupts {
+        &self.interrupts
+    }
+
+    pub fn interrupts_mut(&mut self) -> &mut Interrupts {
+        &mut self.interrupts
+    }
+
+    pub fn timer(&self) -> &Timer {
+        &self.timer
+    }
+
+    pub fn ppu(&self) -> &Ppu {
+        &self.ppu
+    }
+
+    pub fn ppu_mut(&mut self) -> &mut Ppu {
+        &mut self.ppu
+    }
+
+    pub fn apu(&self) -> &Apu {
+        &self.apu
+    }
+
+    pub fn apu_mut(&mut self) -> &mut Apu {
+        &mut self.apu
+    }
+
+    pub fn double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    /// The offset in WRAM of an address in 0xC000-0xDFFF
+    fn wram_offset(&self, addr: u16) -> usize {
+        match addr {
+            0xC000..=0xCFFF => (addr - 0xC000) as usize,
+            _ => self.wram_bank as usize * WRAM_BANK_SIZE + (addr - 0xD000) as usize
+        }
+    }
+
+    /// The DIV bit whose falling edge steps the APU frame sequencer at 512 Hz, bit 4 or bit 5 in double speed
+    fn frame_sequencer_bit(&self) -> bool {
+        let bit = if self.double_speed { 13 } else { 12 };
+        self.timer.counter() & (1 << bit) != 0
+    }
+
+    /// Writes a timer register, resetting DIV steps the frame sequencer if its bit was set
+    fn write_timer(&mut self, addr: u16, value: u8) {
+        let frame_sequencer_bit = self.frame_sequencer_bit();
+        self.timer.write(addr, value);
+        if frame_sequencer_bit && !self.frame_sequencer_bit() {
+            self.apu.step_frame_sequencer();
+        }
+    }
+
+    /// Advances everything but the CPU by a machine cycle
+    fn tick_peripherals(&mut self) {
+        let frame_sequencer_bit = self.frame_sequencer_bit();
+        self.timer.tick(&mut self.interrupts);
+        if frame_sequencer_bit && !self.frame_sequencer_bit() {
+            self.apu.step_frame_sequencer();
+        }
+
+        if let Some((source, index)) = self.oam_dma.tick() {
+            let value = self.read_memory(source);
+            self.oam_dma.copied(value);
+            self.ppu.write_oam_dma(index, value);
+        }
+
+        // A machine cycle lasts half as many dots in double speed
+        let dots = if self.double_speed { 2 } else { 4 };
+        self.ppu.tick(&mut self.interrupts, dots);
+        self.apu.tick(dots);
+    }
+
+    /**
+     * Copies the next HDMA block to VRAM, stalling the CPU for the machine cycles it takes:
+     * 2 bytes are copied per cycle, 1 in double speed as the copy runs at the normal speed.
+     */
+    fn copy_hdma_block(&mut self) {
+        let (source, destination) = self.hdma.next_block();
+        let bytes_per_cycle = if self.double_speed { 1 } else { 2 };
+
+        for offset in 0..HDMA_BLOCK_SIZE {
+            if offset % bytes_per_cycle == 0 {
+                self.tick_peripherals();
+            }
+            let value = self.read_memory(source.wrapping_add(offset));
+            self.ppu.write_vram(destination + offset, value);
+        }
+    }
+
+    fn read_io(&self, addr: u16) -> u8 {
+        let cgb = self.model.is_cgb();
+        match addr {
+            // No input is connected, every button in the selected rows reads as released
+            P1_ADDR => 0xC0 | (self.io[0] & 0x30) | 0x0F,
+            DIV_ADDR..=TAC_ADDR => self.timer.read(addr),
+            NR10_ADDR..=WAVE_RAM_END => self.apu.read(addr) | IO_UNUSED_BITS[(addr - 0xFF00) as usize],
+            DMA_ADDR => self.oam_dma.read(),
+            LCDC_ADDR..=WX_ADDR => self.ppu.read(addr),
+            HDMA1_ADDR..=HDMA5_ADDR if cgb => self.hdma.read(addr),
+            KEY1_ADDR if cgb => 0x7E | ((self.double_speed as u8) << 7) | self.speed_switch_armed as u8,
+            VBK_ADDR | BCPS_ADDR..=OPRI_ADDR if cgb => self.ppu.read(addr),
+            SVBK_ADDR if cgb => 0xF8 | self.wram_bank,
+            IF_ADDR => self.interrupts.read_flags(),
+            _ => {
+                let index = (addr - 0xFF00) as usize;
+                self.io[index] | IO_UNUSED_BITS[index]
+            }
+        }
+    }
+
+    fn write_io(&mut self, addr: u16, value: u8) {
+        let cgb = self.model.is_cgb();
+        match addr {
+            // Only the row select bits are writable
+            P1_ADDR => self.io[0] = value & 0x30,
+            DIV_ADDR..=TAC_ADDR => self.write_timer(addr, value),
+            NR10_ADDR..=WAVE_RAM_END => self.apu.write(addr, value),
+            DMA_ADDR => self.oam_dma.write(value),
+            LCDC_ADDR..=WX_ADDR => self.ppu.write(addr, value),
+            HDMA1_ADDR..=HDMA5_ADDR if cgb => self.hdma.write(addr, value),
+            KEY1_ADDR if cgb => self.speed_switch_armed = value & 0x01 != 0,
+            VBK_ADDR | BCPS_ADDR..=OPRI_ADDR if cgb => self.ppu.write(addr, value),
+            // Selecting bank 0 selects bank 1
+            SVBK_ADDR if cgb => self.wram_bank = (value & 0x07).max(1),
+            IF_ADDR => self.interrupts.write_flags(value),
+            _ => self.io[(addr - 0xFF00) as usize] = value
+        }
+    }
+
+    /// Reads the address space without the restrictions of the CPU during OAM DMA
+    fn read_memory(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => self.mapper.read(addr),
+            0x8000..=0x9FFF => self.ppu.read_vram(addr),
+            0xA000..=0xBFFF => self.mapper.read(addr),
+            0xC000..=0xDFFF => self.wram[self.wram_offset(addr)],
+            0xE000..=0xFDFF => self.wram[self.wram_offset(addr - 0x2000)],
+            0xFE00..=0xFE9F => self.ppu.read_oam(addr),
+            0xFEA0..=0xFEFF => if self.ppu.oam_blocked() { 0xFF } else { 0x00 },
+            0xFF00..=0xFF7F => self.read_io(addr),
+            0xFF80..=0xFFFE => self.hram[(addr - 0xFF80) as usize],
+            IE_ADDR         => self.interrupts.enable
+        }
+    }
+
+    fn write_memory(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x7FFF => self.mapper.write(addr, value),
+            0x8000..=0x9FFF => self.ppu.write_vram(addr, value),
+            0xA000..=0xBFFF => self.mapper.write(addr, value),
+            0xC000..=0xDFFF => {
+                let offset = self.wram_offset(addr);
+                self.wram[offset] = value;
+            },
+            0xE000..=0xFDFF => {
+                let offset = self.wram_offset(addr - 0x2000);
+                self.wram[offset] = value;
+            },
+            0xFE00..=0xFE9F => self.ppu.write_oam(addr, value),
+            0xFEA0..=0xFEFF => {},
+            0xFF00..=0xFF7F => self.write_io(addr, value),
+            0xFF80..=0xFFFE => self.hram[(addr - 0xFF80) as usize] = value,
+            IE_ADDR         => self.interrupts.enable = value
+        }
+    }
+}
+
+impl MemoryBus for Bus {
+    fn read(&mut self, addr: u16) -> u8 {
+        // During OAM DMA the CPU cannot read OAM, nor the bus the transfer is using
+        if let Some(value) = self.oam_dma.conflict(addr) {
+            return value;
+        }
+        self.read_memory(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if self.oam_dma.conflict(addr).is_some() {
+            return;
+        }
+        self.write_memory(addr, value);
+    }
+
+    fn tick(&mut self) {
+        self.tick_peripherals();
+
+        // VRAM DMA runs between CPU cycles, which wait for it to be done
+        while self.hdma.general_purpose() {
+            self.copy_hdma_block();
+        }
+        if self.ppu.take_hblank() && self.hdma.hblank() {
+            self.copy_hdma_block();
+        }
+    }
+
+    fn stop(&mut self) -> bool {
+        self.write_timer(DIV_ADDR, 0);
+
+        if self.model.is_cgb() && self.speed_switch_armed {
+            self.double_speed = !self.double_speed;
+            self.speed_switch_armed = false;
+            return true;
+        }
+        false
+    }
+}
@@ -1,17 +1,60 @@
-use std::{fs, fmt::Display};
-use anyhow::Error;
-use num_enum::TryFromPrimitive;
+use std::{fs, fmt::Display, io};
+use num_enum::FromPrimitive;
+use crate::mbc::{self, Mapper};
 
-const NINTENDO_LOGO: [u8; 48] = [
+pub(crate) const NINTENDO_LOGO: [u8; 48] = [
     0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
     0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
     0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E
 ];
 
+// The header spans 0x100..0x150, anything shorter cannot be a Game Boy ROM
+const HEADER_END: usize = 0x150;
+
+#[derive(Debug)]
+pub enum CartridgeError {
+    Io(io::Error),
+    TooShort(usize),
+    UnknownCartridgeType(u8),
+    UnknownCGBFlag(u8),
+    UnknownSGBFlag(u8),
+    UnsupportedRomSize(u8),
+    UnsupportedRamSize(u8),
+    UnsupportedMapper(CartridgeType)
+}
+
+impl Display for CartridgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CartridgeError::Io(e)                    => write!(f, "{}", e),
+            CartridgeError::TooShort(len)            => write!(f, "file is too short to contain a header ({} bytes, need at least {})", len, HEADER_END),
+            CartridgeError::UnknownCartridgeType(ty) => write!(f, "unknown cartridge type {:#04X}", ty),
+            CartridgeError::UnknownCGBFlag(flag)     => write!(f, "unknown CGB flag {:#04X}", flag),
+            CartridgeError::UnknownSGBFlag(flag)     => write!(f, "unknown SGB flag {:#04X}", flag),
+            CartridgeError::UnsupportedRomSize(code) => write!(f, "unsupported ROM size code {:#04X}", code),
+            CartridgeError::UnsupportedRamSize(code) => write!(f, "unsupported RAM size code {:#04X}", code),
+            CartridgeError::UnsupportedMapper(ty)    => write!(f, "unsupported cartridge type {}", ty)
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CartridgeError::Io(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for CartridgeError {
+    fn from(e: io::Error) -> Self {
+        CartridgeError::Io(e)
+    }
+}
+
 pub struct Cartridge {
     // Header
-    // TODO: Should these be slices instead of arrays??
-    entry: [u8; 4], 
     logo: [u8; 48],
     title: [u8; 16],
     manufacturer: u32,
@@ -32,9 +75,17 @@ pub struct Cartridge {
 
 impl Cartridge {
 
-    fn new(data: Vec<u8>) -> Cartridge {
-        Cartridge {
-            entry:  data[0x100..0x104].try_into().unwrap(),
+    /**
+     * Parses the header of the given ROM image.
+     * In lenient mode unknown header values are kept as `Unknown` variants instead of being rejected,
+     * only a file too short to contain a header is still an error.
+     */
+    fn new(data: Vec<u8>, lenient: bool) -> Result<Cartridge, CartridgeError> {
+        if data.len() < HEADER_END {
+            return Err(CartridgeError::TooShort(data.len()));
+        }
+
+        let cart = Cartridge {
             logo:   data[0x104..0x134].try_into().unwrap(),
             title:  data[0x134..0x144].try_into().unwrap(),
 
@@ -43,12 +94,12 @@ impl Cartridge {
             manufacturer:   ((data[0x13F] as u32) << 24) +
                             ((data[0x140] as u32) << 16) +
                             ((data[0x141] as u32) << 8) +
-                            ((data[0x142] as u32) << 0),
-            cgb_flag:       CGBMode::try_from(data[0x143]).unwrap(),
+                            (data[0x142] as u32),
+            cgb_flag:       CGBMode::from(data[0x143]),
 
             new_licensee_code: data[0x144..0x146].try_into().unwrap(),
-            sgb_flag:       SGBMode::try_from(data[0x146]).unwrap(),
-            ty:             CartridgeType::try_from(data[0x147]).unwrap(),
+            sgb_flag:       SGBMode::from(data[0x146]),
+            ty:             CartridgeType::from(data[0x147]),
             rom_size:       data[0x148],
             ram_size:       data[0x149],
             dest_code:      data[0x14A],
@@ -57,22 +108,85 @@ impl Cartridge {
 
             header_checksum:    data[0x14D],
             global_checksum:    ((data[0x14E] as u16) << 8) +
-                                ((data[0x14F] as u16) << 0),
+                                (data[0x14F] as u16),
 
             data
-        }   
+        };
+
+        if !lenient {
+            cart.validate()?;
+        }
+
+        Ok(cart)
+    }
+
+    fn validate(&self) -> Result<(), CartridgeError> {
+        if let CartridgeType::Unknown(ty) = self.ty {
+            return Err(CartridgeError::UnknownCartridgeType(ty));
+        }
+        if let CGBMode::Unknown(flag) = self.cgb_flag {
+            return Err(CartridgeError::UnknownCGBFlag(flag));
+        }
+        if let SGBMode::Unknown(flag) = self.sgb_flag {
+            return Err(CartridgeError::UnknownSGBFlag(flag));
+        }
+        if self.rom_size_bytes().is_none() {
+            return Err(CartridgeError::UnsupportedRomSize(self.rom_size));
+        }
+        if self.ram_size_bytes().is_none() {
+            return Err(CartridgeError::UnsupportedRamSize(self.ram_size));
+        }
+        Ok(())
     }
 
-    pub fn from_file(path: &str) -> Result<Cartridge, Error> {
-        let data = fs::read(path)?;
-        
-        Ok(Cartridge::new(data))
+    pub fn from_bytes(data: Vec<u8>) -> Result<Cartridge, CartridgeError> {
+        Cartridge::new(data, false)
+    }
+
+    pub fn from_bytes_lenient(data: Vec<u8>) -> Result<Cartridge, CartridgeError> {
+        Cartridge::new(data, true)
+    }
+
+    pub fn from_file(path: &str) -> Result<Cartridge, CartridgeError> {
+        Cartridge::from_bytes(fs::read(path)?)
+    }
+
+    pub fn from_file_lenient(path: &str) -> Result<Cartridge, CartridgeError> {
+        Cartridge::from_bytes_lenient(fs::read(path)?)
+    }
+
+    pub fn ty(&self) -> CartridgeType {
+        self.ty
+    }
+
+    pub fn cgb_flag(&self) -> CGBMode {
+        self.cgb_flag
+    }
+
+    /// Whether the game makes use of the CGB features when running on one
+    pub fn supports_cgb(&self) -> bool {
+        matches!(self.cgb_flag, CGBMode::CGBSupport | CGBMode::CGBOnly)
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /**
+     * Creates the memory bank controller matching the cartridge type,
+     * initialized with a copy of the ROM and blank external RAM.
+     */
+    pub fn mapper(&self) -> Result<Box<dyn Mapper>, CartridgeError> {
+        mbc::from_cartridge(self)
     }
 
     pub fn new_licensee_code_name(&self) -> &str {
-        let idx = ((self.new_licensee_code[0] - b'0') * 10 + (self.new_licensee_code[1] - b'0')) as usize;
-        if idx < 100 {
-            NEW_LICENSEE_CODE_NAMES[idx]
+        let digits = (
+            (self.new_licensee_code[0] as char).to_digit(10),
+            (self.new_licensee_code[1] as char).to_digit(10)
+        );
+        if let (Some(hi), Some(lo)) = digits {
+            NEW_LICENSEE_CODE_NAMES[(hi * 10 + lo) as usize]
         } else if self.new_licensee_code[0] == b'A' && self.new_licensee_code[1] == b'4' {
             "Konami (Yu-Gi-Oh!)"
         } else {
@@ -99,15 +213,20 @@ impl Cartridge {
             Some(((32 * bytesize::KIB) << self.rom_size) as usize)
         } else {
             match self.rom_size {
-                52 => Some((1.1 * (bytesize::MIB as f64)) as usize),
-                53 => Some((1.2 * (bytesize::MIB as f64)) as usize),
-                54 => Some((1.5 * (bytesize::MIB as f64)) as usize),
-                _  => None
+                0x52 => Some((1.1 * (bytesize::MIB as f64)) as usize),
+                0x53 => Some((1.2 * (bytesize::MIB as f64)) as usize),
+                0x54 => Some((1.5 * (bytesize::MIB as f64)) as usize),
+                _    => None
             }
         }
     }
 
     pub fn ram_size_bytes(&self) -> Option<usize> {
+        // MBC2 has its RAM built into the controller and the header reports no RAM
+        if let CartridgeType::MBC2 | CartridgeType::MBC2Battery = self.ty {
+            return Some(mbc::MBC2_RAM_SIZE);
+        }
+
         match self.ram_size {
             0 => Some(0),
             1 => None,
@@ -118,6 +237,104 @@ impl Cartridge {
             _ => None
         }
     }
+
+    /**
+     * The header checksum covers the bytes 0x134..=0x14C,
+     * the boot ROM refuses to start the cartridge if it does not match.
+     */
+    pub fn compute_header_checksum(&self) -> u8 {
+        header_checksum(&self.data)
+    }
+
+    /**
+     * The global checksum is the 16-bit sum of every byte in the ROM except the checksum itself.
+     * No hardware checks it, but it is still useful to detect corrupted dumps.
+     */
+    pub fn compute_global_checksum(&self) -> u16 {
+        global_checksum(&self.data)
+    }
+
+    pub fn verify(&self) -> VerificationReport {
+        VerificationReport {
+            logo_ok: self.logo == NINTENDO_LOGO,
+            header_checksum: self.header_checksum,
+            computed_header_checksum: self.compute_header_checksum(),
+            global_checksum: self.global_checksum,
+            computed_global_checksum: self.compute_global_checksum()
+        }
+    }
+
+    /**
+     * Returns a copy of the ROM image with the logo and both checksums corrected, like rgbfix does.
+     * The global checksum is computed last as it also covers the other fixed fields.
+     */
+    pub fn fixed_data(&self) -> Vec<u8> {
+        let mut data = self.data.clone();
+
+        data[0x104..0x134].copy_from_slice(&NINTENDO_LOGO);
+        data[0x14D] = header_checksum(&data);
+
+        let global = global_checksum(&data);
+        data[0x14E] = (global >> 8) as u8;
+        data[0x14F] = global as u8;
+
+        data
+    }
+
+    pub fn write_fixed(&self, path: &str) -> Result<(), CartridgeError> {
+        fs::write(path, self.fixed_data())?;
+        Ok(())
+    }
+}
+
+fn header_checksum(data: &[u8]) -> u8 {
+    data[0x134..=0x14C].iter()
+        .fold(0u8, |acc, &b| acc.wrapping_sub(b).wrapping_sub(1))
+}
+
+fn global_checksum(data: &[u8]) -> u16 {
+    data.iter()
+        .enumerate()
+        .filter(|(i, _)| *i != 0x14E && *i != 0x14F)
+        .fold(0u16, |acc, (_, &b)| acc.wrapping_add(b as u16))
+}
+
+pub struct VerificationReport {
+    pub logo_ok: bool,
+    pub header_checksum: u8,
+    pub computed_header_checksum: u8,
+    pub global_checksum: u16,
+    pub computed_global_checksum: u16
+}
+
+impl VerificationReport {
+    pub fn header_checksum_ok(&self) -> bool {
+        self.header_checksum == self.computed_header_checksum
+    }
+
+    pub fn global_checksum_ok(&self) -> bool {
+        self.global_checksum == self.computed_global_checksum
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.logo_ok && self.header_checksum_ok() && self.global_checksum_ok()
+    }
+}
+
+impl Display for VerificationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let status = |ok: bool| if ok { "OK" } else { "MISMATCH" };
+
+        write!(f,
+            "logo: {}\n\
+             header_checksum: {} (stored {:#04X}, computed {:#04X})\n\
+             global_checksum: {} (stored {:#06X}, computed {:#06X})\n\
+            ",
+            status(self.logo_ok),
+            status(self.header_checksum_ok()), self.header_checksum, self.computed_header_checksum,
+            status(self.global_checksum_ok()), self.global_checksum, self.computed_global_checksum
+        )
+    }
 }
 
 impl std::fmt::Display for Cartridge {
@@ -146,8 +363,8 @@ impl std::fmt::Display for Cartridge {
             self.cgb_flag,
             self.sgb_flag,
             self.ty,
-            bytesize::to_string(self.rom_size_bytes().unwrap() as u64, true), self.rom_size,
-            bytesize::to_string(self.ram_size_bytes().unwrap() as u64, true), self.ram_size,
+            size_to_string(self.rom_size_bytes()), self.rom_size,
+            size_to_string(self.ram_size_bytes()), self.ram_size,
             self.destination_name(), self.dest_code,
             if new {"new"} else {"old"}, licensee_name, licensee_code,
             self.version
@@ -155,12 +372,21 @@ impl std::fmt::Display for Cartridge {
     }
 }
 
-#[derive(TryFromPrimitive)]
+fn size_to_string(size: Option<usize>) -> String {
+    match size {
+        Some(size) => bytesize::to_string(size as u64, true),
+        None       => String::from("Unknown")
+    }
+}
+
+#[derive(FromPrimitive, Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(u8)]
-enum CGBMode {
+pub enum CGBMode {
     Disabled   = 0x00,
     CGBSupport = 0x80,  // Works on GBC as well as original Game Boy
-    CGBOnly    = 0xC0   // Only works on GBC
+    CGBOnly    = 0xC0,  // Only works on GBC
+    #[num_enum(catch_all)]
+    Unknown(u8)
 }
 
 impl Display for CGBMode {
@@ -168,30 +394,38 @@ impl Display for CGBMode {
         match self {
             CGBMode::Disabled   => write!(f, "Disabled"),
             CGBMode::CGBSupport => write!(f, "Color Game Boy Support"),
-            CGBMode::CGBOnly    => write!(f, "Color Game Boy Only")
+            CGBMode::CGBOnly    => write!(f, "Color Game Boy Only"),
+            CGBMode::Unknown(v) => write!(f, "Unknown ({:#04X})", v)
         }
     }
 }
 
-#[derive(TryFromPrimitive)]
+#[derive(FromPrimitive, Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(u8)]
 enum SGBMode {
     Disabled = 0x00,
-    Enabled  = 0x03
+    Enabled  = 0x03,
+    #[num_enum(catch_all)]
+    Unknown(u8)
 }
 
 impl Display for SGBMode {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             SGBMode::Disabled => write!(f, "Disabled"),
-            SGBMode::Enabled  => write!(f, "Enabled")
+            SGBMode::Enabled  => write!(f, "Enabled"),
+            SGBMode::Unknown(v) => write!(f, "Unknown ({:#04X})", v)
         }
     }
 }
 
-#[derive(TryFromPrimitive)]
+#[derive(FromPrimitive, Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(u8)]
 pub enum CartridgeType {
+    PocketCamera = 0xFC,
+    BandaiTama5 = 0xFD,
+    HuC3 = 0xFE,
+    HuC1RamBattery = 0xFF,
     RomOnly = 0x00,
     MBC1 = 0x01,
     MBC1Ram = 0x02,
@@ -216,10 +450,31 @@ pub enum CartridgeType {
     MBC5RumbleRamBattery = 0x1E,
     MBC6 = 0x20,
     MBC7SensorRumbleRamBattery = 0x22,
-    PocketCamera = 0xFC,
-    BandaiTama5 = 0xFD,
-    HuC3 = 0xFE,
-    HuC1RamBattery = 0xFF
+    // Any unrecognized type byte, the catch-all has to come last and must not follow 0xFF
+    #[num_enum(catch_all)]
+    Unknown(u8)
+}
+
+impl CartridgeType {
+    pub fn has_battery(&self) -> bool {
+        matches!(self,
+            CartridgeType::MBC1RamBattery |
+            CartridgeType::MBC2Battery |
+            CartridgeType::RomRamBattery |
+            CartridgeType::MMM01RamBattery |
+            CartridgeType::MBC3TimerBattery |
+            CartridgeType::MBC3TimerRamBattery |
+            CartridgeType::MBC3RamBattery |
+            CartridgeType::MBC5RamBattery |
+            CartridgeType::MBC5RumbleRamBattery |
+            CartridgeType::MBC7SensorRumbleRamBattery |
+            CartridgeType::HuC1RamBattery
+        )
+    }
+
+    pub fn has_timer(&self) -> bool {
+        matches!(self, CartridgeType::MBC3TimerBattery | CartridgeType::MBC3TimerRamBattery)
+    }
 }
 
 impl Display for CartridgeType {
@@ -252,7 +507,8 @@ impl Display for CartridgeType {
             CartridgeType::PocketCamera               => write!(f, "POCKET CAMERA"),
             CartridgeType::BandaiTama5                => write!(f, "BANDAI TAMA5"),
             CartridgeType::HuC3                       => write!(f, "HuC3"),
-            CartridgeType::HuC1RamBattery             => write!(f, "HuC1 + RAM + BATTERY")
+            CartridgeType::HuC1RamBattery             => write!(f, "HuC1 + RAM + BATTERY"),
+            CartridgeType::Unknown(v)                 => write!(f, "Unknown ({:#04X})", v)
         }
     }
 }
@@ -265,7 +521,7 @@ impl Display for CartridgeType {
  * Only the last licensee does not respect the criteria, which is included anyway for completeness.
  * The special value "Unknown" is used to fill the gaps of unknown or unused licensee codes.
  */
-const NEW_LICENSEE_CODE_NAMES: &'static [&'static str] = &[
+const NEW_LICENSEE_CODE_NAMES: &[&str] = &[
 //  0             1                2               3                  4               5                        6                           7                   8              9
     "None",       "Nintendo R&D1", "Unknown",      "Unknown",         "Unknown",      "Unknown",               "Unknown",                  "Unknown",          "Capcom",      "Unknown",
     "Unknown",    "Unknown",       "Unknown",      "Electronic Arts", "Unknown",      "Unknown",               "Unknown",                  "Unknown",          "Hudson Soft", "b-ai",
@@ -290,7 +546,7 @@ const NEW_LICENSEE_CODE_NAMES: &'static [&'static str] = &[
  * The special value "Unknown" is used to fill the gaps of unknown or unused licensee codes.
  * The code 0x33 is a special value indicating that the new licensee code should be used instead.
  */
-const LICENSEE_CODE_NAMES: &'static [&'static str] = &[
+const LICENSEE_CODE_NAMES: &[&str] = &[
 //  0x0                     0x1                0x2             0x3                      0x4                 0x5                0x6                 0x7                0x8                 0x9                  0xA                      0xB                      0xC                  0xD               0xE                0xF
     "none",                 "nintendo",        "Unknown",      "Unknown",               "Unknown",          "Unknown",         "Unknown",          "Unknown",         "capcom",           "hot-b",             "jaleco",                "coconuts",              "elite systems",     "Unknown",        "Unknown",         "Unknown",
     "Unknown",              "Unknown",         "Unknown",      "electronic arts",       "Unknown",          "Unknown",         "Unknown",          "Unknown",         "hudsonsoft",       "itc entertainment", "yanoman",               "Unknown",               "Unknown",           "clary",          "Unknown",         "virgin",
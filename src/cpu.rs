@@ -0,0 +1,779 @@
+use crate::instruction::{self, Condition, Instruction, Memory, Operand, Operation, Register, RegisterPair};
+use crate::interrupt::{Interrupt, IE_ADDR, IF_ADDR, INTERRUPT_MASK};
+use crate::model::Model;
+use crate::opcode::Opcode;
+
+pub const FLAG_Z: u8 = 0x80;    // Zero
+pub const FLAG_N: u8 = 0x40;    // Subtraction
+pub const FLAG_H: u8 = 0x20;    // Half carry
+pub const FLAG_C: u8 = 0x10;    // Carry
+
+// Machine cycles the CPU stays paused for after STOP switched speed
+const SPEED_SWITCH_CYCLES: u16 = 2050;
+
+/**
+ * Everything the CPU is connected to.
+ * The CPU calls `tick` once for every machine cycle it spends, right before the memory access
+ * of that cycle if there is one, so the rest of the system can be kept in sync with the CPU.
+ */
+pub trait MemoryBus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, value: u8);
+    fn tick(&mut self) {}
+
+    /// Called by STOP, returns true if it switched the CPU speed rather than letting the CPU stop
+    fn stop(&mut self) -> bool {
+        false
+    }
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct Registers {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16
+}
+
+impl Registers {
+    pub fn af(&self) -> u16 {
+        ((self.a as u16) << 8) | self.f as u16
+    }
+
+    pub fn bc(&self) -> u16 {
+        ((self.b as u16) << 8) | self.c as u16
+    }
+
+    pub fn de(&self) -> u16 {
+        ((self.d as u16) << 8) | self.e as u16
+    }
+
+    pub fn hl(&self) -> u16 {
+        ((self.h as u16) << 8) | self.l as u16
+    }
+
+    pub fn set_af(&mut self, value: u16) {
+        self.a = (value >> 8) as u8;
+        // The lower nibble of F does not exist
+        self.f = value as u8 & 0xF0;
+    }
+
+    pub fn set_bc(&mut self, value: u16) {
+        self.b = (value >> 8) as u8;
+        self.c = value as u8;
+    }
+
+    pub fn set_de(&mut self, value: u16) {
+        self.d = (value >> 8) as u8;
+        self.e = value as u8;
+    }
+
+    pub fn set_hl(&mut self, value: u16) {
+        self.h = (value >> 8) as u8;
+        self.l = value as u8;
+    }
+}
+
+/**
+ * The Sharp LR35902 (SM83) CPU.
+ * Instructions are executed one at a time by `step`, every memory access takes one machine cycle
+ * and instructions with internal delays spend the extra cycles without touching the bus.
+ */
+pub struct Cpu {
+    pub regs: Registers,
+    pub ime: bool,
+    // EI enables interrupts only after the following instruction
+    ime_scheduled: bool,
+    pub halted: bool,
+    // HALT with interrupts disabled and one already pending does not halt, and the next opcode is read twice
+    halt_bug: bool,
+    // In low-power mode after STOP the clock is stopped, only a button press wakes the CPU up
+    pub stopped: bool,
+    // Machine cycles left of the pause after a speed switch
+    speed_switch: u16,
+    // Set by the removed opcodes, the hardware hangs until it is reset
+    pub locked: bool,
+    cycles: u32
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Cpu::new()
+    }
+}
+
+impl Cpu {
+    /// Creates a CPU in the state the DMG boot ROM leaves it in when jumping to the cartridge
+    pub fn new() -> Cpu {
+        Cpu::with_model(Model::Dmg)
+    }
+
+    /**
+     * Creates a CPU in the state the boot ROM of the model leaves it in when jumping to the cartridge.
+     * Games tell a CGB apart by A being 0x11.
+     */
+    pub fn with_model(model: Model) -> Cpu {
+        let regs = match model {
+            Model::Dmg => Registers {
+                a: 0x01, f: 0xB0, b: 0x00, c: 0x13, d: 0x00, e: 0xD8, h: 0x01, l: 0x4D, sp: 0xFFFE, pc: 0x0100
+            },
+            Model::Cgb => Registers {
+                a: 0x11, f: 0x80, b: 0x00, c: 0x00, d: 0xFF, e: 0x56, h: 0x00, l: 0x0D, sp: 0xFFFE, pc: 0x0100
+            },
+            Model::CgbCompatibility => Registers {
+                a: 0x11, f: 0x80, b: 0x00, c: 0x00, d: 0x00, e: 0x08, h: 0x00, l: 0x7C, sp: 0xFFFE, pc: 0x0100
+            }
+        };
+
+        Cpu {
+            regs,
+            ime: false,
+            ime_scheduled: false,
+            halted: false,
+            halt_bug: false,
+            stopped: false,
+            speed_switch: 0,
+            locked: false,
+            cycles: 0
+        }
+    }
+
+    /**
+     * Executes a single instruction or services an interrupt, returns the number of machine cycles it took.
+     * While halted a single cycle passes, the CPU wakes up as soon as an enabled interrupt is requested
+     * and services it only if IME is set, otherwise execution continues after the HALT.
+     */
+    pub fn step<B: MemoryBus>(&mut self, bus: &mut B) -> u32 {
+        self.cycles = 0;
+
+        if self.halted {
+            self.idle(bus);
+            if self.pending_interrupts(bus) != 0 {
+                self.halted = false;
+            }
+            return self.cycles;
+        }
+
+        if self.speed_switch > 0 {
+            self.speed_switch -= 1;
+            self.idle(bus);
+            return self.cycles;
+        }
+
+        // Nothing runs while stopped, a cycle is counted without ticking the bus
+        if self.stopped {
+            self.cycles += 1;
+            if bus.read(IF_ADDR) & Interrupt::Joypad.bit() != 0 {
+                self.stopped = false;
+            }
+            return self.cycles;
+        }
+
+        if self.locked {
+            self.idle(bus);
+            return self.cycles;
+        }
+
+        if self.ime && self.pending_interrupts(bus) != 0 {
+            self.interrupt(bus);
+            return self.cycles;
+        }
+
+        if self.ime_scheduled {
+            self.ime_scheduled = false;
+            self.ime = true;
+        }
+
+        let instruction = self.fetch_instruction(bus);
+        self.execute(bus, &instruction);
+
+        // Removed opcodes have no duration in the table
+        debug_assert!(
+            instruction.cycles == 0 ||
+            self.cycles as usize == instruction.cycles ||
+            self.cycles as usize == instruction.cycles_taken,
+            "{} took {} cycles", instruction, self.cycles
+        );
+
+        self.cycles
+    }
+
+    pub fn flag(&self, flag: u8) -> bool {
+        self.regs.f & flag != 0
+    }
+
+    pub fn set_flag(&mut self, flag: u8, value: bool) {
+        if value {
+            self.regs.f |= flag;
+        } else {
+            self.regs.f &= !flag;
+        }
+    }
+
+    fn set_flags(&mut self, z: bool, n: bool, h: bool, c: bool) {
+        self.set_flag(FLAG_Z, z);
+        self.set_flag(FLAG_N, n);
+        self.set_flag(FLAG_H, h);
+        self.set_flag(FLAG_C, c);
+    }
+
+    // Bus access, each one takes a machine cycle
+
+    fn idle<B: MemoryBus>(&mut self, bus: &mut B) {
+        bus.tick();
+        self.cycles += 1;
+    }
+
+    fn read<B: MemoryBus>(&mut self, bus: &mut B, addr: u16) -> u8 {
+        self.idle(bus);
+        bus.read(addr)
+    }
+
+    fn write<B: MemoryBus>(&mut self, bus: &mut B, addr: u16, value: u8) {
+        self.idle(bus);
+        bus.write(addr, value);
+    }
+
+    fn fetch<B: MemoryBus>(&mut self, bus: &mut B) -> u8 {
+        let value = self.read(bus, self.regs.pc);
+        self.regs.pc = self.regs.pc.wrapping_add(1);
+        value
+    }
+
+    fn fetch_opcode<B: MemoryBus>(&mut self, bus: &mut B) -> u8 {
+        if self.halt_bug {
+            // PC fails to increment, the byte after HALT is read again as the next opcode or operand
+            self.halt_bug = false;
+            return self.read(bus, self.regs.pc);
+        }
+        self.fetch(bus)
+    }
+
+    /**
+     * Fetches the opcode and the bytes following it, one cycle each, and decodes them.
+     * The byte after STOP is skipped without being read.
+     */
+    fn fetch_instruction<B: MemoryBus>(&mut self, bus: &mut B) -> Instruction {
+        let mut code = [self.fetch_opcode(bus), 0, 0];
+        let length = instruction::length(code[0]);
+
+        if code[0] == Opcode::STOP as u8 {
+            self.regs.pc = self.regs.pc.wrapping_add(1);
+        } else {
+            for byte in &mut code[1..length] {
+                *byte = self.fetch(bus);
+            }
+        }
+
+        // Every byte `length` asked for was fetched, so the instruction is complete
+        instruction::decode(&code[..length]).unwrap()
+    }
+
+    fn push<B: MemoryBus>(&mut self, bus: &mut B, value: u16) {
+        self.regs.sp = self.regs.sp.wrapping_sub(1);
+        self.write(bus, self.regs.sp, (value >> 8) as u8);
+        self.regs.sp = self.regs.sp.wrapping_sub(1);
+        self.write(bus, self.regs.sp, value as u8);
+    }
+
+    fn pop<B: MemoryBus>(&mut self, bus: &mut B) -> u16 {
+        let lo = self.read(bus, self.regs.sp) as u16;
+        self.regs.sp = self.regs.sp.wrapping_add(1);
+        let hi = self.read(bus, self.regs.sp) as u16;
+        self.regs.sp = self.regs.sp.wrapping_add(1);
+        (hi << 8) | lo
+    }
+
+    fn modify_hl<B: MemoryBus>(&mut self, bus: &mut B, f: impl FnOnce(&mut Cpu, u8) -> u8) {
+        let hl = self.regs.hl();
+        let value = self.read(bus, hl);
+        let value = f(self, value);
+        self.write(bus, hl, value);
+    }
+
+    // Interrupts
+
+    /// Requested and enabled interrupts, IE and IF are looked at without spending a cycle
+    fn pending_interrupts<B: MemoryBus>(&self, bus: &mut B) -> u8 {
+        bus.read(IE_ADDR) & bus.read(IF_ADDR) & INTERRUPT_MASK
+    }
+
+    /**
+     * Pushes PC and jumps to the vector of the highest priority pending interrupt, in 5 cycles.
+     * The interrupt is chosen after the upper byte of PC is pushed: if that write disabled it in IE,
+     * no interrupt is serviced and execution continues at 0x0000.
+     */
+    fn interrupt<B: MemoryBus>(&mut self, bus: &mut B) {
+        self.ime = false;
+        self.idle(bus);
+        self.idle(bus);
+
+        let pc = self.regs.pc;
+        self.regs.sp = self.regs.sp.wrapping_sub(1);
+        self.write(bus, self.regs.sp, (pc >> 8) as u8);
+
+        let interrupt = Interrupt::highest(self.pending_interrupts(bus));
+
+        self.regs.sp = self.regs.sp.wrapping_sub(1);
+        self.write(bus, self.regs.sp, pc as u8);
+
+        self.regs.pc = match interrupt {
+            Some(interrupt) => {
+                let flags = bus.read(IF_ADDR);
+                bus.write(IF_ADDR, flags & !interrupt.bit());
+                interrupt.vector()
+            },
+            None => 0x0000
+        };
+        self.idle(bus);
+    }
+
+    fn halt<B: MemoryBus>(&mut self, bus: &mut B) {
+        if !self.ime && self.pending_interrupts(bus) != 0 {
+            self.halt_bug = true;
+        } else {
+            self.halted = true;
+        }
+    }
+
+    /// Switches speed if the bus was prepared for it, otherwise enters low-power mode
+    fn stop<B: MemoryBus>(&mut self, bus: &mut B) {
+        if bus.stop() {
+            self.speed_switch = SPEED_SWITCH_CYCLES;
+        } else {
+            self.stopped = true;
+        }
+    }
+
+    // Control flow
+
+    fn jr<B: MemoryBus>(&mut self, bus: &mut B, condition: bool, offset: i8) {
+        if condition {
+            self.idle(bus);
+            self.regs.pc = self.regs.pc.wrapping_add(offset as u16);
+        }
+    }
+
+    fn jp<B: MemoryBus>(&mut self, bus: &mut B, condition: bool, addr: u16) {
+        if condition {
+            self.idle(bus);
+            self.regs.pc = addr;
+        }
+    }
+
+    fn call<B: MemoryBus>(&mut self, bus: &mut B, condition: bool, addr: u16) {
+        if condition {
+            self.idle(bus);
+            self.push(bus, self.regs.pc);
+            self.regs.pc = addr;
+        }
+    }
+
+    fn ret<B: MemoryBus>(&mut self, bus: &mut B) {
+        self.regs.pc = self.pop(bus);
+        self.idle(bus);
+    }
+
+    fn ret_cc<B: MemoryBus>(&mut self, bus: &mut B, condition: bool) {
+        // Evaluating the condition takes a cycle of its own
+        self.idle(bus);
+        if condition {
+            self.ret(bus);
+        }
+    }
+
+    fn rst<B: MemoryBus>(&mut self, bus: &mut B, vector: u16) {
+        self.idle(bus);
+        self.push(bus, self.regs.pc);
+        self.regs.pc = vector;
+    }
+
+    // Arithmetic and logic
+
+    fn add(&mut self, value: u8, carry: bool) {
+        let carry = (carry && self.flag(FLAG_C)) as u8;
+        let a = self.regs.a;
+        let result = a as u16 + value as u16 + carry as u16;
+
+        self.regs.a = result as u8;
+        self.set_flags(
+            result as u8 == 0,
+            false,
+            (a & 0x0F) + (value & 0x0F) + carry > 0x0F,
+            result > 0xFF
+        );
+    }
+
+    fn sub(&mut self, value: u8, carry: bool) {
+        self.regs.a = self.compare(value, carry);
+    }
+
+    fn cp(&mut self, value: u8) {
+        self.compare(value, false);
+    }
+
+    fn compare(&mut self, value: u8, carry: bool) -> u8 {
+        let carry = (carry && self.flag(FLAG_C)) as u8;
+        let a = self.regs.a;
+        let result = a.wrapping_sub(value).wrapping_sub(carry);
+
+        self.set_flags(
+            result == 0,
+            true,
+            (a & 0x0F) < (value & 0x0F) + carry,
+            (a as u16) < value as u16 + carry as u16
+        );
+        result
+    }
+
+    fn and(&mut self, value: u8) {
+        self.regs.a &= value;
+        self.set_flags(self.regs.a == 0, false, true, false);
+    }
+
+    fn xor(&mut self, value: u8) {
+        self.regs.a ^= value;
+        self.set_flags(self.regs.a == 0, false, false, false);
+    }
+
+    fn or(&mut self, value: u8) {
+        self.regs.a |= value;
+        self.set_flags(self.regs.a == 0, false, false, false);
+    }
+
+    fn inc(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_add(1);
+        self.set_flag(FLAG_Z, result == 0);
+        self.set_flag(FLAG_N, false);
+        self.set_flag(FLAG_H, value & 0x0F == 0x0F);
+        result
+    }
+
+    fn dec(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_sub(1);
+        self.set_flag(FLAG_Z, result == 0);
+        self.set_flag(FLAG_N, true);
+        self.set_flag(FLAG_H, value & 0x0F == 0x00);
+        result
+    }
+
+    fn add_hl<B: MemoryBus>(&mut self, bus: &mut B, value: u16) {
+        self.idle(bus);
+        let hl = self.regs.hl();
+        let result = hl as u32 + value as u32;
+
+        self.regs.set_hl(result as u16);
+        self.set_flag(FLAG_N, false);
+        self.set_flag(FLAG_H, (hl & 0x0FFF) + (value & 0x0FFF) > 0x0FFF);
+        self.set_flag(FLAG_C, result > 0xFFFF);
+    }
+
+    /// SP plus a signed offset, flags are computed on the lower byte as an unsigned addition
+    fn add_sp(&mut self, offset: u8) -> u16 {
+        let sp = self.regs.sp;
+        let value = offset as i8 as u16;
+
+        self.set_flags(
+            false,
+            false,
+            (sp & 0x0F) + (value & 0x0F) > 0x0F,
+            (sp & 0xFF) + (value & 0xFF) > 0xFF
+        );
+        sp.wrapping_add(value)
+    }
+
+    fn daa(&mut self) {
+        let mut a = self.regs.a;
+        let mut carry = self.flag(FLAG_C);
+
+        if !self.flag(FLAG_N) {
+            if carry || a > 0x99 {
+                a = a.wrapping_add(0x60);
+                carry = true;
+            }
+            if self.flag(FLAG_H) || (a & 0x0F) > 0x09 {
+                a = a.wrapping_add(0x06);
+            }
+        } else {
+            if carry {
+                a = a.wrapping_sub(0x60);
+            }
+            if self.flag(FLAG_H) {
+                a = a.wrapping_sub(0x06);
+            }
+        }
+
+        self.regs.a = a;
+        self.set_flag(FLAG_Z, a == 0);
+        self.set_flag(FLAG_H, false);
+        self.set_flag(FLAG_C, carry);
+    }
+
+    // Rotations and shifts, the accumulator versions clear Z afterwards
+
+    fn rlc(&mut self, value: u8) -> u8 {
+        let result = value.rotate_left(1);
+        self.set_flags(result == 0, false, false, value & 0x80 != 0);
+        result
+    }
+
+    fn rrc(&mut self, value: u8) -> u8 {
+        let result = value.rotate_right(1);
+        self.set_flags(result == 0, false, false, value & 0x01 != 0);
+        result
+    }
+
+    fn rl(&mut self, value: u8) -> u8 {
+        let result = (value << 1) | self.flag(FLAG_C) as u8;
+        self.set_flags(result == 0, false, false, value & 0x80 != 0);
+        result
+    }
+
+    fn rr(&mut self, value: u8) -> u8 {
+        let result = (value >> 1) | ((self.flag(FLAG_C) as u8) << 7);
+        self.set_flags(result == 0, false, false, value & 0x01 != 0);
+        result
+    }
+
+    fn sla(&mut self, value: u8) -> u8 {
+        let result = value << 1;
+        self.set_flags(result == 0, false, false, value & 0x80 != 0);
+        result
+    }
+
+    fn sra(&mut self, value: u8) -> u8 {
+        let result = (value >> 1) | (value & 0x80);
+        self.set_flags(result == 0, false, false, value & 0x01 != 0);
+        result
+    }
+
+    fn srl(&mut self, value: u8) -> u8 {
+        let result = value >> 1;
+        self.set_flags(result == 0, false, false, value & 0x01 != 0);
+        result
+    }
+
+    fn swap(&mut self, value: u8) -> u8 {
+        let result = value.rotate_left(4);
+        self.set_flags(result == 0, false, false, false);
+        result
+    }
+
+    fn bit(&mut self, bit: u8, value: u8) {
+        self.set_flag(FLAG_Z, value & (1 << bit) == 0);
+        self.set_flag(FLAG_N, false);
+        self.set_flag(FLAG_H, true);
+    }
+
+    // Operands
+
+    fn pair(&self, pair: RegisterPair) -> u16 {
+        match pair {
+            RegisterPair::AF => self.regs.af(),
+            RegisterPair::BC => self.regs.bc(),
+            RegisterPair::DE => self.regs.de(),
+            RegisterPair::HL => self.regs.hl(),
+            RegisterPair::SP => self.regs.sp
+        }
+    }
+
+    fn set_pair(&mut self, pair: RegisterPair, value: u16) {
+        match pair {
+            RegisterPair::AF => self.regs.set_af(value),
+            RegisterPair::BC => self.regs.set_bc(value),
+            RegisterPair::DE => self.regs.set_de(value),
+            RegisterPair::HL => self.regs.set_hl(value),
+            RegisterPair::SP => self.regs.sp = value
+        }
+    }
+
+    fn register(&mut self, register: Register) -> &mut u8 {
+        match register {
+            Register::A => &mut self.regs.a,
+            Register::B => &mut self.regs.b,
+            Register::C => &mut self.regs.c,
+            Register::D => &mut self.regs.d,
+            Register::E => &mut self.regs.e,
+            Register::H => &mut self.regs.h,
+            Register::L => &mut self.regs.l
+        }
+    }
+
+    fn condition(&self, condition: Condition) -> bool {
+        match condition {
+            Condition::NZ => !self.flag(FLAG_Z),
+            Condition::Z  => self.flag(FLAG_Z),
+            Condition::NC => !self.flag(FLAG_C),
+            Condition::C  => self.flag(FLAG_C)
+        }
+    }
+
+    /// The address of a memory operand, HL is incremented or decremented as a side effect
+    fn address(&mut self, memory: Memory) -> u16 {
+        let hl = self.regs.hl();
+        match memory {
+            Memory::BC              => self.regs.bc(),
+            Memory::DE              => self.regs.de(),
+            Memory::HL              => hl,
+            Memory::HLIncrement     => { self.regs.set_hl(hl.wrapping_add(1)); hl },
+            Memory::HLDecrement     => { self.regs.set_hl(hl.wrapping_sub(1)); hl },
+            Memory::HighC           => 0xFF00 | self.regs.c as u16,
+            Memory::Address(nn)     => nn,
+            Memory::HighAddress(n)  => 0xFF00 | n as u16
+        }
+    }
+
+    /// The value of an 8-bit operand
+    fn load<B: MemoryBus>(&mut self, bus: &mut B, operand: Operand) -> u8 {
+        match operand {
+            Operand::Register(r) => *self.register(r),
+            Operand::Immediate8(n) => n,
+            Operand::Memory(memory) => {
+                let addr = self.address(memory);
+                self.read(bus, addr)
+            },
+            _ => unreachable!("{:?} is not an 8-bit operand", operand)
+        }
+    }
+
+    fn store<B: MemoryBus>(&mut self, bus: &mut B, operand: Operand, value: u8) {
+        match operand {
+            Operand::Register(r) => *self.register(r) = value,
+            Operand::Memory(memory) => {
+                let addr = self.address(memory);
+                self.write(bus, addr, value);
+            },
+            _ => unreachable!("{:?} cannot be written", operand)
+        }
+    }
+
+    /// Reads, changes and writes back an 8-bit operand
+    fn modify<B: MemoryBus>(&mut self, bus: &mut B, operand: Operand, f: impl FnOnce(&mut Cpu, u8) -> u8) {
+        match operand {
+            Operand::Memory(Memory::HL) => self.modify_hl(bus, f),
+            Operand::Register(r) => {
+                let value = *self.register(r);
+                *self.register(r) = f(self, value);
+            },
+            _ => unreachable!("{:?} cannot be modified", operand)
+        }
+    }
+
+    // Instruction dispatch
+
+    fn execute<B: MemoryBus>(&mut self, bus: &mut B, instruction: &Instruction) {
+        use Operation::*;
+        use Operand::{Bit as BitNumber, Condition as Cond, Immediate16, Offset, RegisterPair as Pair, SpOffset, Vector};
+
+        match (instruction.operation, instruction.operands()) {
+            (Nop, []) => {},
+
+            // 16-bit loads
+            (Ld, [Pair(RegisterPair::HL), SpOffset(d)]) => {
+                let value = self.add_sp(*d as u8);
+                self.regs.set_hl(value);
+                self.idle(bus);
+            },
+            (Ld, [Pair(RegisterPair::SP), Pair(RegisterPair::HL)]) => {
+                self.idle(bus);
+                self.regs.sp = self.regs.hl();
+            },
+            (Ld, [Pair(rr), Immediate16(nn)]) => self.set_pair(*rr, *nn),
+            (Ld, [Operand::Memory(Memory::Address(nn)), Pair(RegisterPair::SP)]) => {
+                self.write(bus, *nn, self.regs.sp as u8);
+                self.write(bus, nn.wrapping_add(1), (self.regs.sp >> 8) as u8);
+            },
+            (Push, [Pair(rr)]) => {
+                self.idle(bus);
+                self.push(bus, self.pair(*rr));
+            },
+            (Pop, [Pair(rr)]) => {
+                let value = self.pop(bus);
+                self.set_pair(*rr, value);
+            },
+
+            // 8-bit loads
+            (Ld, [dst, src]) => {
+                let value = self.load(bus, *src);
+                self.store(bus, *dst, value);
+            },
+
+            // 16-bit arithmetic
+            (Add, [Pair(RegisterPair::HL), Pair(rr)]) => self.add_hl(bus, self.pair(*rr)),
+            (Add, [Pair(RegisterPair::SP), Offset(d)]) => {
+                self.regs.sp = self.add_sp(*d as u8);
+                self.idle(bus);
+                self.idle(bus);
+            },
+            (Inc, [Pair(rr)]) => {
+                self.idle(bus);
+                self.set_pair(*rr, self.pair(*rr).wrapping_add(1));
+            },
+            (Dec, [Pair(rr)]) => {
+                self.idle(bus);
+                self.set_pair(*rr, self.pair(*rr).wrapping_sub(1));
+            },
+
+            // 8-bit arithmetic and logic
+            (Add, [_, src]) => { let v = self.load(bus, *src); self.add(v, false) },
+            (Adc, [_, src]) => { let v = self.load(bus, *src); self.add(v, true) },
+            (Sub, [src])    => { let v = self.load(bus, *src); self.sub(v, false) },
+            (Sbc, [_, src]) => { let v = self.load(bus, *src); self.sub(v, true) },
+            (And, [src])    => { let v = self.load(bus, *src); self.and(v) },
+            (Xor, [src])    => { let v = self.load(bus, *src); self.xor(v) },
+            (Or, [src])     => { let v = self.load(bus, *src); self.or(v) },
+            (Cp, [src])     => { let v = self.load(bus, *src); self.cp(v) },
+            (Inc, [dst])    => self.modify(bus, *dst, Cpu::inc),
+            (Dec, [dst])    => self.modify(bus, *dst, Cpu::dec),
+            (Daa, [])       => self.daa(),
+            (Cpl, [])       => { self.regs.a = !self.regs.a; self.set_flag(FLAG_N, true); self.set_flag(FLAG_H, true) },
+            (Scf, [])       => { self.set_flag(FLAG_N, false); self.set_flag(FLAG_H, false); self.set_flag(FLAG_C, true) },
+            (Ccf, [])       => { let c = self.flag(FLAG_C); self.set_flag(FLAG_N, false); self.set_flag(FLAG_H, false); self.set_flag(FLAG_C, !c) },
+
+            // Rotations, shifts and bit operations
+            (Rlca, [])      => { self.regs.a = self.rlc(self.regs.a); self.set_flag(FLAG_Z, false) },
+            (Rrca, [])      => { self.regs.a = self.rrc(self.regs.a); self.set_flag(FLAG_Z, false) },
+            (Rla, [])       => { self.regs.a = self.rl(self.regs.a); self.set_flag(FLAG_Z, false) },
+            (Rra, [])       => { self.regs.a = self.rr(self.regs.a); self.set_flag(FLAG_Z, false) },
+            (Rlc, [dst])    => self.modify(bus, *dst, Cpu::rlc),
+            (Rrc, [dst])    => self.modify(bus, *dst, Cpu::rrc),
+            (Rl, [dst])     => self.modify(bus, *dst, Cpu::rl),
+            (Rr, [dst])     => self.modify(bus, *dst, Cpu::rr),
+            (Sla, [dst])    => self.modify(bus, *dst, Cpu::sla),
+            (Sra, [dst])    => self.modify(bus, *dst, Cpu::sra),
+            (Swap, [dst])   => self.modify(bus, *dst, Cpu::swap),
+            (Srl, [dst])    => self.modify(bus, *dst, Cpu::srl),
+            (Bit, [BitNumber(bit), src]) => { let v = self.load(bus, *src); self.bit(*bit, v) },
+            (Res, [BitNumber(bit), dst]) => { let bit = *bit; self.modify(bus, *dst, |_, v| v & !(1 << bit)) },
+            (Set, [BitNumber(bit), dst]) => { let bit = *bit; self.modify(bus, *dst, |_, v| v | (1 << bit)) },
+
+            // Control flow
+            (Jp, [Pair(RegisterPair::HL)])          => self.regs.pc = self.regs.hl(),
+            (Jp, [Immediate16(nn)])                 => self.jp(bus, true, *nn),
+            (Jp, [Cond(cc), Immediate16(nn)])       => self.jp(bus, self.condition(*cc), *nn),
+            (Jr, [Offset(d)])                       => self.jr(bus, true, *d),
+            (Jr, [Cond(cc), Offset(d)])             => self.jr(bus, self.condition(*cc), *d),
+            (Call, [Immediate16(nn)])               => self.call(bus, true, *nn),
+            (Call, [Cond(cc), Immediate16(nn)])     => self.call(bus, self.condition(*cc), *nn),
+            (Ret, [])                               => self.ret(bus),
+            (Ret, [Cond(cc)])                       => self.ret_cc(bus, self.condition(*cc)),
+            (Reti, [])                              => { self.ret(bus); self.ime = true },
+            (Rst, [Vector(vector)])                 => self.rst(bus, *vector as u16),
+
+            // Control
+            (Halt, [])      => self.halt(bus),
+            (Stop, [])      => self.stop(bus),
+            (Di, [])        => { self.ime = false; self.ime_scheduled = false },
+            (Ei, [])        => self.ime_scheduled = true,
+            (Illegal, [])   => self.locked = true,
+
+            _ => unreachable!("{} cannot be executed", instruction)
+        }
+    }
+}
@@ -0,0 +1,124 @@
+mod rgbds;
+mod trace;
+
+pub use rgbds::export as export_rgbds;
+pub use trace::Trace;
+
+use std::fmt::Display;
+
+use crate::cartridge::Cartridge;
+use crate::mbc::ROM_BANK_SIZE;
+use crate::instruction::{self, Instruction, Operand, Operation};
+
+/// A single decoded instruction of a listing
+pub struct Line {
+    pub bank: usize,
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    pub mnemonic: String,
+    // None for data
+    pub instruction: Option<Instruction>,
+    // Duration in machine cycles when not branching and when branching, 0 if unknown
+    pub cycles: (usize, usize)
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let bytes = self.bytes.iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        write!(f, "{:02X}:{:04X}  {:<8}  ", self.bank, self.addr, bytes)?;
+        match self.cycles {
+            (0, _)                              => write!(f, "{}", self.mnemonic),
+            (cycles, taken) if cycles == taken  => write!(f, "{:<20} ; {}", self.mnemonic, cycles),
+            (cycles, taken)                     => write!(f, "{:<20} ; {}/{}", self.mnemonic, cycles, taken)
+        }
+    }
+}
+
+/**
+ * The address at which a bank is visible to the CPU:
+ * bank 0 is always at 0x0000-0x3FFF, every other bank is switched in at 0x4000-0x7FFF.
+ */
+pub fn bank_base(bank: usize) -> u16 {
+    if bank == 0 { 0x0000 } else { 0x4000 }
+}
+
+/// The number of ROM banks in the cartridge, counting a partial bank at the end
+pub fn bank_count(cart: &Cartridge) -> usize {
+    cart.data().len().div_ceil(ROM_BANK_SIZE)
+}
+
+/**
+ * Decodes the instructions of a bank between the CPU addresses `from` (inclusive) and `to` (exclusive).
+ * An instruction crossing the end of the range is still decoded whole if the bank contains it,
+ * trailing bytes which do not form a complete instruction are listed as data.
+ */
+pub fn disassemble_range(cart: &Cartridge, bank: usize, from: u16, to: u16) -> Vec<Line> {
+    let base = bank_base(bank);
+    let start = bank * ROM_BANK_SIZE;
+    let data = cart.data();
+    let bank_data = &data[start.min(data.len())..(start + ROM_BANK_SIZE).min(data.len())];
+
+    let mut lines = Vec::new();
+    let mut offset = from.saturating_sub(base) as usize;
+    let end = (to.saturating_sub(base) as usize).min(bank_data.len());
+
+    while offset < end {
+        let addr = base + offset as u16;
+        let line = decode(&bank_data[offset..], addr).unwrap_or_else(|| Line {
+            bank,
+            addr,
+            bytes: bank_data[offset..end].to_vec(),
+            mnemonic: String::from("db"),
+            instruction: None,
+            cycles: (0, 0)
+        });
+
+        offset += line.bytes.len();
+        lines.push(Line { bank, ..line });
+    }
+
+    lines
+}
+
+pub fn disassemble_bank(cart: &Cartridge, bank: usize) -> Vec<Line> {
+    let base = bank_base(bank);
+    disassemble_range(cart, bank, base, base + ROM_BANK_SIZE as u16)
+}
+
+/**
+ * Decodes the instruction at the start of `code`, located at the CPU address `addr`.
+ * Returns `None` if `code` ends before the instruction does.
+ */
+pub fn decode(code: &[u8], addr: u16) -> Option<Line> {
+    let instruction = instruction::decode(code)?;
+    let bytes = code[..instruction.length].to_vec();
+
+    Some(Line {
+        bank: 0,
+        addr,
+        bytes,
+        mnemonic: format_mnemonic(&instruction, addr),
+        instruction: Some(instruction),
+        cycles: (instruction.cycles, instruction.cycles_taken)
+    })
+}
+
+/// Formats the instruction, relative jumps show the resolved target address instead of the offset
+fn format_mnemonic(instruction: &Instruction, addr: u16) -> String {
+    let operands = instruction.operands().iter()
+        .map(|operand| match (operand, instruction.target(addr)) {
+            (Operand::Offset(_), Some(target)) if instruction.operation == Operation::Jr => format!("${:04X}", target),
+            (operand, _) => operand.to_string()
+        })
+        .collect::<Vec<_>>();
+
+    if operands.is_empty() {
+        instruction.mnemonic().to_string()
+    } else {
+        format!("{} {}", instruction.mnemonic(), operands.join(", "))
+    }
+}
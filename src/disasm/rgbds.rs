@@ -0,0 +1,127 @@
+use std::fmt::Write;
+
+use super::{Line, Trace};
+use crate::cartridge::Cartridge;
+use crate::mbc::ROM_BANK_SIZE;
+use crate::instruction::{Memory, Operand, Operation};
+
+/**
+ * Exports the whole ROM as RGBDS assembly, with one fixed section per bank.
+ * Assembling the output with rgbasm and linking it with rgblink reproduces the ROM byte for byte,
+ * which is why the few instructions older rgbasm versions would assemble differently
+ * (`halt` followed by an implicit `nop`, `ld` to 0xFFxx turned into `ldh`) are written as bytes.
+ */
+pub fn export(cart: &Cartridge, trace: &Trace) -> String {
+    let data = cart.data();
+    let banks = data.len().div_ceil(ROM_BANK_SIZE);
+    let mut out = String::new();
+
+    for bank in 0..banks {
+        if bank == 0 {
+            writeln!(out, "SECTION \"ROM Bank $000\", ROM0[$0000]").unwrap();
+        } else {
+            writeln!(out, "\nSECTION \"ROM Bank ${:03X}\", ROMX[$4000], BANK[${:03X}]", bank, bank).unwrap();
+        }
+
+        for line in trace.listing(cart, bank) {
+            let offset = bank * ROM_BANK_SIZE + (line.addr as usize & (ROM_BANK_SIZE - 1));
+
+            if trace.targets().contains(&offset) && trace.is_instruction(offset) {
+                writeln!(out, "\n{}:", label(offset)).unwrap();
+            }
+
+            if trace.is_instruction(offset) {
+                writeln!(out, "    {}", instruction(&line, bank, trace)).unwrap();
+            } else {
+                writeln!(out, "    {}", bytes(&line.bytes)).unwrap();
+            }
+        }
+    }
+
+    out
+}
+
+fn label(offset: usize) -> String {
+    let bank = offset / ROM_BANK_SIZE;
+    let addr = if bank == 0 { offset } else { 0x4000 + offset % ROM_BANK_SIZE };
+    format!("Label_{:03X}_{:04X}", bank, addr)
+}
+
+fn bytes(bytes: &[u8]) -> String {
+    format!("db {}", bytes.iter()
+        .map(|b| format!("${:02X}", b))
+        .collect::<Vec<_>>()
+        .join(", "))
+}
+
+/**
+ * A jump or call target, as a label if one is generated for it.
+ * Jumps from bank 0 into the switchable area cannot tell which bank they end up in,
+ * so they keep the numeric address.
+ */
+fn target(addr: u16, bank: usize, trace: &Trace) -> String {
+    let offset = match addr {
+        0x0000..=0x3FFF => Some(addr as usize),
+        0x4000..=0x7FFF if bank != 0 => Some(bank * ROM_BANK_SIZE + (addr - 0x4000) as usize),
+        _ => None
+    };
+
+    match offset {
+        Some(offset) if trace.targets().contains(&offset) && trace.is_instruction(offset) => label(offset),
+        _ => format!("${:04X}", addr)
+    }
+}
+
+/// Writes the instruction in RGBDS syntax, e.g. `ld [hl+], a`
+fn instruction(line: &Line, bank: usize, trace: &Trace) -> String {
+    let b = &line.bytes;
+
+    // Instructions cut by the end of the bank are listed as data
+    let instruction = match line.instruction {
+        Some(instruction) => instruction,
+        None => return bytes(b)
+    };
+    let destination = instruction.target(line.addr);
+
+    let operands: Vec<String> = instruction.operands().iter()
+        .map(|operand| match (operand, destination) {
+            (Operand::Immediate16(_) | Operand::Offset(_), Some(addr)) => target(addr, bank, trace),
+            (Operand::Register(r), _)       => format!("{:?}", r).to_lowercase(),
+            (Operand::RegisterPair(rr), _)  => format!("{:?}", rr).to_lowercase(),
+            (Operand::Condition(c), _)      => format!("{:?}", c).to_lowercase(),
+            (Operand::Immediate8(n), _)     => format!("${:02X}", n),
+            (Operand::Immediate16(nn), _)   => format!("${:04X}", nn),
+            (Operand::Offset(d), _)         => format!("{}", d),
+            (Operand::SpOffset(d), _)       => format!("sp{:+}", d),
+            (Operand::Bit(bit), _)          => format!("{}", bit),
+            (Operand::Vector(vector), _)    => format!("${:02X}", vector),
+            (Operand::Memory(memory), _)    => match memory {
+                Memory::BC              => String::from("[bc]"),
+                Memory::DE              => String::from("[de]"),
+                Memory::HL              => String::from("[hl]"),
+                Memory::HLIncrement     => String::from("[hl+]"),
+                Memory::HLDecrement     => String::from("[hl-]"),
+                Memory::HighC           => String::from("[c]"),
+                Memory::Address(nn)     => format!("[${:04X}]", nn),
+                Memory::HighAddress(n)  => format!("[${:04X}]", 0xFF00 | *n as u16)
+            }
+        })
+        .collect();
+
+    let text = if operands.is_empty() {
+        instruction.mnemonic().to_lowercase()
+    } else {
+        format!("{} {}", instruction.mnemonic().to_lowercase(), operands.join(", "))
+    };
+
+    match (instruction.operation, instruction.operands()) {
+        (Operation::Illegal, _) => bytes(b),
+        (Operation::Halt, _) => format!("{} ; halt", bytes(b)),
+        (Operation::Stop, _) if b[1] == 0 => text,
+        (Operation::Stop, _) => bytes(b),
+        (Operation::Ld, [Operand::Memory(Memory::Address(nn)), _] | [_, Operand::Memory(Memory::Address(nn))]) if *nn >= 0xFF00 => {
+            format!("{} ; {}", bytes(b), text)
+        },
+        _ => text
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::BTreeSet;
+
+use super::{bank_base, decode, Line};
+use crate::cartridge::Cartridge;
+use crate::mbc::ROM_BANK_SIZE;
+use crate::instruction::{Instruction, Memory, Operand, Operation, Register};
+
+const RST_VECTORS: [u16; 8] = [0x00, 0x08, 0x10, 0x18, 0x20, 0x28, 0x30, 0x38];
+const INTERRUPT_VECTORS: [u16; 5] = [0x40, 0x48, 0x50, 0x58, 0x60];
+const ENTRY_POINT: u16 = 0x100;
+
+// The number of data bytes listed on a single line
+const DATA_LINE_SIZE: usize = 8;
+
+/// How an instruction affects the control flow
+enum Flow {
+    Next,               // Execution continues with the next instruction
+    Jump(u16),          // Unconditional jump, execution does not continue
+    Branch(u16),        // Conditional jump or call, execution may continue
+    Stop                // Return or jump to an unknown address
+}
+
+/**
+ * The result of following the control flow of a ROM from its entry points.
+ * Every byte reached as part of an instruction is code, everything else is considered data.
+ */
+pub struct Trace {
+    code: Vec<bool>,
+    starts: Vec<bool>,
+    targets: BTreeSet<usize>
+}
+
+/**
+ * Converts a CPU address to an offset in the ROM, given the bank currently switched in at 0x4000-0x7FFF.
+ * Returns `None` for addresses outside of the ROM area.
+ */
+fn rom_offset(addr: u16, bank: usize) -> Option<usize> {
+    match addr {
+        0x0000..=0x3FFF => Some(addr as usize),
+        0x4000..=0x7FFF => Some(bank.max(1) * ROM_BANK_SIZE + (addr - 0x4000) as usize),
+        _ => None
+    }
+}
+
+fn flow(instruction: &Instruction, addr: u16) -> Flow {
+    use Operation::*;
+
+    let conditional = instruction.condition().is_some();
+    match (instruction.operation, instruction.target(addr)) {
+        (Jp | Jr, Some(target)) if !conditional => Flow::Jump(target),
+        (Jp | Jr | Call | Rst, Some(target)) => Flow::Branch(target),
+        (Ret, _) if !conditional => Flow::Stop,
+        // JP HL
+        (Jp, None) => Flow::Stop,
+        (Reti | Illegal, _) => Flow::Stop,
+        _ => Flow::Next
+    }
+}
+
+/**
+ * Detects writes to the MBC ROM bank register (0x2000-0x3FFF) of a constant loaded into A
+ * by the previous instruction, the usual way of switching banks before a far call.
+ */
+fn bank_switch(previous: Option<&Line>, line: &Line) -> Option<usize> {
+    let previous = previous?.instruction?;
+    let current = line.instruction?;
+
+    match (previous.operation, previous.operands(), current.operation, current.operands()) {
+        (
+            Operation::Ld, [Operand::Register(Register::A), Operand::Immediate8(bank)],
+            Operation::Ld, [Operand::Memory(Memory::Address(0x2000..=0x3FFF)), Operand::Register(Register::A)]
+        ) => Some(*bank as usize),
+        _ => None
+    }
+}
+
+impl Trace {
+    /**
+     * Follows every path starting at the entry point, the RST vectors and the interrupt vectors.
+     * Jumps into the switchable area from bank 0 assume the bank selected by the last recognized
+     * bank switch on the same path, or bank 1 if there was none.
+     */
+    pub fn new(cart: &Cartridge) -> Trace {
+        let data = cart.data();
+        let mut trace = Trace {
+            code: vec![false; data.len()],
+            starts: vec![false; data.len()],
+            targets: BTreeSet::new()
+        };
+
+        let mut pending: Vec<(u16, usize)> = RST_VECTORS.iter()
+            .chain(INTERRUPT_VECTORS.iter())
+            .chain(std::iter::once(&ENTRY_POINT))
+            .map(|&addr| (addr, 1))
+            .collect();
+
+        while let Some((addr, bank)) = pending.pop() {
+            trace.follow(data, addr, bank, &mut pending);
+        }
+
+        trace
+    }
+
+    fn follow(&mut self, data: &[u8], mut addr: u16, mut bank: usize, pending: &mut Vec<(u16, usize)>) {
+        let mut previous: Option<Line> = None;
+
+        while let Some(offset) = rom_offset(addr, bank) {
+            if offset >= data.len() || self.starts[offset] {
+                return;
+            }
+
+            let line = match decode(&data[offset..], addr) {
+                Some(line) => line,
+                None => return
+            };
+
+            // Overlapping an instruction decoded from another path means one of the two is wrong
+            let range = offset..offset + line.bytes.len();
+            if self.code[range.clone()].iter().any(|&c| c) {
+                return;
+            }
+            self.code[range].iter_mut().for_each(|c| *c = true);
+            self.starts[offset] = true;
+
+            if let Some(new_bank) = bank_switch(previous.as_ref(), &line) {
+                bank = new_bank;
+            }
+
+            let mut add_target = |target: u16| {
+                // Code in a switchable bank keeps jumping within the same bank
+                if let Some(target_offset) = rom_offset(target, bank) {
+                    self.targets.insert(target_offset);
+                    pending.push((target, bank));
+                }
+            };
+
+            match line.instruction.map_or(Flow::Stop, |instruction| flow(&instruction, addr)) {
+                Flow::Next => {},
+                Flow::Branch(target) => add_target(target),
+                Flow::Jump(target) => {
+                    add_target(target);
+                    return;
+                },
+                Flow::Stop => return
+            }
+
+            addr = addr.wrapping_add(line.bytes.len() as u16);
+            previous = Some(line);
+        }
+    }
+
+    pub fn is_code(&self, offset: usize) -> bool {
+        self.code.get(offset).copied().unwrap_or(false)
+    }
+
+    /// Whether the ROM offset is the start of an instruction reached by the trace
+    pub fn is_instruction(&self, offset: usize) -> bool {
+        self.starts.get(offset).copied().unwrap_or(false)
+    }
+
+    /// The ROM offsets which are the target of a jump, call or restart
+    pub fn targets(&self) -> &BTreeSet<usize> {
+        &self.targets
+    }
+
+    /**
+     * Lists a whole bank, decoding the instructions reached by the trace
+     * and grouping everything else in `db` lines.
+     */
+    pub fn listing(&self, cart: &Cartridge, bank: usize) -> Vec<Line> {
+        let data = cart.data();
+        let start = bank * ROM_BANK_SIZE;
+        let end = (start + ROM_BANK_SIZE).min(data.len());
+        let base = bank_base(bank);
+
+        let mut lines = Vec::new();
+        let mut offset = start;
+
+        while offset < end {
+            let addr = base + (offset - start) as u16;
+
+            if self.starts[offset] {
+                if let Some(line) = decode(&data[offset..end], addr) {
+                    offset += line.bytes.len();
+                    lines.push(Line { bank, ..line });
+                    continue;
+                }
+            }
+
+            let mut data_end = offset + 1;
+            while data_end < end && data_end - offset < DATA_LINE_SIZE && !self.code[data_end] {
+                data_end += 1;
+            }
+
+            let bytes = data[offset..data_end].to_vec();
+            let mnemonic = format!("db {}", bytes.iter()
+                .map(|b| format!("${:02X}", b))
+                .collect::<Vec<_>>()
+                .join(", "));
+
+            lines.push(Line { bank, addr, bytes, mnemonic, instruction: None, cycles: (0, 0) });
+            offset = data_end;
+        }
+
+        lines
+    }
+}
@@ -0,0 +1,125 @@
+use crate::ppu::OAM_SIZE;
+
+pub const DMA_ADDR: u16 = 0xFF46;
+
+/// The two buses DMA can read from, the CPU keeps the one it is not using
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum DataBus {
+    // The cartridge and WRAM
+    External,
+    Vram
+}
+
+/// The bus an address is on, None for OAM, the I/O registers and HRAM which are internal to the console
+fn data_bus(addr: u16) -> Option<DataBus> {
+    match addr {
+        0x0000..=0x7FFF | 0xA000..=0xFDFF   => Some(DataBus::External),
+        0x8000..=0x9FFF                     => Some(DataBus::Vram),
+        _ => None
+    }
+}
+
+/**
+ * OAM DMA, started by writing the upper byte of the source address to DMA (0xFF46).
+ * After a cycle of setup one of the 160 bytes is copied to OAM every machine cycle.
+ * Meanwhile OAM reads 0xFF, and a CPU read on the bus DMA reads from returns the byte being copied
+ * instead while writes there are lost. The other bus, the I/O registers and HRAM are accessed normally.
+ * Writing DMA again during a transfer restarts it, the old one keeps running during the setup cycle.
+ */
+pub struct OamDma {
+    register: u8,
+    // A transfer requested by a write to DMA and its source, with the setup cycles left
+    starting: Option<(u16, u8)>,
+    source: u16,
+    // The next byte to copy while a transfer is running, OAM_SIZE during its last cycle
+    index: Option<u16>,
+    // The byte copied during the current cycle
+    value: u8
+}
+
+impl Default for OamDma {
+    fn default() -> Self {
+        OamDma::new()
+    }
+}
+
+impl OamDma {
+    /// Creates the DMA controller in the state the boot ROM leaves it in, with the register reading 0xFF
+    pub fn new() -> OamDma {
+        OamDma {
+            register: 0xFF,
+            starting: None,
+            source: 0,
+            index: None,
+            value: 0xFF
+        }
+    }
+
+    /// Whether a transfer is running and getting in the way of the CPU
+    pub fn active(&self) -> bool {
+        self.index.is_some()
+    }
+
+    /**
+     * What the CPU reads at an address during a transfer if DMA is in the way: 0xFF for OAM,
+     * or the byte being copied on the bus DMA reads from. Writes are lost when this is Some.
+     */
+    pub fn conflict(&self, addr: u16) -> Option<u8> {
+        if !self.active() {
+            return None;
+        }
+
+        match addr {
+            0xFE00..=0xFEFF => Some(0xFF),
+            _ if data_bus(addr).is_some() && data_bus(addr) == data_bus(self.source) => Some(self.value),
+            _ => None
+        }
+    }
+
+    /// Records the byte copied during this cycle, read by the bus for the address returned by `tick`
+    pub fn copied(&mut self, value: u8) {
+        self.value = value;
+    }
+
+    /**
+     * Advances the DMA by a machine cycle.
+     * Returns the source address of the byte to copy during this cycle and its index in OAM.
+     */
+    pub fn tick(&mut self) -> Option<(u16, u8)> {
+        // A new transfer replaces the running one after the setup cycle
+        if let Some((source, cycles)) = self.starting {
+            if cycles == 0 {
+                self.starting = None;
+                self.source = source;
+                self.index = Some(0);
+            } else {
+                self.starting = Some((source, cycles - 1));
+            }
+        }
+
+        match self.index {
+            Some(index) if (index as usize) < OAM_SIZE => {
+                self.index = Some(index + 1);
+                Some((self.source + index, index as u8))
+            },
+            Some(_) => {
+                self.index = None;
+                None
+            },
+            None => None
+        }
+    }
+
+    pub fn read(&self) -> u8 {
+        self.register
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.register = value;
+
+        // Sources past WRAM read from its echo, DMA cannot read OAM or the I/O registers
+        let source = (value as u16) << 8;
+        let source = if source >= 0xE000 { source - 0x2000 } else { source };
+        self.starting = Some((source, 1));
+    }
+}
@@ -0,0 +1,82 @@
+use std::io;
+
+use crate::bus::Bus;
+use crate::cartridge::{Cartridge, CartridgeError};
+use crate::cpu::Cpu;
+use crate::model::Model;
+use crate::save::BatterySave;
+
+// 154 lines of 456 dots, 4 dots per machine cycle
+pub const CYCLES_PER_FRAME: u32 = 154 * 456 / 4;
+
+/// The CPU and everything on its bus, plus the save file of the cartridge RAM if it has a battery
+pub struct GameBoy {
+    pub cpu: Cpu,
+    pub bus: Bus,
+    save: Option<BatterySave>
+}
+
+impl GameBoy {
+    /// Boots the cartridge on the model it is meant for, see `Model::for_cartridge`
+    pub fn new(cart: &Cartridge) -> Result<GameBoy, CartridgeError> {
+        GameBoy::with_model(cart, Model::for_cartridge(cart))
+    }
+
+    pub fn with_model(cart: &Cartridge, model: Model) -> Result<GameBoy, CartridgeError> {
+        Ok(GameBoy { cpu: Cpu::with_model(model), bus: Bus::new(cart, model)?, save: None })
+    }
+
+    pub fn model(&self) -> Model {
+        self.bus.model()
+    }
+
+    /**
+     * Loads the save file into the cartridge RAM, returns false if there is no save file yet.
+     * From then on `run_frame` writes it back regularly and `flush_save` writes it on exit.
+     */
+    pub fn attach_save(&mut self, mut save: BatterySave) -> io::Result<bool> {
+        let loaded = save.load(self.bus.mapper_mut())?;
+        self.save = Some(save);
+        Ok(loaded)
+    }
+
+    /// Writes the save file unconditionally, to be called when the emulation ends
+    pub fn flush_save(&mut self) -> io::Result<()> {
+        match &mut self.save {
+            Some(save) => save.flush(self.bus.mapper_mut()),
+            None => Ok(())
+        }
+    }
+
+    /// Executes a single instruction, returns the number of machine cycles it took
+    pub fn step(&mut self) -> u32 {
+        self.cpu.step(&mut self.bus)
+    }
+
+    /**
+     * Runs until the PPU completes a frame, returns the number of machine cycles it took.
+     * With the LCD off or the CPU stopped no frame is ever completed, so it stops after the duration of one.
+     * Afterwards the save file is written if its autosave interval elapsed, which is the only way this fails.
+     */
+    pub fn run_frame(&mut self) -> io::Result<u32> {
+        let frame_cycles = if self.bus.double_speed() { CYCLES_PER_FRAME * 2 } else { CYCLES_PER_FRAME };
+
+        let mut cycles = 0;
+        while cycles < frame_cycles || (self.bus.ppu().enabled() && !self.cpu.stopped) {
+            cycles += self.step();
+            if self.bus.ppu_mut().take_frame() {
+                break;
+            }
+        }
+
+        if let Some(save) = &mut self.save {
+            save.autosave(self.bus.mapper_mut())?;
+        }
+        Ok(cycles)
+    }
+
+    /// The last frame, 160x144 pixels as 0xRRGGBB row by row
+    pub fn framebuffer(&self) -> &[u32] {
+        self.bus.ppu().framebuffer()
+    }
+}
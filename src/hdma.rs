@@ -0,0 +1,82 @@
+pub const HDMA1_ADDR: u16 = 0xFF51;
+pub const HDMA2_ADDR: u16 = 0xFF52;
+pub const HDMA3_ADDR: u16 = 0xFF53;
+pub const HDMA4_ADDR: u16 = 0xFF54;
+pub const HDMA5_ADDR: u16 = 0xFF55;
+
+// Set in HDMA5 to copy a block every HBlank instead of everything at once
+const HDMA5_HBLANK: u8 = 0x80;
+
+pub const HDMA_BLOCK_SIZE: u16 = 0x10;
+
+/**
+ * The CGB VRAM DMA registers, HDMA1-HDMA5.
+ * Writing HDMA5 copies (HDMA5 & 0x7F) + 1 blocks of 16 bytes from the source in HDMA1-2 to VRAM at the
+ * destination in HDMA3-4, either all at once (general purpose DMA) or one block at the start of every HBlank.
+ * The CPU is stalled while a block is copied, an HBlank transfer can be stopped by writing HDMA5 with bit 7 cleared.
+ * The copy itself is done by the bus, this only keeps track of the transfer.
+ */
+#[derive(Default)]
+pub struct Hdma {
+    source: u16,
+    // Offset in VRAM
+    destination: u16,
+    // Blocks left to copy
+    blocks: u8,
+    active: bool,
+    hblank: bool
+}
+
+impl Hdma {
+    pub fn new() -> Hdma {
+        Hdma::default()
+    }
+
+    /// A general purpose transfer was started and has not been done yet
+    pub fn general_purpose(&self) -> bool {
+        self.active && !self.hblank
+    }
+
+    /// An HBlank transfer has blocks left to copy
+    pub fn hblank(&self) -> bool {
+        self.active && self.hblank
+    }
+
+    /// The source and destination of the next block, advancing the transfer past it
+    pub fn next_block(&mut self) -> (u16, u16) {
+        let block = (self.source, 0x8000 + self.destination);
+
+        self.source = self.source.wrapping_add(HDMA_BLOCK_SIZE);
+        self.destination = (self.destination + HDMA_BLOCK_SIZE) & 0x1FF0;
+        self.blocks -= 1;
+        self.active = self.blocks > 0;
+        block
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            // Bit 7 is set while no transfer is running, the rest is the number of blocks left minus 1
+            HDMA5_ADDR => {
+                let status = if self.active { 0 } else { HDMA5_HBLANK };
+                status | (self.blocks.wrapping_sub(1) & 0x7F)
+            },
+            _ => 0xFF
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            HDMA1_ADDR => self.source = (self.source & 0x00FF) | (value as u16) << 8,
+            HDMA2_ADDR => self.source = (self.source & 0xFF00) | (value & 0xF0) as u16,
+            HDMA3_ADDR => self.destination = (self.destination & 0x00FF) | ((value & 0x1F) as u16) << 8,
+            HDMA4_ADDR => self.destination = (self.destination & 0xFF00) | (value & 0xF0) as u16,
+            HDMA5_ADDR if self.hblank() && value & HDMA5_HBLANK == 0 => self.active = false,
+            HDMA5_ADDR => {
+                self.blocks = (value & 0x7F) + 1;
+                self.hblank = value & HDMA5_HBLANK != 0;
+                self.active = true;
+            },
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,437 @@
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+use crate::opcode::{Opcode, OpcodeExt, TOpcode, OPCODE_NAMES};
+
+/// What an instruction does, independently of its operands
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Operation {
+    Nop,
+    Ld,
+    Push,
+    Pop,
+    Add,
+    Adc,
+    Sub,
+    Sbc,
+    And,
+    Xor,
+    Or,
+    Cp,
+    Inc,
+    Dec,
+    Daa,
+    Cpl,
+    Scf,
+    Ccf,
+    Rlca,
+    Rrca,
+    Rla,
+    Rra,
+    Rlc,
+    Rrc,
+    Rl,
+    Rr,
+    Sla,
+    Sra,
+    Swap,
+    Srl,
+    Bit,
+    Res,
+    Set,
+    Jp,
+    Jr,
+    Call,
+    Ret,
+    Reti,
+    Rst,
+    Halt,
+    Stop,
+    Di,
+    Ei,
+    // One of the opcodes removed from the CPU, executing it hangs the hardware
+    Illegal
+}
+
+impl Operation {
+    pub fn mnemonic(&self) -> &'static str {
+        use Operation::*;
+
+        match self {
+            Nop     => "NOP",
+            Ld      => "LD",
+            Push    => "PUSH",
+            Pop     => "POP",
+            Add     => "ADD",
+            Adc     => "ADC",
+            Sub     => "SUB",
+            Sbc     => "SBC",
+            And     => "AND",
+            Xor     => "XOR",
+            Or      => "OR",
+            Cp      => "CP",
+            Inc     => "INC",
+            Dec     => "DEC",
+            Daa     => "DAA",
+            Cpl     => "CPL",
+            Scf     => "SCF",
+            Ccf     => "CCF",
+            Rlca    => "RLCA",
+            Rrca    => "RRCA",
+            Rla     => "RLA",
+            Rra     => "RRA",
+            Rlc     => "RLC",
+            Rrc     => "RRC",
+            Rl      => "RL",
+            Rr      => "RR",
+            Sla     => "SLA",
+            Sra     => "SRA",
+            Swap    => "SWAP",
+            Srl     => "SRL",
+            Bit     => "BIT",
+            Res     => "RES",
+            Set     => "SET",
+            Jp      => "JP",
+            Jr      => "JR",
+            Call    => "CALL",
+            Ret     => "RET",
+            Reti    => "RETI",
+            Rst     => "RST",
+            Halt    => "HALT",
+            Stop    => "STOP",
+            Di      => "DI",
+            Ei      => "EI",
+            Illegal => "ILLEGAL"
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Register {
+    A,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RegisterPair {
+    AF,
+    BC,
+    DE,
+    HL,
+    SP
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Condition {
+    NZ,
+    Z,
+    NC,
+    C
+}
+
+/// A memory location accessed by an instruction
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Memory {
+    BC,
+    DE,
+    HL,
+    HLIncrement,        // HL, incremented after the access
+    HLDecrement,        // HL, decremented after the access
+    HighC,              // 0xFF00 + C
+    Address(u16),
+    HighAddress(u8)     // 0xFF00 + n
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Operand {
+    Register(Register),
+    RegisterPair(RegisterPair),
+    Immediate8(u8),
+    Immediate16(u16),
+    Offset(i8),         // Relative jump or addition to SP
+    SpOffset(i8),       // SP plus a signed offset, only loaded into HL
+    Memory(Memory),
+    Condition(Condition),
+    Bit(u8),
+    Vector(u8)          // Restart address
+}
+
+/**
+ * A decoded instruction.
+ * The operands are in assembly order, destination first, and the accumulator is listed
+ * for ADD, ADC and SBC but implied for SUB, AND, XOR, OR and CP.
+ */
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Instruction {
+    pub operation: Operation,
+    operands: [Operand; 2],
+    operand_count: usize,
+    // Size in bytes, including the 0xCB prefix of extended opcodes
+    pub length: usize,
+    // Duration in machine cycles when not branching and when branching, 0 for removed opcodes
+    pub cycles: usize,
+    pub cycles_taken: usize
+}
+
+impl Instruction {
+    pub fn operands(&self) -> &[Operand] {
+        &self.operands[..self.operand_count]
+    }
+
+    /// The mnemonic to write, loads from 0xFF00-0xFFFF using the short addressing forms are `LDH`
+    pub fn mnemonic(&self) -> &'static str {
+        let high = self.operands().iter().any(|operand| {
+            matches!(operand, Operand::Memory(Memory::HighC | Memory::HighAddress(_)))
+        });
+
+        if high { "LDH" } else { self.operation.mnemonic() }
+    }
+
+    /// The condition of a conditional jump, call or return
+    pub fn condition(&self) -> Option<Condition> {
+        self.operands().iter().find_map(|operand| match operand {
+            Operand::Condition(condition) => Some(*condition),
+            _ => None
+        })
+    }
+
+    /**
+     * The address a jump, call or restart at `addr` transfers control to.
+     * Returns `None` for other instructions and for jumps whose target is only known at runtime.
+     */
+    pub fn target(&self, addr: u16) -> Option<u16> {
+        match (self.operation, self.operands().last()) {
+            (Operation::Jp | Operation::Call, Some(Operand::Immediate16(nn))) => Some(*nn),
+            (Operation::Jr, Some(Operand::Offset(offset))) => {
+                Some(addr.wrapping_add(self.length as u16).wrapping_add(*offset as u16))
+            },
+            (Operation::Rst, Some(Operand::Vector(vector))) => Some(*vector as u16),
+            _ => None
+        }
+    }
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Operand::Register(r)        => write!(f, "{:?}", r),
+            Operand::RegisterPair(rr)   => write!(f, "{:?}", rr),
+            Operand::Immediate8(n)      => write!(f, "${:02X}", n),
+            Operand::Immediate16(nn)    => write!(f, "${:04X}", nn),
+            Operand::Offset(d)          => write!(f, "{:+}", d),
+            Operand::SpOffset(d)        => write!(f, "SP{:+}", d),
+            Operand::Condition(c)       => write!(f, "{:?}", c),
+            Operand::Bit(bit)           => write!(f, "{}", bit),
+            Operand::Vector(vector)     => write!(f, "${:02X}", vector),
+            Operand::Memory(memory) => match memory {
+                Memory::BC              => write!(f, "(BC)"),
+                Memory::DE              => write!(f, "(DE)"),
+                Memory::HL              => write!(f, "(HL)"),
+                Memory::HLIncrement     => write!(f, "(HL+)"),
+                Memory::HLDecrement     => write!(f, "(HL-)"),
+                Memory::HighC           => write!(f, "($FF00+C)"),
+                Memory::Address(nn)     => write!(f, "(${:04X})", nn),
+                Memory::HighAddress(n)  => write!(f, "(${:04X})", 0xFF00 | *n as u16)
+            }
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        for (i, operand) in self.operands().iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, operand)?;
+        }
+        Ok(())
+    }
+}
+
+/// The size of the instruction starting with `opcode`, known before fetching the rest of it
+pub fn length(opcode: u8) -> usize {
+    match Opcode::from_byte(opcode) {
+        Opcode::EXT_OPS => 2,
+        // Removed opcodes have no size, they are a single byte
+        opcode => opcode.size().max(1)
+    }
+}
+
+/**
+ * Decodes the instruction at the start of `code`, extra bytes after it are ignored.
+ * Returns `None` if `code` ends before the instruction does, `length` tells its size from the first byte.
+ */
+pub fn decode(code: &[u8]) -> Option<Instruction> {
+    let index = match *code.first()? {
+        0xCB => 0x100 + *code.get(1)? as usize,
+        opcode => opcode as usize
+    };
+    let mut instruction = templates()[index];
+    if code.len() < instruction.length {
+        return None;
+    }
+
+    for operand in &mut instruction.operands[..instruction.operand_count] {
+        let n = || code[1];
+        let nn = || ((code[2] as u16) << 8) | code[1] as u16;
+
+        *operand = match *operand {
+            Operand::Immediate8(_)                      => Operand::Immediate8(n()),
+            Operand::Immediate16(_)                     => Operand::Immediate16(nn()),
+            Operand::Offset(_)                          => Operand::Offset(n() as i8),
+            Operand::SpOffset(_)                        => Operand::SpOffset(n() as i8),
+            Operand::Memory(Memory::Address(_))         => Operand::Memory(Memory::Address(nn())),
+            Operand::Memory(Memory::HighAddress(_))     => Operand::Memory(Memory::HighAddress(n())),
+            other => other
+        };
+    }
+
+    Some(instruction)
+}
+
+/// Every instruction with its immediate operands set to 0, indexed like OPCODE_NAMES
+fn templates() -> &'static [Instruction] {
+    static TEMPLATES: OnceLock<Vec<Instruction>> = OnceLock::new();
+
+    TEMPLATES.get_or_init(|| {
+        (0..OPCODE_NAMES.len())
+            .map(|index| {
+                let (name, length, cycles, cycles_taken) = if index < 0x100 {
+                    let opcode = Opcode::from_byte(index as u8);
+                    (opcode.name(), opcode.size().max(1), opcode.cycles(), opcode.cycles_taken())
+                } else {
+                    let opcode = OpcodeExt::from_byte((index - 0x100) as u8);
+                    (opcode.name(), opcode.size(), opcode.cycles(), opcode.cycles_taken())
+                };
+
+                // The names are fixed, an unknown one can only come from a typo in the opcode tables
+                let (operation, operands) = parse_name(name, index >= 0x100).unwrap_or((Operation::Illegal, Vec::new()));
+                let mut instruction = Instruction {
+                    operation,
+                    operands: [Operand::Immediate8(0); 2],
+                    operand_count: operands.len(),
+                    length,
+                    cycles,
+                    cycles_taken
+                };
+                instruction.operands[..operands.len()].copy_from_slice(&operands);
+                instruction
+            })
+            .collect()
+    })
+}
+
+/**
+ * Turns an opcode name such as `LDI_HLa_A` into the operation and the operands it stands for.
+ * Returns `None` for names with an unknown mnemonic or operand.
+ */
+fn parse_name(name: &str, extended: bool) -> Option<(Operation, Vec<Operand>)> {
+    use Operation::*;
+
+    if name.starts_with("XX__") || name == "EXT_OPS" {
+        return Some((Illegal, Vec::new()));
+    }
+
+    // The 'r' prefix marks extended opcodes redefining a base one, it is not part of the mnemonic
+    let name = name.strip_prefix('r').unwrap_or(name);
+    let mut tokens = name.split('_');
+    let mnemonic = tokens.next().unwrap_or_default();
+    let mut tokens: Vec<&str> = tokens.collect();
+
+    let operation = match mnemonic {
+        // The one-byte rotations of A leave the zero flag cleared, unlike the extended ones
+        "RLC" if !extended => return Some((Rlca, Vec::new())),
+        "RRC" if !extended => return Some((Rrca, Vec::new())),
+        "RL" if !extended  => return Some((Rla, Vec::new())),
+        "RR" if !extended  => return Some((Rra, Vec::new())),
+        "JP" if tokens == ["HLa"] => return Some((Jp, vec![Operand::RegisterPair(RegisterPair::HL)])),
+        "LDHL" => return Some((Ld, vec![Operand::RegisterPair(RegisterPair::HL), Operand::SpOffset(0)])),
+        "SUB" if !tokens.is_empty() => {
+            tokens.remove(0);
+            Sub
+        },
+        "NOP"   => Nop,
+        "LD" | "LDI" | "LDD" | "LDH" => Ld,
+        "PUSH"  => Push,
+        "POP"   => Pop,
+        "ADD"   => Add,
+        "ADC"   => Adc,
+        "SBC"   => Sbc,
+        "AND"   => And,
+        "XOR"   => Xor,
+        "OR"    => Or,
+        "CP"    => Cp,
+        "INC"   => Inc,
+        "DEC"   => Dec,
+        "DAA"   => Daa,
+        "CPL"   => Cpl,
+        "SCF"   => Scf,
+        "CCF"   => Ccf,
+        "RLC"   => Rlc,
+        "RRC"   => Rrc,
+        "RL"    => Rl,
+        "RR"    => Rr,
+        "SLA"   => Sla,
+        "SRA"   => Sra,
+        "SWAP"  => Swap,
+        "SRL"   => Srl,
+        "BIT"   => Bit,
+        "RES"   => Res,
+        "SET"   => Set,
+        "JP"    => Jp,
+        "JR"    => Jr,
+        "CALL"  => Call,
+        "RET"   => Ret,
+        "RETI"  => Reti,
+        "RST"   => Rst,
+        "HALT"  => Halt,
+        "STOP"  => Stop,
+        "DI"    => Di,
+        "EI"    => Ei,
+        _ => return None
+    };
+
+    let branch = matches!(operation, Jp | Jr | Call | Ret);
+    let operands = tokens.iter()
+        .map(|&token| Some(match token {
+            "A" => Operand::Register(Register::A),
+            "B" => Operand::Register(Register::B),
+            "C" if branch => Operand::Condition(Condition::C),
+            "C" => Operand::Register(Register::C),
+            "D" => Operand::Register(Register::D),
+            "E" => Operand::Register(Register::E),
+            "H" => Operand::Register(Register::H),
+            "L" => Operand::Register(Register::L),
+            "AF" => Operand::RegisterPair(RegisterPair::AF),
+            "BC" => Operand::RegisterPair(RegisterPair::BC),
+            "DE" => Operand::RegisterPair(RegisterPair::DE),
+            "HL" => Operand::RegisterPair(RegisterPair::HL),
+            "SP" => Operand::RegisterPair(RegisterPair::SP),
+            "NZ" => Operand::Condition(Condition::NZ),
+            "Z"  => Operand::Condition(Condition::Z),
+            "NC" => Operand::Condition(Condition::NC),
+            "BCa" => Operand::Memory(Memory::BC),
+            "DEa" => Operand::Memory(Memory::DE),
+            "HLa" if mnemonic == "LDI" => Operand::Memory(Memory::HLIncrement),
+            "HLa" if mnemonic == "LDD" => Operand::Memory(Memory::HLDecrement),
+            "HLa" => Operand::Memory(Memory::HL),
+            "Ca"  => Operand::Memory(Memory::HighC),
+            "n" if operation == Jr => Operand::Offset(0),
+            "n"   => Operand::Immediate8(0),
+            "nn"  => Operand::Immediate16(0),
+            "na"  => Operand::Memory(Memory::HighAddress(0)),
+            "nna" => Operand::Memory(Memory::Address(0)),
+            "d"   => Operand::Offset(0),
+            vector if operation == Rst => Operand::Vector(u8::from_str_radix(vector, 16).ok()?),
+            bit => Operand::Bit(bit.parse().ok()?)
+        }))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some((operation, operands))
+}
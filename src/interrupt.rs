@@ -0,0 +1,64 @@
+pub const IF_ADDR: u16 = 0xFF0F;
+pub const IE_ADDR: u16 = 0xFFFF;
+
+// Only the lower 5 bits of IE and IF are connected to an interrupt source
+pub const INTERRUPT_MASK: u8 = 0x1F;
+
+/// Interrupt sources, in order of priority
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Interrupt {
+    VBlank  = 0,
+    Stat    = 1,
+    Timer   = 2,
+    Serial  = 3,
+    Joypad  = 4
+}
+
+impl Interrupt {
+    const ALL: [Interrupt; 5] = [Interrupt::VBlank, Interrupt::Stat, Interrupt::Timer, Interrupt::Serial, Interrupt::Joypad];
+
+    /// The bit of the interrupt in IE and IF
+    pub fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    /// The address the CPU jumps to when servicing the interrupt
+    pub fn vector(self) -> u16 {
+        0x40 + 8 * self as u16
+    }
+
+    /// The interrupt with the highest priority among the bits set in `pending`
+    pub fn highest(pending: u8) -> Option<Interrupt> {
+        Interrupt::ALL.into_iter().find(|interrupt| pending & interrupt.bit() != 0)
+    }
+}
+
+/**
+ * The IE (0xFFFF) and IF (0xFF0F) registers.
+ * Peripherals request interrupts by setting their bit in IF, the CPU services the ones also enabled in IE.
+ */
+#[derive(Default)]
+pub struct Interrupts {
+    pub enable: u8,
+    pub flags: u8
+}
+
+impl Interrupts {
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.flags |= interrupt.bit();
+    }
+
+    /// Requested interrupts which are also enabled
+    pub fn pending(&self) -> u8 {
+        self.enable & self.flags & INTERRUPT_MASK
+    }
+
+    pub fn read_flags(&self) -> u8 {
+        // The upper 3 bits of IF do not exist and read as 1
+        self.flags | !INTERRUPT_MASK
+    }
+
+    pub fn write_flags(&mut self, value: u8) {
+        self.flags = value & INTERRUPT_MASK;
+    }
+}
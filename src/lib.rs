@@ -0,0 +1,18 @@
+pub mod apu;
+pub mod asm;
+pub mod audio;
+pub mod bus;
+pub mod cartridge;
+pub mod cpu;
+pub mod disasm;
+pub mod dma;
+pub mod gameboy;
+pub mod hdma;
+pub mod instruction;
+pub mod interrupt;
+pub mod mbc;
+pub mod model;
+mod opcode;
+pub mod ppu;
+pub mod save;
+pub mod timer;
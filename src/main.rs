@@ -1,26 +1,249 @@
-mod opcode;
-mod cartridge;
+use std::fs;
 
-use clap::Parser;
-use cartridge::Cartridge;
+use clap::{Parser, Subcommand};
+use emu::audio::wav;
+use emu::cartridge::Cartridge;
+use emu::disasm;
+use emu::gameboy::GameBoy;
+use emu::model::Model;
+use emu::ppu::{Renderer, SCREEN_HEIGHT, SCREEN_WIDTH};
+use emu::save::BatterySave;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
+/// Prints the cartridge header of `FILE`, or runs one of the subcommands
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    file: String
+    #[clap(required = true)]
+    file: Option<String>,
+
+    /// Keep loading cartridges with unknown or unsupported header values
+    #[clap(long)]
+    lenient: bool,
+
+    /// Check the logo and checksums, failing if any of them does not match
+    #[clap(long)]
+    verify: bool,
+
+    /// Write a copy of the ROM with corrected logo and checksums
+    #[clap(long, value_name = "OUTPUT")]
+    fix: Option<String>,
+
+    #[clap(subcommand)]
+    command: Option<Command>
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Disassemble a ROM bank
+    Disasm {
+        rom: String,
+
+        #[clap(long, default_value_t = 0)]
+        bank: usize,
+
+        /// First address to decode, defaults to the start of the bank
+        #[clap(long, parse(try_from_str = parse_addr))]
+        from: Option<u16>,
+
+        /// Address to stop decoding at (exclusive), defaults to the end of the bank
+        #[clap(long, parse(try_from_str = parse_addr))]
+        to: Option<u16>,
+
+        /// Follow the control flow from the entry points and list unreachable bytes as data
+        #[clap(long)]
+        trace: bool,
+
+        /// Export the whole ROM as reassemblable RGBDS source instead of printing a listing
+        #[clap(long, value_name = "OUTPUT")]
+        rgbds: Option<String>
+    },
+
+    /// Run a ROM without a display for a number of frames
+    Run {
+        rom: String,
+
+        #[clap(long, default_value_t = 60)]
+        frames: u32,
+
+        /// Run DMG games on a CGB in compatibility mode, instead of on a DMG
+        #[clap(long)]
+        cgb: bool,
 
-    let cart = Cartridge::from_file(&args.file)
-        .context("Cannot load cartridge, make sure the file exists and it is a valid Game Boy ROM")?;
+        /// PPU renderer, `scanline` or `fifo` for effects which change registers in the middle of a line
+        #[clap(long, default_value = "scanline", parse(try_from_str = parse_renderer))]
+        renderer: Renderer,
+
+        /// Save the last frame as a PPM image
+        #[clap(long, value_name = "OUTPUT")]
+        screenshot: Option<String>,
+
+        /// Record the audio of all frames as a 16-bit stereo WAV file
+        #[clap(long, value_name = "OUTPUT")]
+        wav: Option<String>
+    }
+}
+
+/// Accepts addresses in decimal, or in hexadecimal with a `0x` or `$` prefix
+fn parse_addr(s: &str) -> Result<u16> {
+    let addr = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix('$')) {
+        u16::from_str_radix(hex, 16)
+    } else {
+        s.parse()
+    };
+    addr.with_context(|| format!("Invalid address {}", s))
+}
+
+fn load(file: &str, lenient: bool) -> Result<Cartridge> {
+    if lenient {
+        Cartridge::from_file_lenient(file)
+    } else {
+        Cartridge::from_file(file)
+    }.context("Cannot load cartridge, make sure the file exists and it is a valid Game Boy ROM")
+}
+
+fn info(file: &str, lenient: bool, verify: bool, fix: Option<&str>) -> Result<()> {
+    let cart = load(file, lenient)?;
 
     println!("Loaded cartridge!");
 
     println!("{}", cart);
 
+    if let Some(output) = fix {
+        cart.write_fixed(output)
+            .with_context(|| format!("Cannot write fixed ROM to {}", output))?;
+        println!("Fixed ROM written to {}", output);
+    }
+
+    if verify {
+        let report = cart.verify();
+        println!("{}", report);
+
+        if !report.is_valid() {
+            bail!("Cartridge verification failed");
+        }
+    }
+
+    Ok(())
+}
+
+fn disasm(rom: &str, bank: usize, from: Option<u16>, to: Option<u16>, trace: bool) -> Result<()> {
+    // Only the bytes matter here, so odd headers are not a reason to refuse
+    let cart = load(rom, true)?;
+
+    let banks = disasm::bank_count(&cart);
+    if bank >= banks {
+        bail!("Bank {} is past the end of the ROM, valid banks are 0-{}", bank, banks - 1);
+    }
+
+    // The bank is visible at base..end, `to` may be end itself to decode up to the last byte
+    let base = disasm::bank_base(bank);
+    let end = base + 0x4000;
+    let from = from.unwrap_or(base);
+    let to = to.unwrap_or(end);
+
+    if !(base..end).contains(&from) {
+        bail!("Start address {:#06X} is outside bank {}, valid addresses are {:#06X}-{:#06X}", from, bank, base, end - 1);
+    }
+    if !(base + 1..=end).contains(&to) {
+        bail!("End address {:#06X} is outside bank {}, valid addresses are {:#06X}-{:#06X}", to, bank, base + 1, end);
+    }
+    if from >= to {
+        bail!("Start address {:#06X} must be below the end address {:#06X}", from, to);
+    }
+
+    let lines = if trace {
+        disasm::Trace::new(&cart).listing(&cart, bank).into_iter()
+            .filter(|line| line.addr >= from && line.addr < to)
+            .collect()
+    } else {
+        disasm::disassemble_range(&cart, bank, from, to)
+    };
+
+    for line in lines {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+fn export_rgbds(rom: &str, output: &str) -> Result<()> {
+    let cart = load(rom, true)?;
+    let source = disasm::export_rgbds(&cart, &disasm::Trace::new(&cart));
+
+    fs::write(output, source)
+        .with_context(|| format!("Cannot write RGBDS source to {}", output))?;
+    println!("RGBDS source written to {}", output);
+
+    Ok(())
+}
+
+fn parse_renderer(s: &str) -> Result<Renderer> {
+    match s {
+        "scanline"  => Ok(Renderer::Scanline),
+        "fifo"      => Ok(Renderer::Fifo),
+        _ => bail!("Unknown renderer {}, expected scanline or fifo", s)
+    }
+}
+
+/// Encodes a frame as a binary PPM image
+fn ppm(framebuffer: &[u32]) -> Vec<u8> {
+    let mut image = format!("P6\n{} {}\n255\n", SCREEN_WIDTH, SCREEN_HEIGHT).into_bytes();
+    for pixel in framebuffer {
+        image.extend_from_slice(&pixel.to_be_bytes()[1..]);
+    }
+    image
+}
+
+fn run(rom: &str, frames: u32, cgb: bool, renderer: Renderer, screenshot: Option<&str>, wav_output: Option<&str>) -> Result<()> {
+    let cart = load(rom, false)?;
+    let model = if cgb { Model::cgb_for(&cart) } else { Model::for_cartridge(&cart) };
+    let mut gb = GameBoy::with_model(&cart, model).context("Cannot start the emulation")?;
+    gb.bus.ppu_mut().set_renderer(renderer);
+
+    // Kept for the error messages, running a frame only fails when the save file cannot be written
+    let mut save_path = String::new();
+    if let Some(save) = BatterySave::for_rom(rom, &cart) {
+        save_path = save.path().display().to_string();
+        gb.attach_save(save).with_context(|| format!("Cannot load save file {}", save_path))?;
+    }
+
+    let mut audio = Vec::new();
+    for _ in 0..frames {
+        gb.run_frame().with_context(|| format!("Cannot write save file {}", save_path))?;
+        // Samples are taken every frame either way, so that they do not pile up in the APU
+        let samples = gb.bus.apu_mut().take_samples();
+        if wav_output.is_some() {
+            audio.extend(samples);
+        }
+    }
+    gb.flush_save().with_context(|| format!("Cannot write save file {}", save_path))?;
+
+    if let Some(output) = screenshot {
+        fs::write(output, ppm(gb.framebuffer()))
+            .with_context(|| format!("Cannot write screenshot to {}", output))?;
+        println!("Screenshot written to {}", output);
+    }
+
+    if let Some(output) = wav_output {
+        fs::write(output, wav(&audio, gb.bus.apu().sample_rate()))
+            .with_context(|| format!("Cannot write audio to {}", output))?;
+        println!("Audio written to {}", output);
+    }
+
     Ok(())
 }
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    match args.command {
+        None => info(&args.file.unwrap_or_default(), args.lenient, args.verify, args.fix.as_deref()),
+        Some(Command::Disasm { rom, rgbds: Some(output), .. }) => export_rgbds(&rom, &output),
+        Some(Command::Disasm { rom, bank, from, to, trace, .. }) => disasm(&rom, bank, from, to, trace),
+        Some(Command::Run { rom, frames, cgb, renderer, screenshot, wav }) =>
+            run(&rom, frames, cgb, renderer, screenshot.as_deref(), wav.as_deref())
+    }
+}
@@ -0,0 +1,104 @@
+mod rom_only;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod rtc;
+
+pub use rom_only::RomOnly;
+pub use mbc1::MBC1;
+pub use mbc2::{MBC2, MBC2_RAM_SIZE};
+pub use mbc3::MBC3;
+pub use mbc5::MBC5;
+pub use rtc::{Clock, SystemClock, FakeClock, Rtc, RtcRegisters, RtcState};
+
+use crate::cartridge::{Cartridge, CartridgeError, CartridgeType};
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+/**
+ * A memory bank controller, which decides what the CPU sees in the cartridge address ranges:
+ * ROM at 0x0000-0x7FFF and external RAM at 0xA000-0xBFFF.
+ * Writes to the ROM range do not modify the ROM but program the controller registers.
+ */
+pub trait Mapper {
+    fn read(&self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, value: u8);
+
+    /**
+     * Registers a function called with the new motor state every time a rumble cartridge
+     * turns its motor on or off. Cartridges without a motor never call it.
+     */
+    fn set_rumble_callback(&mut self, _callback: Box<dyn FnMut(bool)>) {}
+
+    /// The external RAM contents, empty for cartridges without RAM
+    fn ram(&self) -> &[u8];
+    fn ram_mut(&mut self) -> &mut [u8];
+
+    fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        None
+    }
+}
+
+pub fn from_cartridge(cart: &Cartridge) -> Result<Box<dyn Mapper>, CartridgeError> {
+    from_cartridge_with_clock(cart, Box::new(SystemClock))
+}
+
+/**
+ * Like `from_cartridge`, with the real-time clock of MBC3 timer cartridges driven by the given clock.
+ * The clock is ignored for cartridges without one.
+ */
+pub fn from_cartridge_with_clock(cart: &Cartridge, clock: Box<dyn Clock>) -> Result<Box<dyn Mapper>, CartridgeError> {
+    let rom = cart.data().to_vec();
+    let ram = vec![0; cart.ram_size_bytes().unwrap_or(0)];
+
+    match cart.ty() {
+        CartridgeType::RomOnly |
+        CartridgeType::RomRam |
+        CartridgeType::RomRamBattery    => Ok(Box::new(RomOnly::new(rom, ram))),
+
+        CartridgeType::MBC1 |
+        CartridgeType::MBC1Ram |
+        CartridgeType::MBC1RamBattery   => Ok(Box::new(MBC1::new(rom, ram))),
+
+        CartridgeType::MBC2 |
+        CartridgeType::MBC2Battery      => Ok(Box::new(MBC2::new(rom, ram))),
+
+        CartridgeType::MBC3TimerBattery |
+        CartridgeType::MBC3TimerRamBattery => Ok(Box::new(MBC3::new(rom, ram, Some(Rtc::new(clock))))),
+
+        CartridgeType::MBC3 |
+        CartridgeType::MBC3Ram |
+        CartridgeType::MBC3RamBattery   => Ok(Box::new(MBC3::new(rom, ram, None))),
+
+        CartridgeType::MBC5 |
+        CartridgeType::MBC5Ram |
+        CartridgeType::MBC5RamBattery   => Ok(Box::new(MBC5::new(rom, ram, false))),
+
+        CartridgeType::MBC5Rumble |
+        CartridgeType::MBC5RumbleRam |
+        CartridgeType::MBC5RumbleRamBattery => Ok(Box::new(MBC5::new(rom, ram, true))),
+
+        ty => Err(CartridgeError::UnsupportedMapper(ty))
+    }
+}
+
+/**
+ * Reads a byte from the given bank, banks past the end of the data wrap around
+ * like they do on hardware where the upper bank bits are simply not connected.
+ * Reads outside the data (e.g. from a truncated dump) return 0xFF like an open bus.
+ */
+fn read_banked(data: &[u8], bank_size: usize, bank: usize, offset: usize) -> u8 {
+    let banks = (data.len() / bank_size).max(1);
+    data.get((bank % banks) * bank_size + offset)
+        .copied()
+        .unwrap_or(0xFF)
+}
+
+fn write_banked(data: &mut [u8], bank_size: usize, bank: usize, offset: usize, value: u8) {
+    let banks = (data.len() / bank_size).max(1);
+    if let Some(byte) = data.get_mut((bank % banks) * bank_size + offset) {
+        *byte = value;
+    }
+}
@@ -0,0 +1,104 @@
+use super::{Mapper, read_banked, write_banked, ROM_BANK_SIZE, RAM_BANK_SIZE};
+use crate::cartridge::NINTENDO_LOGO;
+
+const MULTICART_SIZE: usize = 1024 * 1024;
+
+/**
+ * MBC1 supports up to 2 MiB of ROM and 32 KiB of RAM.
+ * The 2-bit BANK2 register either extends the ROM bank number (bits 5-6)
+ * or selects the RAM bank, depending on the banking mode.
+ * In mode 1 BANK2 also applies to the 0x0000-0x3FFF area.
+ *
+ * Multicarts (MBC1M) wire BANK2 to bits 4-5 of the ROM bank instead,
+ * leaving only 4 bits of BANK1 connected, so that each game sees 256 KiB.
+ */
+pub struct MBC1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    multicart: bool,
+
+    ram_enabled: bool,
+    bank1: u8,  // 5 bits, 0 is translated to 1
+    bank2: u8,  // 2 bits
+    mode: bool
+}
+
+impl MBC1 {
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>) -> MBC1 {
+        let multicart = MBC1::detect_multicart(&rom);
+        MBC1 {
+            rom,
+            ram,
+            multicart,
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            mode: false
+        }
+    }
+
+    /**
+     * There is no header flag for multicarts, the usual heuristic is to look for
+     * a second copy of the Nintendo logo in the header of the game at bank 0x10.
+     */
+    fn detect_multicart(rom: &[u8]) -> bool {
+        let logo = 0x10 * ROM_BANK_SIZE + 0x104;
+        rom.len() == MULTICART_SIZE && rom[logo..logo + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+    }
+
+    fn bank2_shift(&self) -> u32 {
+        if self.multicart { 4 } else { 5 }
+    }
+
+    fn low_rom_bank(&self) -> usize {
+        if self.mode {
+            (self.bank2 as usize) << self.bank2_shift()
+        } else {
+            0
+        }
+    }
+
+    fn high_rom_bank(&self) -> usize {
+        let bank1 = if self.multicart { self.bank1 & 0x0F } else { self.bank1 };
+        ((self.bank2 as usize) << self.bank2_shift()) | bank1 as usize
+    }
+
+    fn ram_bank(&self) -> usize {
+        if self.mode { self.bank2 as usize } else { 0 }
+    }
+}
+
+impl Mapper for MBC1 {
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_banked(&self.rom, ROM_BANK_SIZE, self.low_rom_bank(), addr as usize),
+            0x4000..=0x7FFF => read_banked(&self.rom, ROM_BANK_SIZE, self.high_rom_bank(), (addr - 0x4000) as usize),
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram.is_empty() => {
+                read_banked(&self.ram, RAM_BANK_SIZE, self.ram_bank(), (addr - 0xA000) as usize)
+            },
+            _ => 0xFF
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.bank1 = (value & 0x1F).max(1),
+            0x4000..=0x5FFF => self.bank2 = value & 0x03,
+            0x6000..=0x7FFF => self.mode = value & 0x01 != 0,
+            0xA000..=0xBFFF if self.ram_enabled => {
+                let bank = self.ram_bank();
+                write_banked(&mut self.ram, RAM_BANK_SIZE, bank, (addr - 0xA000) as usize, value);
+            },
+            _ => {}
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
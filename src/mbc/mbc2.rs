@@ -0,0 +1,64 @@
+use super::{Mapper, read_banked, ROM_BANK_SIZE};
+
+pub const MBC2_RAM_SIZE: usize = 512;
+
+/**
+ * MBC2 supports up to 256 KiB of ROM and has 512 half-bytes of RAM built into the controller.
+ * Only the lower nibble of each RAM byte exists, the upper one reads as 1s,
+ * and the 512 bytes are echoed through the whole 0xA000-0xBFFF range.
+ * The RAM enable and ROM bank registers share 0x0000-0x3FFF, address bit 8 selects between them.
+ */
+pub struct MBC2 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+
+    ram_enabled: bool,
+    rom_bank: u8    // 4 bits, 0 is translated to 1
+}
+
+impl MBC2 {
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>) -> MBC2 {
+        MBC2 {
+            rom,
+            ram,
+            ram_enabled: false,
+            rom_bank: 1
+        }
+    }
+}
+
+impl Mapper for MBC2 {
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_banked(&self.rom, ROM_BANK_SIZE, 0, addr as usize),
+            0x4000..=0x7FFF => read_banked(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, (addr - 0x4000) as usize),
+            0xA000..=0xBFFF if self.ram_enabled => {
+                let offset = (addr as usize) % MBC2_RAM_SIZE;
+                self.ram.get(offset).map_or(0xFF, |v| v | 0xF0)
+            },
+            _ => 0xFF
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x3FFF if addr & 0x0100 == 0 => self.ram_enabled = value & 0x0F == 0x0A,
+            0x0000..=0x3FFF => self.rom_bank = (value & 0x0F).max(1),
+            0xA000..=0xBFFF if self.ram_enabled => {
+                let offset = (addr as usize) % MBC2_RAM_SIZE;
+                if let Some(byte) = self.ram.get_mut(offset) {
+                    *byte = value & 0x0F;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
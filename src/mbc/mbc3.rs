@@ -0,0 +1,86 @@
+use super::{Mapper, read_banked, write_banked, ROM_BANK_SIZE, RAM_BANK_SIZE};
+use super::rtc::Rtc;
+
+/**
+ * MBC3 supports up to 2 MiB of ROM through a 7-bit bank number and 32 KiB of RAM,
+ * plus an optional real-time clock whose registers are mapped in place of RAM
+ * when a register number 0x08-0x0C is written to 0x4000-0x5FFF.
+ */
+pub struct MBC3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rtc: Option<Rtc>,
+
+    ram_enabled: bool,
+    rom_bank: u8,   // 7 bits, 0 is translated to 1
+    ram_bank: u8,   // 0x00-0x07 selects RAM, 0x08-0x0C selects an RTC register
+    latch: u8       // Last value written to the latch register
+}
+
+impl MBC3 {
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>, rtc: Option<Rtc>) -> MBC3 {
+        MBC3 {
+            rom,
+            ram,
+            rtc,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            latch: 0xFF
+        }
+    }
+}
+
+impl Mapper for MBC3 {
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_banked(&self.rom, ROM_BANK_SIZE, 0, addr as usize),
+            0x4000..=0x7FFF => read_banked(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, (addr - 0x4000) as usize),
+            0xA000..=0xBFFF if self.ram_enabled => match (self.ram_bank, &self.rtc) {
+                (0x00..=0x07, _) if !self.ram.is_empty() => {
+                    read_banked(&self.ram, RAM_BANK_SIZE, self.ram_bank as usize, (addr - 0xA000) as usize)
+                },
+                (0x08..=0x0C, Some(rtc)) => rtc.read(self.ram_bank),
+                _ => 0xFF
+            },
+            _ => 0xFF
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = (value & 0x7F).max(1),
+            0x4000..=0x5FFF => self.ram_bank = value & 0x0F,
+            0x6000..=0x7FFF => {
+                if self.latch == 0x00 && value == 0x01 {
+                    if let Some(rtc) = &mut self.rtc {
+                        rtc.latch();
+                    }
+                }
+                self.latch = value;
+            },
+            0xA000..=0xBFFF if self.ram_enabled => match (self.ram_bank, &mut self.rtc) {
+                (0x00..=0x07, _) => {
+                    let bank = self.ram_bank as usize;
+                    write_banked(&mut self.ram, RAM_BANK_SIZE, bank, (addr - 0xA000) as usize, value);
+                },
+                (0x08..=0x0C, Some(rtc)) => rtc.write(self.ram_bank, value),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        self.rtc.as_mut()
+    }
+}
@@ -0,0 +1,97 @@
+use super::{Mapper, read_banked, write_banked, ROM_BANK_SIZE, RAM_BANK_SIZE};
+
+/**
+ * MBC5 supports up to 8 MiB of ROM through a 9-bit bank number and 128 KiB of RAM in 16 banks.
+ * Unlike the older controllers, ROM bank 0 can also be mapped at 0x4000-0x7FFF.
+ * On rumble cartridges bit 3 of the RAM bank register drives the motor instead,
+ * leaving only 8 RAM banks addressable.
+ */
+pub struct MBC5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rumble: Option<Rumble>,
+
+    ram_enabled: bool,
+    rom_bank: u16,  // 9 bits
+    ram_bank: u8
+}
+
+struct Rumble {
+    active: bool,
+    callback: Option<Box<dyn FnMut(bool)>>
+}
+
+impl MBC5 {
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>, rumble: bool) -> MBC5 {
+        MBC5 {
+            rom,
+            ram,
+            rumble: rumble.then(|| Rumble { active: false, callback: None }),
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0
+        }
+    }
+
+    pub fn rumble_active(&self) -> bool {
+        self.rumble.as_ref().is_some_and(|r| r.active)
+    }
+
+    fn write_ram_bank(&mut self, value: u8) {
+        match &mut self.rumble {
+            Some(rumble) => {
+                self.ram_bank = value & 0x07;
+
+                let active = value & 0x08 != 0;
+                if active != rumble.active {
+                    rumble.active = active;
+                    if let Some(callback) = &mut rumble.callback {
+                        callback(active);
+                    }
+                }
+            },
+            None => self.ram_bank = value & 0x0F
+        }
+    }
+}
+
+impl Mapper for MBC5 {
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_banked(&self.rom, ROM_BANK_SIZE, 0, addr as usize),
+            0x4000..=0x7FFF => read_banked(&self.rom, ROM_BANK_SIZE, self.rom_bank as usize, (addr - 0x4000) as usize),
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram.is_empty() => {
+                read_banked(&self.ram, RAM_BANK_SIZE, self.ram_bank as usize, (addr - 0xA000) as usize)
+            },
+            _ => 0xFF
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | value as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0x0FF) | ((value as u16 & 0x01) << 8),
+            0x4000..=0x5FFF => self.write_ram_bank(value),
+            0xA000..=0xBFFF if self.ram_enabled => {
+                let bank = self.ram_bank as usize;
+                write_banked(&mut self.ram, RAM_BANK_SIZE, bank, (addr - 0xA000) as usize, value);
+            },
+            _ => {}
+        }
+    }
+
+    fn set_rumble_callback(&mut self, callback: Box<dyn FnMut(bool)>) {
+        if let Some(rumble) = &mut self.rumble {
+            rumble.callback = Some(callback);
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
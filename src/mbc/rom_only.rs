@@ -0,0 +1,42 @@
+use super::Mapper;
+
+/**
+ * Cartridges without a controller: 32 KiB of ROM directly mapped,
+ * optionally with up to 8 KiB of RAM which is always accessible.
+ */
+pub struct RomOnly {
+    rom: Vec<u8>,
+    ram: Vec<u8>
+}
+
+impl RomOnly {
+    pub fn new(rom: Vec<u8>, ram: Vec<u8>) -> RomOnly {
+        RomOnly { rom, ram }
+    }
+}
+
+impl Mapper for RomOnly {
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => self.rom.get(addr as usize).copied().unwrap_or(0xFF),
+            0xA000..=0xBFFF => self.ram.get((addr - 0xA000) as usize).copied().unwrap_or(0xFF),
+            _ => 0xFF
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if let 0xA000..=0xBFFF = addr {
+            if let Some(byte) = self.ram.get_mut((addr - 0xA000) as usize) {
+                *byte = value;
+            }
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
@@ -0,0 +1,229 @@
+use std::{cell::Cell, rc::Rc, time::{SystemTime, UNIX_EPOCH}};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+const DAY_COUNTER_SIZE: u64 = 512;
+
+/**
+ * Source of the current time for the real-time clock, in seconds.
+ * Only differences between readings matter, so the epoch is arbitrary.
+ */
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+/// Wall clock time, in seconds since the Unix epoch
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+/**
+ * A manually driven clock, clones share the same time
+ * so a copy can be kept to advance the clock after handing it to a mapper.
+ */
+#[derive(Clone, Default)]
+pub struct FakeClock {
+    now: Rc<Cell<u64>>
+}
+
+impl FakeClock {
+    pub fn new(now: u64) -> FakeClock {
+        FakeClock { now: Rc::new(Cell::new(now)) }
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.set(now);
+    }
+
+    pub fn advance(&self, seconds: u64) {
+        self.now.set(self.now.get() + seconds);
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> u64 {
+        self.now.get()
+    }
+}
+
+/**
+ * The RTC register values, as visible through the 0xA000-0xBFFF window
+ * when one of the RTC registers 0x08-0x0C is selected.
+ */
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct RtcRegisters {
+    pub seconds: u8,    // 0-59, 6 bits wide
+    pub minutes: u8,    // 0-59, 6 bits wide
+    pub hours: u8,      // 0-23, 5 bits wide
+    pub day_low: u8,    // Lower 8 bits of the day counter
+    pub day_high: u8    // Bit 0: day counter bit 8, bit 6: halt, bit 7: day counter carry
+}
+
+impl RtcRegisters {
+    fn days(&self) -> u64 {
+        (((self.day_high & 0x01) as u64) << 8) | self.day_low as u64
+    }
+
+    fn set_days(&mut self, days: u64) {
+        self.day_low = days as u8;
+        self.day_high = (self.day_high & 0xFE) | ((days >> 8) as u8 & 0x01);
+    }
+
+    fn halted(&self) -> bool {
+        self.day_high & 0x40 != 0
+    }
+
+    fn in_range(&self) -> bool {
+        self.seconds < 60 && self.minutes < 60 && self.hours < 24
+    }
+
+    /**
+     * Counts a single second like the hardware does.
+     * Out of range values written by the game keep counting up to the register width
+     * and wrap to 0 without carrying into the next register.
+     */
+    fn tick(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+
+        self.add_days(1);
+    }
+
+    fn add_days(&mut self, days: u64) {
+        let days = self.days() + days;
+        if days >= DAY_COUNTER_SIZE {
+            self.day_high |= 0x80;
+        }
+        self.set_days(days % DAY_COUNTER_SIZE);
+    }
+
+    fn advance(&mut self, mut seconds: u64) {
+        // Tick one by one until the registers hold valid values, then do the rest in one go
+        while seconds > 0 && !self.in_range() {
+            self.tick();
+            seconds -= 1;
+        }
+        if seconds == 0 {
+            return;
+        }
+
+        let time_of_day = self.hours as u64 * 3600 + self.minutes as u64 * 60 + self.seconds as u64 + seconds;
+        self.seconds = (time_of_day % 60) as u8;
+        self.minutes = (time_of_day / 60 % 60) as u8;
+        self.hours = (time_of_day / 3600 % 24) as u8;
+        self.add_days(time_of_day / SECONDS_PER_DAY);
+    }
+}
+
+/**
+ * Everything needed to persist the clock: both register sets
+ * and the clock reading they were last brought up to date at.
+ */
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RtcState {
+    pub live: RtcRegisters,
+    pub latched: RtcRegisters,
+    pub timestamp: u64
+}
+
+/**
+ * The MBC3 real-time clock.
+ * Instead of ticking every second, the elapsed time is applied lazily
+ * whenever the registers are accessed, based on the readings of the clock source.
+ * Games read the registers through a latched copy, updated by writing 0x00 then 0x01 to 0x6000-0x7FFF.
+ */
+pub struct Rtc {
+    clock: Box<dyn Clock>,
+    last_update: u64,
+    live: RtcRegisters,
+    latched: RtcRegisters
+}
+
+impl Rtc {
+    pub fn new(clock: Box<dyn Clock>) -> Rtc {
+        let last_update = clock.now();
+        Rtc {
+            clock,
+            last_update,
+            live: RtcRegisters::default(),
+            latched: RtcRegisters::default()
+        }
+    }
+
+    fn update(&mut self) {
+        let now = self.clock.now();
+        if !self.live.halted() {
+            self.live.advance(now.saturating_sub(self.last_update));
+        }
+        self.last_update = now;
+    }
+
+    pub fn state(&mut self) -> RtcState {
+        self.update();
+        RtcState {
+            live: self.live,
+            latched: self.latched,
+            timestamp: self.last_update
+        }
+    }
+
+    /**
+     * Restores a saved state, the time elapsed since the state was saved
+     * is applied the next time the registers are accessed.
+     */
+    pub fn set_state(&mut self, state: RtcState) {
+        self.live = state.live;
+        self.latched = state.latched;
+        self.last_update = state.timestamp;
+    }
+
+    pub fn latch(&mut self) {
+        self.update();
+        self.latched = self.live;
+    }
+
+    pub fn read(&self, reg: u8) -> u8 {
+        match reg {
+            0x08 => self.latched.seconds,
+            0x09 => self.latched.minutes,
+            0x0A => self.latched.hours,
+            0x0B => self.latched.day_low,
+            0x0C => self.latched.day_high,
+            _    => 0xFF
+        }
+    }
+
+    pub fn write(&mut self, reg: u8, value: u8) {
+        // Bring the counter up to date first, so that the elapsed time is not applied to the new value
+        self.update();
+        match reg {
+            0x08 => self.live.seconds = value & 0x3F,
+            0x09 => self.live.minutes = value & 0x3F,
+            0x0A => self.live.hours = value & 0x1F,
+            0x0B => self.live.day_low = value,
+            0x0C => self.live.day_high = value & 0xC1,
+            _    => {}
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::cartridge::Cartridge;
+
+/// The console being emulated and the mode its boot ROM left it in
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Model {
+    Dmg,
+    /// A CGB running a game with CGB support, with banked VRAM and WRAM and color palettes
+    Cgb,
+    /**
+     * A CGB running a DMG game. The CGB features are locked and the DMG palette registers
+     * pick colors from the CGB palettes the boot ROM loaded for the game.
+     */
+    CgbCompatibility
+}
+
+impl Model {
+    /// The model a cartridge boots into: games with CGB support on a CGB, the rest on a DMG
+    pub fn for_cartridge(cart: &Cartridge) -> Model {
+        if cart.supports_cgb() { Model::Cgb } else { Model::Dmg }
+    }
+
+    /// Runs a cartridge on a CGB, in compatibility mode if it has no CGB support
+    pub fn cgb_for(cart: &Cartridge) -> Model {
+        if cart.supports_cgb() { Model::Cgb } else { Model::CgbCompatibility }
+    }
+
+    /// Whether the CGB features are available
+    pub fn is_cgb(self) -> bool {
+        self == Model::Cgb
+    }
+}
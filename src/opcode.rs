@@ -1,13 +1,19 @@
-trait TOpcode {
-    fn name(&self) -> &str;
+use num_enum::TryFromPrimitive;
+
+pub(crate) trait TOpcode {
+    fn name(&self) -> &'static str;
     fn size(&self) -> usize;
+    // Duration in machine cycles, when the instruction does not branch
+    fn cycles(&self) -> usize;
+    // Duration in machine cycles, when the instruction branches
+    fn cycles_taken(&self) -> usize;
 }
 
-// TODO: remove dead_code suppression
 // Opcode names in camel case look hideous, hence the warning is suppressed
-#[allow(non_camel_case_types, dead_code)]
-#[derive(Copy, Clone)]
-enum Opcode {
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Copy, Clone, TryFromPrimitive)]
+#[repr(u8)]
+pub(crate) enum Opcode {
 
     // An 'a' after a parameter name means that the value is treated as an address to a memory location (pointer)
     // n = 8-bit immediate; nn = 16-bit immediate
@@ -289,7 +295,7 @@ enum Opcode {
     // F0
     LDH_A_na    = 0xF0,    // Load A from address pointed to by (FF00h + 8-bit immediate)
     POP_AF      = 0xF1,    // Pop 16-bit value from stack into AF
-    XX__F2__    = 0xF2,    // Operation removed in this CPU
+    LDH_A_Ca    = 0xF2,    // Load A from address pointed to by (FF00h + C)
     DI          = 0xF3,    // Disable interrupts
     XX__F4__    = 0xF4,    // Operation removed in this CPU
     PUSH_AF     = 0xF5,    // Push 16-bit AF onto the stack
@@ -305,10 +311,10 @@ enum Opcode {
     RST_38      = 0xFF     // Call routine at address 0038h
 }
 
-    // TODO: remove dead_code suppression
-#[allow(non_camel_case_types, dead_code)]
-#[derive(Copy, Clone)]
-enum OpcodeExt {
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Copy, Clone, TryFromPrimitive)]
+#[repr(u8)]
+pub(crate) enum OpcodeExt {
     // Two-byte instruction codes
 
     // 00
@@ -600,7 +606,7 @@ enum OpcodeExt {
     SET_7_A     = 0xFF     // Set bit 7 of A
 }
 
-const OPCODE_NAMES: &'static [&'static str] = &[
+pub(crate) const OPCODE_NAMES: &[&str] = &[
 //  0x0         0x1         0x2             0x3         0x4             0x5         0x6             0x7         0x8             0x9             0xA             0xB         0xC             0xD         0xE             0xF
     "NOP",      "LD_BC_nn", "LD_BCa_A",     "INC_BC",   "INC_B",        "DEC_B",    "LD_B_n",       "RLC_A",    "LD_nna_SP",    "ADD_HL_BC",    "LD_A_BCa",     "DEC_BC",   "INC_C",        "DEC_C",    "LD_C_n",       "RRC_A",
     "STOP",     "LD_DE_nn", "LD_DEa_A",     "INC_DE",   "INC_D",        "DEC_D",    "LD_D_n",       "RL_A",     "JR_n",         "ADD_HL_DE",    "LD_A_DEa",     "DEC_DE",   "INC_E",        "DEC_E",    "LD_E_n",       "RR_A",
@@ -617,7 +623,7 @@ const OPCODE_NAMES: &'static [&'static str] = &[
     "RET_NZ",   "POP_BC",   "JP_NZ_nn",     "JP_nn",    "CALL_NZ_nn",   "PUSH_BC",  "ADD_A_n",      "RST_0",    "RET_Z",        "RET",          "JP_Z_nn",      "EXT_OPS",  "CALL_Z_nn",    "CALL_nn",  "ADC_A_n",      "RST_8",
     "RET_NC",   "POP_DE",   "JP_NC_nn",     "XX__D3__", "CALL_NC_nn",   "PUSH_DE",  "SUB_A_n",      "RST_10",   "RET_C",        "RETI",         "JP_C_nn",      "XX__DB__", "CALL_C_nn",    "XX__DD__", "SBC_A_n",      "RST_18",
     "LDH_na_A", "POP_HL",   "LDH_Ca_A",     "XX__E3__", "XX__E4__",     "PUSH_HL",  "AND_n",        "RST_20",   "ADD_SP_d",     "JP_HLa",       "LD_nna_A",     "XX__EB__", "XX__EC__",     "XX__ED__", "XOR_n",        "RST_28",
-    "LDH_A_na", "POP_AF",   "XX__F2__",     "DI",       "XX__F4__",     "PUSH_AF",  "OR_n",         "RST_30",   "LDHL_SP_d",    "LD_SP_HL",     "LD_A_nna",     "EI",       "XX__FC__",     "XX__FD__", "CP_n",         "RST_38",
+    "LDH_A_na", "POP_AF",   "LDH_A_Ca",     "DI",       "XX__F4__",     "PUSH_AF",  "OR_n",         "RST_30",   "LDHL_SP_d",    "LD_SP_HL",     "LD_A_nna",     "EI",       "XX__FC__",     "XX__FD__", "CP_n",         "RST_38",
     "RLC_B",    "RLC_C",    "RLC_D",        "RLC_E",    "RLC_H",        "RLC_L",    "RLC_HLa",      "rRLC_A",   "RRC_B",        "RRC_C",        "RRC_D",        "RRC_E",    "RRC_H",        "RRC_L",    "RRC_HLa",      "rRRC_A",
     "RL_B",     "RL_C",     "RL_D",         "RL_E",     "RL_H",         "RL_L",     "RL_HLa",       "rRL_A",    "RR_B",         "RR_C",         "RR_D",         "RR_E",     "RR_H",         "RR_L",     "RR_HLa",       "rRR_A",
     "SLA_B",    "SLA_C",    "SLA_D",        "SLA_E",    "SLA_H",        "SLA_L",    "SLA_HLa",      "SLA_A",    "SRA_B",        "SRA_C",        "SRA_D",        "SRA_E",    "SRA_H",        "SRA_L",    "SRA_HLa",      "SRA_A",
@@ -642,7 +648,7 @@ const OPCODE_NAMES: &'static [&'static str] = &[
     I am still not sure whether this is a good idea or not.
     This note is written so that I don't forget about this detail.
 */
-const OPCODE_SIZES: [usize; 512] = [
+pub(crate) const OPCODE_SIZES: [usize; 512] = [
 //  0  1  2  3  4  5  6  7  8  9  A  B  C  D  E  F
     1, 3, 1, 1, 1, 1, 2, 1, 3, 1, 1, 1, 1, 1, 2, 1, 
     2, 3, 1, 1, 1, 1, 2, 1, 2, 1, 1, 1, 1, 1, 2, 1, 
@@ -678,8 +684,84 @@ const OPCODE_SIZES: [usize; 512] = [
     2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2
 ];
 
+/*
+    Duration of each instruction in machine cycles (4 clock cycles each), using the same layout as OPCODE_SIZES.
+    Conditional jumps, calls and returns list the duration when the condition is false,
+    OPCODE_CYCLES_TAKEN has the duration when it is true.
+    Extended opcodes include the cycle spent fetching the 0xCB prefix, which is set to 0 like its size.
+    Removed opcodes hang the CPU and are also set to 0.
+*/
+const OPCODE_CYCLES: [usize; 512] = [
+//  0  1  2  3  4  5  6  7  8  9  A  B  C  D  E  F
+    1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1, 
+    1, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, 
+    2, 3, 2, 2, 1, 1, 2, 1, 2, 2, 2, 2, 1, 1, 2, 1, 
+    2, 3, 2, 2, 3, 3, 3, 1, 2, 2, 2, 2, 1, 1, 2, 1, 
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, 
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, 
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, 
+    2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1, 
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, 
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, 
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, 
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, 
+    2, 3, 3, 4, 3, 4, 2, 4, 2, 4, 3, 0, 3, 6, 2, 4, 
+    2, 3, 3, 0, 3, 4, 2, 4, 2, 4, 3, 0, 3, 0, 2, 4, 
+    3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4, 
+    3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4, 
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, 
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, 
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, 
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, 
+    2, 2, 2, 2, 2, 2, 3, 2, 2, 2, 2, 2, 2, 2, 3, 2, 
+    2, 2, 2, 2, 2, 2, 3, 2, 2, 2, 2, 2, 2, 2, 3, 2, 
+    2, 2, 2, 2, 2, 2, 3, 2, 2, 2, 2, 2, 2, 2, 3, 2, 
+    2, 2, 2, 2, 2, 2, 3, 2, 2, 2, 2, 2, 2, 2, 3, 2, 
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, 
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, 
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, 
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, 
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, 
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, 
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, 
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2
+];
+
+const OPCODE_CYCLES_TAKEN: [usize; 256] = [
+//  0  1  2  3  4  5  6  7  8  9  A  B  C  D  E  F
+    1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1, 
+    1, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, 
+    3, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, 
+    3, 3, 2, 2, 3, 3, 3, 1, 3, 2, 2, 2, 1, 1, 2, 1, 
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, 
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, 
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, 
+    2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1, 
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, 
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, 
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, 
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, 
+    5, 3, 4, 4, 6, 4, 2, 4, 5, 4, 4, 0, 6, 6, 2, 4, 
+    5, 3, 4, 0, 6, 4, 2, 4, 5, 4, 4, 0, 6, 0, 2, 4, 
+    3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4, 
+    3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4
+];
+
+impl Opcode {
+    pub fn from_byte(byte: u8) -> Opcode {
+        // Every byte value has a variant, so the conversion cannot fail
+        Opcode::try_from(byte).unwrap()
+    }
+}
+
+impl OpcodeExt {
+    pub fn from_byte(byte: u8) -> OpcodeExt {
+        OpcodeExt::try_from(byte).unwrap()
+    }
+}
+
 impl TOpcode for Opcode {
-    fn name(&self) -> &str {
+    fn name(&self) -> &'static str {
         let idx = *self as usize;
         OPCODE_NAMES[idx]
     }
@@ -688,10 +770,20 @@ impl TOpcode for Opcode {
         let idx = *self as usize;
         OPCODE_SIZES[idx]
     }
+
+    fn cycles(&self) -> usize {
+        let idx = *self as usize;
+        OPCODE_CYCLES[idx]
+    }
+
+    fn cycles_taken(&self) -> usize {
+        let idx = *self as usize;
+        OPCODE_CYCLES_TAKEN[idx]
+    }
 }
 
 impl TOpcode for OpcodeExt {
-    fn name(&self) -> &str {
+    fn name(&self) -> &'static str {
         let idx = *self as usize;
         OPCODE_NAMES[0xFF + 1 + idx]
     }
@@ -700,4 +792,14 @@ impl TOpcode for OpcodeExt {
         let idx = *self as usize;
         OPCODE_SIZES[0xFF + 1 + idx]
     }
+
+    fn cycles(&self) -> usize {
+        let idx = *self as usize;
+        OPCODE_CYCLES[0xFF + 1 + idx]
+    }
+
+    // Extended opcodes never branch
+    fn cycles_taken(&self) -> usize {
+        self.cycles()
+    }
 }
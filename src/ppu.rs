@@ -0,0 +1,639 @@
+mod fifo;
+mod scanline;
+
+use crate::interrupt::{Interrupt, Interrupts};
+use crate::model::Model;
+
+use self::fifo::Fifo;
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+pub const VRAM_SIZE: usize = 0x2000;
+pub const OAM_SIZE: usize = 0xA0;
+
+pub const LCDC_ADDR: u16 = 0xFF40;
+pub const STAT_ADDR: u16 = 0xFF41;
+pub const SCY_ADDR: u16 = 0xFF42;
+pub const SCX_ADDR: u16 = 0xFF43;
+pub const LY_ADDR: u16 = 0xFF44;
+pub const LYC_ADDR: u16 = 0xFF45;
+pub const BGP_ADDR: u16 = 0xFF47;
+pub const OBP0_ADDR: u16 = 0xFF48;
+pub const OBP1_ADDR: u16 = 0xFF49;
+pub const WY_ADDR: u16 = 0xFF4A;
+pub const WX_ADDR: u16 = 0xFF4B;
+pub const VBK_ADDR: u16 = 0xFF4F;
+pub const BCPS_ADDR: u16 = 0xFF68;
+pub const BCPD_ADDR: u16 = 0xFF69;
+pub const OCPS_ADDR: u16 = 0xFF6A;
+pub const OCPD_ADDR: u16 = 0xFF6B;
+pub const OPRI_ADDR: u16 = 0xFF6C;
+
+const LCDC_ENABLE: u8 = 0x80;
+const LCDC_WINDOW_MAP: u8 = 0x40;
+const LCDC_WINDOW_ENABLE: u8 = 0x20;
+const LCDC_TILE_DATA: u8 = 0x10;
+const LCDC_BG_MAP: u8 = 0x08;
+const LCDC_OBJ_SIZE: u8 = 0x04;
+const LCDC_OBJ_ENABLE: u8 = 0x02;
+const LCDC_BG_ENABLE: u8 = 0x01;
+
+const STAT_LYC_INTERRUPT: u8 = 0x40;
+const STAT_OAM_INTERRUPT: u8 = 0x20;
+const STAT_VBLANK_INTERRUPT: u8 = 0x10;
+const STAT_HBLANK_INTERRUPT: u8 = 0x08;
+const STAT_LYC_EQUAL: u8 = 0x04;
+
+const DOTS_PER_LINE: u32 = 456;
+const OAM_SCAN_DOTS: u32 = 80;
+const TRANSFER_DOTS: u32 = 172;
+const VBLANK_LINE: u8 = 144;
+const LINES_PER_FRAME: u8 = 154;
+
+// The four DMG shades as 0xRRGGBB, from the lightest
+pub const DMG_COLORS: [u32; 4] = [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000];
+
+// 8 palettes of 4 colors, each color 2 bytes of little-endian RGB555
+const PALETTE_RAM_SIZE: usize = 64;
+// BCPS and OCPS increment the index after every write to BCPD and OCPD when bit 7 is set
+const PALETTE_AUTO_INCREMENT: u8 = 0x80;
+
+/*
+    The palettes the CGB boot ROM loads in compatibility mode for games it does not recognize:
+    the background uses palette 0, sprites OBJ palette 0 or 1, indexed by the shades of the DMG palette registers.
+*/
+const COMPATIBILITY_BG_PALETTE: [u16; 4] = [0x7FFF, 0x1BEF, 0x6180, 0x0000];
+const COMPATIBILITY_OBJ_PALETTE: [u16; 4] = [0x7FFF, 0x421F, 0x1CF2, 0x0000];
+
+const MAX_SPRITES_PER_LINE: usize = 10;
+
+const OBJ_BG_PRIORITY: u8 = 0x80;
+const OBJ_Y_FLIP: u8 = 0x40;
+const OBJ_X_FLIP: u8 = 0x20;
+const OBJ_PALETTE: u8 = 0x10;
+const OBJ_BANK: u8 = 0x08;
+const OBJ_CGB_PALETTE: u8 = 0x07;
+
+// The attributes of background and window tiles, in VRAM bank 1 at the same position as the tile index
+const ATTR_PRIORITY: u8 = 0x80;
+const ATTR_Y_FLIP: u8 = 0x40;
+const ATTR_X_FLIP: u8 = 0x20;
+const ATTR_BANK: u8 = 0x08;
+const ATTR_PALETTE: u8 = 0x07;
+
+/// The color index (0-3) of pixel `x` in a tile row, 0 being the leftmost
+fn pixel((low, high): (u8, u8), x: u8) -> u8 {
+    let bit = 7 - x;
+    (((high >> bit) & 1) << 1) | ((low >> bit) & 1)
+}
+
+/// The shade (0-3) a DMG palette register maps a color index to
+fn shade(palette: u8, color: u8) -> u8 {
+    (palette >> (color * 2)) & 0x03
+}
+
+/// Converts a color of CGB palette RAM to 0xRRGGBB
+fn cgb_color(palettes: &[u8; PALETTE_RAM_SIZE], palette: u8, color: u8) -> u32 {
+    let index = palette as usize * 8 + color as usize * 2;
+    let rgb = u16::from_le_bytes([palettes[index], palettes[index + 1]]);
+
+    // Scales the 5-bit channels to 8 bits, so that 0x1F becomes 0xFF
+    let channel = |shift: u16| {
+        let value = ((rgb >> shift) & 0x1F) as u32;
+        (value << 3) | (value >> 2)
+    };
+    (channel(0) << 16) | (channel(5) << 8) | channel(10)
+}
+
+/// Writes a palette of 4 RGB555 colors into palette RAM
+fn load_palette(palettes: &mut [u8; PALETTE_RAM_SIZE], palette: usize, colors: [u16; 4]) {
+    for (color, rgb) in colors.iter().enumerate() {
+        let index = palette * 8 + color * 2;
+        palettes[index..index + 2].copy_from_slice(&rgb.to_le_bytes());
+    }
+}
+
+/// A background or window pixel and the attributes of its tile, which are always 0 outside of CGB mode
+#[derive(Copy, Clone, Default, Debug)]
+struct BgPixel {
+    color: u8,
+    attributes: u8
+}
+
+/// A sprite pixel, transparent if the color is 0
+#[derive(Copy, Clone, Default, Debug)]
+struct SpritePixel {
+    color: u8,
+    attributes: u8,
+    // Position in OAM, sprites earlier in OAM win in CGB mode
+    index: u8
+}
+
+/// An OAM entry, the position is the one on screen plus 16 for Y and 8 for X
+#[derive(Copy, Clone, Debug)]
+struct Sprite {
+    index: u8,
+    y: u8,
+    x: u8,
+    tile: u8,
+    attributes: u8
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Mode {
+    HBlank      = 0,
+    VBlank      = 1,
+    OamScan     = 2,
+    Transfer    = 3
+}
+
+/// How the PPU turns VRAM into pixels during the transfer
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Renderer {
+    /// Renders a whole line at once at the start of the transfer, which always takes 172 dots
+    #[default]
+    Scanline,
+    /// Emulates the pixel fetcher and FIFOs dot by dot, registers written during the transfer take effect mid-line
+    Fifo
+}
+
+/**
+ * The picture processing unit.
+ * Every line takes 456 dots (4 per machine cycle): 80 scanning OAM for the sprites on the line,
+ * 172 transferring pixels to the LCD and the rest in HBlank. Lines 144 to 153 are the VBlank period.
+ * With the scanline renderer the whole line is drawn at the start of the transfer, with the FIFO renderer
+ * the transfer is stretched by scrolling, the window and sprites like on hardware, shortening HBlank.
+ *
+ * In CGB mode VRAM has a second bank, holding more tiles and the attributes of the tile maps,
+ * and colors come from 8 background and 8 sprite palettes of palette RAM.
+ */
+pub struct Ppu {
+    model: Model,
+    // Two banks, the second is only accessible in CGB mode
+    vram: Vec<u8>,
+    vram_bank: u8,
+    oam: [u8; OAM_SIZE],
+
+    lcdc: u8,
+    // Only the interrupt selection bits, the rest of STAT is computed when read
+    stat: u8,
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+
+    bg_palettes: [u8; PALETTE_RAM_SIZE],
+    obj_palettes: [u8; PALETTE_RAM_SIZE],
+    bcps: u8,
+    ocps: u8,
+    // Bit 0 set gives priority to the sprite with the lowest X like on DMG, cleared to the first in OAM
+    opri: u8,
+
+    mode: Mode,
+    dots: u32,
+    // The STAT interrupt is requested only when this goes from low to high
+    stat_line: bool,
+    // The window is drawn only after LY matched WY during the frame, and has a line counter of its own
+    window_triggered: bool,
+    window_line: u8,
+
+    renderer: Renderer,
+    // State of the FIFO renderer during the transfer, None while rendering with the scanline one
+    fifo: Option<Fifo>,
+
+    framebuffer: Vec<u32>,
+    frame_ready: bool,
+    hblank_started: bool
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Ppu::new()
+    }
+}
+
+impl Ppu {
+    /// Creates a PPU with the registers the DMG boot ROM leaves behind
+    pub fn new() -> Ppu {
+        Ppu::with_model(Model::Dmg)
+    }
+
+    /// Creates a PPU with the registers and palettes the boot ROM of the model leaves behind
+    pub fn with_model(model: Model) -> Ppu {
+        let mut ppu = Ppu {
+            model,
+            vram: vec![0; VRAM_SIZE * 2],
+            vram_bank: 0,
+            oam: [0; OAM_SIZE],
+            lcdc: 0x91,
+            stat: 0x00,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0xFC,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            // The CGB boot ROM sets all background colors to white
+            bg_palettes: [0xFF; PALETTE_RAM_SIZE],
+            obj_palettes: [0; PALETTE_RAM_SIZE],
+            bcps: 0,
+            ocps: 0,
+            opri: if model.is_cgb() { 0 } else { 1 },
+            mode: Mode::OamScan,
+            dots: 0,
+            stat_line: false,
+            window_triggered: false,
+            window_line: 0,
+            renderer: Renderer::default(),
+            fifo: None,
+            framebuffer: vec![DMG_COLORS[0]; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame_ready: false,
+            hblank_started: false
+        };
+
+        if model == Model::CgbCompatibility {
+            load_palette(&mut ppu.bg_palettes, 0, COMPATIBILITY_BG_PALETTE);
+            load_palette(&mut ppu.obj_palettes, 0, COMPATIBILITY_OBJ_PALETTE);
+            load_palette(&mut ppu.obj_palettes, 1, COMPATIBILITY_OBJ_PALETTE);
+        }
+
+        ppu.start_line();
+        ppu
+    }
+
+    /// Whether the LCD is on
+    pub fn enabled(&self) -> bool {
+        self.lcdc & LCDC_ENABLE != 0
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn renderer(&self) -> Renderer {
+        self.renderer
+    }
+
+    /// Switches the renderer, the line being transferred is finished with the current one
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.renderer = renderer;
+    }
+
+    /// The last frame, 160x144 pixels as 0xRRGGBB row by row
+    pub fn framebuffer(&self) -> &[u32] {
+        &self.framebuffer
+    }
+
+    /// Whether a frame was completed since the last call
+    pub fn take_frame(&mut self) -> bool {
+        std::mem::take(&mut self.frame_ready)
+    }
+
+    /// Whether HBlank was entered since the last call, on one of the visible lines
+    pub fn take_hblank(&mut self) -> bool {
+        std::mem::take(&mut self.hblank_started)
+    }
+
+    /// Advances the PPU by a number of dots, 4 per machine cycle or 2 in CGB double speed
+    pub fn tick(&mut self, interrupts: &mut Interrupts, dots: u32) {
+        if !self.enabled() {
+            return;
+        }
+
+        for _ in 0..dots {
+            self.dot(interrupts);
+        }
+    }
+
+    fn dot(&mut self, interrupts: &mut Interrupts) {
+        self.dots += 1;
+
+        match self.mode {
+            Mode::OamScan if self.dots == OAM_SCAN_DOTS => {
+                self.mode = Mode::Transfer;
+                match self.renderer {
+                    Renderer::Scanline  => self.render_scanline(),
+                    Renderer::Fifo      => self.fifo = Some(Fifo::new(self))
+                }
+            },
+            Mode::Transfer => {
+                let done = match self.fifo.take() {
+                    Some(mut fifo) => {
+                        let done = self.fifo_dot(&mut fifo);
+                        if !done {
+                            self.fifo = Some(fifo);
+                        }
+                        done
+                    },
+                    None => self.dots == OAM_SCAN_DOTS + TRANSFER_DOTS
+                };
+                if done {
+                    self.mode = Mode::HBlank;
+                    self.hblank_started = true;
+                }
+            },
+            Mode::HBlank | Mode::VBlank if self.dots == DOTS_PER_LINE => {
+                self.dots = 0;
+                self.next_line(interrupts);
+            },
+            _ => {}
+        }
+
+        self.update_stat(interrupts);
+    }
+
+    fn next_line(&mut self, interrupts: &mut Interrupts) {
+        self.ly += 1;
+
+        if self.ly == VBLANK_LINE {
+            self.mode = Mode::VBlank;
+            self.frame_ready = true;
+            interrupts.request(Interrupt::VBlank);
+        } else if self.ly == LINES_PER_FRAME {
+            self.ly = 0;
+            self.window_triggered = false;
+            self.window_line = 0;
+            self.start_line();
+        } else if self.ly < VBLANK_LINE {
+            self.start_line();
+        }
+    }
+
+    fn start_line(&mut self) {
+        self.mode = Mode::OamScan;
+        if self.ly == self.wy {
+            self.window_triggered = true;
+        }
+    }
+
+    fn update_stat(&mut self, interrupts: &mut Interrupts) {
+        let line = (self.ly == self.lyc && self.stat & STAT_LYC_INTERRUPT != 0) || match self.mode {
+            Mode::HBlank    => self.stat & STAT_HBLANK_INTERRUPT != 0,
+            Mode::VBlank    => self.stat & STAT_VBLANK_INTERRUPT != 0,
+            Mode::OamScan   => self.stat & STAT_OAM_INTERRUPT != 0,
+            Mode::Transfer  => false
+        };
+
+        if line && !self.stat_line {
+            interrupts.request(Interrupt::Stat);
+        }
+        self.stat_line = line;
+    }
+
+    fn set_lcdc(&mut self, value: u8) {
+        let was_enabled = self.enabled();
+        self.lcdc = value;
+
+        if was_enabled && !self.enabled() {
+            // LY stays at 0 and the PPU idles in mode 0 while the LCD is off
+            self.ly = 0;
+            self.dots = 0;
+            self.mode = Mode::HBlank;
+            self.window_triggered = false;
+            self.window_line = 0;
+            self.fifo = None;
+        } else if !was_enabled && self.enabled() {
+            self.start_line();
+        }
+    }
+
+    fn sprite_height(&self) -> u8 {
+        if self.lcdc & LCDC_OBJ_SIZE != 0 { 16 } else { 8 }
+    }
+
+    /// The first 10 sprites in OAM order which overlap the current line
+    fn scan_oam(&self) -> Vec<Sprite> {
+        let height = self.sprite_height();
+
+        self.oam.chunks(4)
+            .enumerate()
+            .map(|(index, entry)| Sprite { index: index as u8, y: entry[0], x: entry[1], tile: entry[2], attributes: entry[3] })
+            .filter(|sprite| {
+                let top = sprite.y as i16 - 16;
+                (top..top + height as i16).contains(&(self.ly as i16))
+            })
+            .take(MAX_SPRITES_PER_LINE)
+            .collect()
+    }
+
+    /// The two bytes of a tile row, the low and high bits of the 8 pixels starting from the leftmost
+    fn tile_row(&self, addr: usize, row: u8) -> (u8, u8) {
+        let addr = addr + row as usize * 2;
+        (self.vram[addr], self.vram[addr + 1])
+    }
+
+    /// The VRAM offset of a background or window tile, LCDC selects unsigned or signed indices
+    fn bg_tile_addr(&self, index: u8) -> usize {
+        if self.lcdc & LCDC_TILE_DATA != 0 {
+            index as usize * 16
+        } else {
+            (0x1000 + index as i8 as isize * 16) as usize
+        }
+    }
+
+    /// The tile index and attributes at a position of the 32x32 tile map selected by `map_bit` in LCDC
+    fn map_tile(&self, map_bit: u8, column: u8, row: u8) -> (u8, u8) {
+        let base = if self.lcdc & map_bit != 0 { 0x1C00 } else { 0x1800 };
+        let addr = base + (row as usize % 32) * 32 + column as usize % 32;
+        let attributes = if self.model.is_cgb() { self.vram[VRAM_SIZE + addr] } else { 0 };
+        (self.vram[addr], attributes)
+    }
+
+    /// The row of a background or window tile at pixel row `y`, with the flips of its attributes applied
+    fn bg_tile_row(&self, tile: u8, attributes: u8, y: u8) -> (u8, u8) {
+        let row = if attributes & ATTR_Y_FLIP != 0 { 7 - y % 8 } else { y % 8 };
+        let bank = if attributes & ATTR_BANK != 0 { VRAM_SIZE } else { 0 };
+        let (low, high) = self.tile_row(bank + self.bg_tile_addr(tile), row);
+
+        if attributes & ATTR_X_FLIP != 0 {
+            (low.reverse_bits(), high.reverse_bits())
+        } else {
+            (low, high)
+        }
+    }
+
+    /// The row of a sprite at the current line, with the flips applied
+    fn sprite_row(&self, sprite: &Sprite) -> (u8, u8) {
+        let height = self.sprite_height();
+        let mut row = (self.ly as i16 - (sprite.y as i16 - 16)) as u8;
+        if sprite.attributes & OBJ_Y_FLIP != 0 {
+            row = height - 1 - row;
+        }
+
+        // Tall sprites ignore the lowest bit of the tile index, the second tile follows the first
+        let tile = if height == 16 { sprite.tile & 0xFE } else { sprite.tile };
+        let bank = if self.model.is_cgb() && sprite.attributes & OBJ_BANK != 0 { VRAM_SIZE } else { 0 };
+        let (low, high) = self.tile_row(bank + tile as usize * 16, row);
+
+        if sprite.attributes & OBJ_X_FLIP != 0 {
+            (low.reverse_bits(), high.reverse_bits())
+        } else {
+            (low, high)
+        }
+    }
+
+    /// Whether overlapping sprites are prioritized by OAM order rather than by X
+    fn oam_priority(&self) -> bool {
+        self.opri & 0x01 == 0
+    }
+
+    /// The color shown for a pixel, from the background and the sprite pixel on top of it
+    fn compose(&self, bg: BgPixel, sprite: SpritePixel) -> u32 {
+        let cgb = self.model.is_cgb();
+        let bg_enabled = self.lcdc & LCDC_BG_ENABLE != 0;
+
+        // LCDC bit 0 blanks background and window, in CGB mode it takes away their priority over sprites instead
+        let color = if bg_enabled || cgb { bg.color } else { 0 };
+        let bg_priority = if cgb {
+            bg_enabled && (bg.attributes & ATTR_PRIORITY != 0 || sprite.attributes & OBJ_BG_PRIORITY != 0)
+        } else {
+            sprite.attributes & OBJ_BG_PRIORITY != 0
+        };
+
+        if self.lcdc & LCDC_OBJ_ENABLE != 0 && sprite.color != 0 && !(bg_priority && color != 0) {
+            self.obj_color(sprite)
+        } else {
+            self.bg_color(color, bg.attributes)
+        }
+    }
+
+    fn bg_color(&self, color: u8, attributes: u8) -> u32 {
+        match self.model {
+            Model::Dmg              => DMG_COLORS[shade(self.bgp, color) as usize],
+            Model::Cgb              => cgb_color(&self.bg_palettes, attributes & ATTR_PALETTE, color),
+            Model::CgbCompatibility => cgb_color(&self.bg_palettes, 0, shade(self.bgp, color))
+        }
+    }
+
+    fn obj_color(&self, sprite: SpritePixel) -> u32 {
+        let (number, palette) = if sprite.attributes & OBJ_PALETTE != 0 { (1, self.obp1) } else { (0, self.obp0) };
+        match self.model {
+            Model::Dmg              => DMG_COLORS[shade(palette, sprite.color) as usize],
+            Model::Cgb              => cgb_color(&self.obj_palettes, sprite.attributes & OBJ_CGB_PALETTE, sprite.color),
+            Model::CgbCompatibility => cgb_color(&self.obj_palettes, number, shade(palette, sprite.color))
+        }
+    }
+
+    // VRAM is inaccessible to the CPU during the transfer, OAM during the scan and the transfer
+
+    fn vram_blocked(&self) -> bool {
+        self.enabled() && self.mode == Mode::Transfer
+    }
+
+    pub fn oam_blocked(&self) -> bool {
+        self.enabled() && matches!(self.mode, Mode::OamScan | Mode::Transfer)
+    }
+
+    pub fn read_vram(&self, addr: u16) -> u8 {
+        if self.vram_blocked() {
+            return 0xFF;
+        }
+        self.vram[self.vram_bank as usize * VRAM_SIZE + (addr - 0x8000) as usize]
+    }
+
+    pub fn write_vram(&mut self, addr: u16, value: u8) {
+        if !self.vram_blocked() {
+            self.vram[self.vram_bank as usize * VRAM_SIZE + (addr - 0x8000) as usize] = value;
+        }
+    }
+
+    // Palette RAM is inaccessible to the CPU during the transfer, like VRAM
+
+    fn read_palette(&self, palettes: &[u8; PALETTE_RAM_SIZE], spec: u8) -> u8 {
+        if self.vram_blocked() {
+            return 0xFF;
+        }
+        palettes[(spec & 0x3F) as usize]
+    }
+
+    /// Writes palette RAM at the index in `spec`, which is incremented even if the write is blocked
+    fn write_palette(blocked: bool, palettes: &mut [u8; PALETTE_RAM_SIZE], spec: &mut u8, value: u8) {
+        if !blocked {
+            palettes[(*spec & 0x3F) as usize] = value;
+        }
+        if *spec & PALETTE_AUTO_INCREMENT != 0 {
+            *spec = PALETTE_AUTO_INCREMENT | (spec.wrapping_add(1) & 0x3F);
+        }
+    }
+
+    pub fn read_oam(&self, addr: u16) -> u8 {
+        if self.oam_blocked() {
+            return 0xFF;
+        }
+        self.oam[(addr - 0xFE00) as usize]
+    }
+
+    pub fn write_oam(&mut self, addr: u16, value: u8) {
+        if !self.oam_blocked() {
+            self.oam[(addr - 0xFE00) as usize] = value;
+        }
+    }
+
+    /// Writes OAM from OAM DMA, which has access to it whatever the mode
+    pub fn write_oam_dma(&mut self, index: u8, value: u8) {
+        self.oam[index as usize] = value;
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            LCDC_ADDR   => self.lcdc,
+            STAT_ADDR   => {
+                let mode = if self.enabled() { self.mode as u8 } else { 0 };
+                let equal = if self.ly == self.lyc { STAT_LYC_EQUAL } else { 0 };
+                0x80 | self.stat | equal | mode
+            },
+            SCY_ADDR    => self.scy,
+            SCX_ADDR    => self.scx,
+            LY_ADDR     => self.ly,
+            LYC_ADDR    => self.lyc,
+            BGP_ADDR    => self.bgp,
+            OBP0_ADDR   => self.obp0,
+            OBP1_ADDR   => self.obp1,
+            WY_ADDR     => self.wy,
+            WX_ADDR     => self.wx,
+            // The CGB registers do not exist outside of CGB mode
+            _ if !self.model.is_cgb() => 0xFF,
+            VBK_ADDR    => 0xFE | self.vram_bank,
+            BCPS_ADDR   => 0x40 | self.bcps,
+            BCPD_ADDR   => self.read_palette(&self.bg_palettes, self.bcps),
+            OCPS_ADDR   => 0x40 | self.ocps,
+            OCPD_ADDR   => self.read_palette(&self.obj_palettes, self.ocps),
+            OPRI_ADDR   => 0xFE | self.opri,
+            _ => 0xFF
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            LCDC_ADDR   => self.set_lcdc(value),
+            STAT_ADDR   => self.stat = value & 0x78,
+            SCY_ADDR    => self.scy = value,
+            SCX_ADDR    => self.scx = value,
+            // LY is read only
+            LY_ADDR     => {},
+            LYC_ADDR    => self.lyc = value,
+            BGP_ADDR    => self.bgp = value,
+            OBP0_ADDR   => self.obp0 = value,
+            OBP1_ADDR   => self.obp1 = value,
+            WY_ADDR     => self.wy = value,
+            WX_ADDR     => self.wx = value,
+            _ if !self.model.is_cgb() => {},
+            VBK_ADDR    => self.vram_bank = value & 0x01,
+            BCPS_ADDR   => self.bcps = value & 0xBF,
+            BCPD_ADDR   => Ppu::write_palette(self.vram_blocked(), &mut self.bg_palettes, &mut self.bcps, value),
+            OCPS_ADDR   => self.ocps = value & 0xBF,
+            OCPD_ADDR   => Ppu::write_palette(self.vram_blocked(), &mut self.obj_palettes, &mut self.ocps, value),
+            OPRI_ADDR   => self.opri = value & 0x01,
+            _ => {}
+        }
+    }
+}
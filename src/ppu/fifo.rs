@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+
+use super::*;
+
+// The first tile fetched on every line is thrown away, delaying the fetcher
+const DISCARDED_FETCH_DOTS: u8 = 6;
+// Fetching the tile and the two data bytes of a sprite while the background fetcher is stalled
+const SPRITE_FETCH_DOTS: u8 = 6;
+
+/// The steps of the fetcher, all but the last take 2 dots
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Step {
+    Tile,
+    DataLow,
+    DataHigh,
+    // Repeated every dot until the background FIFO is empty
+    Push
+}
+
+/**
+ * The pixel fetcher and the background and sprite FIFOs of the line being transferred.
+ * A pixel is shifted out to the LCD every dot the background FIFO is not empty, mixing it with the sprite FIFO
+ * using the palettes and LCDC as they are at that dot. The fetcher refills the background FIFO 8 pixels at a time,
+ * stalling the LCD when it cannot keep up: discarding SCX % 8 pixels, restarting for the window
+ * and fetching sprites all make the transfer longer than 172 dots.
+ */
+pub(super) struct Fifo {
+    background: VecDeque<BgPixel>,
+    sprites: VecDeque<SpritePixel>,
+
+    step: Step,
+    step_dots: u8,
+    // Tile column of the next fetch, relative to SCX or to the left edge of the window
+    column: u8,
+    tile: u8,
+    attributes: u8,
+    row: (u8, u8),
+    window: bool,
+
+    // Dots to wait before the first fetch
+    delay: u8,
+    // Pixels still to be dropped for the fine horizontal scroll
+    discard: u8,
+    // The next pixel of the line to be shifted out
+    x: u8,
+
+    // Sprites found by the OAM scan and not fetched yet, by X then OAM order
+    pending: VecDeque<Sprite>,
+    // The sprite being fetched and the dots left
+    fetching: Option<(Sprite, u8)>
+}
+
+impl Fifo {
+    pub(super) fn new(ppu: &Ppu) -> Fifo {
+        let mut pending = ppu.scan_oam();
+        pending.sort_by_key(|sprite| sprite.x);
+
+        Fifo {
+            background: VecDeque::with_capacity(16),
+            sprites: VecDeque::with_capacity(8),
+            step: Step::Tile,
+            step_dots: 0,
+            column: 0,
+            tile: 0,
+            attributes: 0,
+            row: (0, 0),
+            window: false,
+            delay: DISCARDED_FETCH_DOTS,
+            discard: ppu.scx % 8,
+            x: 0,
+            pending: pending.into(),
+            fetching: None
+        }
+    }
+
+    fn next_step(&mut self, step: Step) {
+        self.step = step;
+        self.step_dots = 0;
+    }
+}
+
+impl Ppu {
+    /// Advances the transfer by a dot, returns true when the last pixel of the line was shifted out
+    pub(super) fn fifo_dot(&mut self, fifo: &mut Fifo) -> bool {
+        if fifo.delay > 0 {
+            fifo.delay -= 1;
+            return false;
+        }
+
+        // Sprites are fetched when the LCD reaches their left edge, those passed while disabled are never drawn
+        let obj_enabled = self.lcdc & LCDC_OBJ_ENABLE != 0;
+        while fifo.fetching.is_none() && fifo.pending.front().is_some_and(|sprite| sprite.x <= fifo.x + 8) {
+            let sprite = fifo.pending.pop_front();
+            if obj_enabled {
+                fifo.fetching = sprite.map(|sprite| (sprite, SPRITE_FETCH_DOTS));
+            }
+        }
+
+        if let Some((sprite, dots)) = fifo.fetching {
+            // The background fetcher is let finish the tile it is fetching first
+            if fifo.step != Step::Push {
+                self.fetch(fifo);
+            } else if dots > 1 {
+                fifo.fetching = Some((sprite, dots - 1));
+            } else {
+                fifo.fetching = None;
+                self.merge_sprite(fifo, &sprite);
+            }
+            return false;
+        }
+
+        // Reaching the window throws away the background pixels and restarts the fetcher on the window map
+        let window_enabled = self.lcdc & LCDC_WINDOW_ENABLE != 0 && self.window_triggered;
+        if !fifo.window && window_enabled && fifo.x as u16 + 7 >= self.wx as u16 {
+            fifo.window = true;
+            fifo.column = 0;
+            fifo.background.clear();
+            fifo.next_step(Step::Tile);
+        }
+
+        self.fetch(fifo);
+
+        let bg = match fifo.background.pop_front() {
+            Some(bg) => bg,
+            None => return false
+        };
+        if fifo.discard > 0 {
+            fifo.discard -= 1;
+            return false;
+        }
+        let sprite = fifo.sprites.pop_front().unwrap_or_default();
+
+        self.framebuffer[self.ly as usize * SCREEN_WIDTH + fifo.x as usize] = self.compose(bg, sprite);
+        fifo.x += 1;
+
+        if fifo.x as usize == SCREEN_WIDTH {
+            if fifo.window {
+                self.window_line += 1;
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Advances the background fetcher by a dot
+    fn fetch(&mut self, fifo: &mut Fifo) {
+        fifo.step_dots += 1;
+
+        match fifo.step {
+            Step::Tile if fifo.step_dots == 2 => {
+                (fifo.tile, fifo.attributes) = if fifo.window {
+                    self.map_tile(LCDC_WINDOW_MAP, fifo.column, self.window_line / 8)
+                } else {
+                    let row = self.ly.wrapping_add(self.scy) / 8;
+                    self.map_tile(LCDC_BG_MAP, (self.scx / 8).wrapping_add(fifo.column), row)
+                };
+                fifo.next_step(Step::DataLow);
+            },
+            Step::DataLow if fifo.step_dots == 2 => fifo.next_step(Step::DataHigh),
+            Step::DataHigh if fifo.step_dots == 2 => {
+                let y = if fifo.window { self.window_line } else { self.ly.wrapping_add(self.scy) };
+                fifo.row = self.bg_tile_row(fifo.tile, fifo.attributes, y);
+                fifo.next_step(Step::Push);
+            },
+            Step::Push if fifo.background.is_empty() => {
+                let attributes = fifo.attributes;
+                fifo.background.extend((0..8).map(|x| BgPixel { color: pixel(fifo.row, x), attributes }));
+                fifo.column = fifo.column.wrapping_add(1);
+                fifo.next_step(Step::Tile);
+            },
+            _ => {}
+        }
+    }
+
+    /**
+     * Loads a fetched sprite into the sprite FIFO, under the opaque pixels of the sprites fetched before it
+     * unless it comes first in OAM and OAM order has priority.
+     */
+    fn merge_sprite(&self, fifo: &mut Fifo, sprite: &Sprite) {
+        let row = self.sprite_row(sprite);
+        fifo.sprites.resize(8, SpritePixel::default());
+
+        for column in 0..8 {
+            // Pixels left of the LCD position are off screen, or were already shifted out
+            let x = sprite.x as i16 - 8 + column as i16;
+            if x < fifo.x as i16 {
+                continue;
+            }
+
+            let below = &mut fifo.sprites[(x - fifo.x as i16) as usize];
+            let color = pixel(row, column);
+            if color != 0 && (below.color == 0 || (self.oam_priority() && sprite.index < below.index)) {
+                *below = SpritePixel { color, attributes: sprite.attributes, index: sprite.index };
+            }
+        }
+    }
+}
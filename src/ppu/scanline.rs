@@ -0,0 +1,74 @@
+use super::*;
+
+impl Ppu {
+    /// Draws the current line in one go, with the registers as they are at the start of the transfer
+    pub(super) fn render_scanline(&mut self) {
+        let ly = self.ly;
+        let mut row = [BgPixel::default(); SCREEN_WIDTH];
+
+        let window_visible = self.lcdc & LCDC_WINDOW_ENABLE != 0 && self.window_triggered && self.wx <= 166;
+        let mut window_drawn = false;
+
+        for (x, pixel_out) in row.iter_mut().enumerate() {
+            let x = x as u8;
+
+            let (map_bit, column, y) = if window_visible && x as u16 + 7 >= self.wx as u16 {
+                window_drawn = true;
+                (LCDC_WINDOW_MAP, x + 7 - self.wx, self.window_line)
+            } else {
+                (LCDC_BG_MAP, x.wrapping_add(self.scx), ly.wrapping_add(self.scy))
+            };
+
+            let (tile, attributes) = self.map_tile(map_bit, column / 8, y / 8);
+            let color = pixel(self.bg_tile_row(tile, attributes, y), column % 8);
+            *pixel_out = BgPixel { color, attributes };
+        }
+
+        if window_drawn {
+            self.window_line += 1;
+        }
+
+        let sprites = if self.lcdc & LCDC_OBJ_ENABLE != 0 {
+            self.render_sprites()
+        } else {
+            [SpritePixel::default(); SCREEN_WIDTH]
+        };
+
+        let line = ly as usize * SCREEN_WIDTH;
+        for x in 0..SCREEN_WIDTH {
+            self.framebuffer[line + x] = self.compose(row[x], sprites[x]);
+        }
+    }
+
+    /**
+     * The sprite pixels of the current line.
+     * Where sprites overlap the one with the highest priority wins, even if it is hidden behind the background:
+     * the one with the lowest X then the first in OAM, or just the first in OAM in CGB mode.
+     */
+    fn render_sprites(&self) -> [SpritePixel; SCREEN_WIDTH] {
+        let mut sprites = self.scan_oam();
+        if !self.oam_priority() {
+            sprites.sort_by_key(|sprite| sprite.x);
+        }
+        // Drawn from the lowest priority, so that the highest priority opaque pixel ends up on top
+        sprites.reverse();
+
+        let mut pixels = [SpritePixel::default(); SCREEN_WIDTH];
+        for sprite in &sprites {
+            let row = self.sprite_row(sprite);
+
+            for column in 0..8 {
+                let x = sprite.x as i16 - 8 + column as i16;
+                if !(0..SCREEN_WIDTH as i16).contains(&x) {
+                    continue;
+                }
+
+                let color = pixel(row, column);
+                if color != 0 {
+                    pixels[x as usize] = SpritePixel { color, attributes: sprite.attributes, index: sprite.index };
+                }
+            }
+        }
+        pixels
+    }
+}
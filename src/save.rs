@@ -0,0 +1,147 @@
+use std::{fs, io, path::{Path, PathBuf}, time::{Duration, Instant}};
+
+use crate::cartridge::Cartridge;
+use crate::mbc::{Mapper, RtcRegisters, RtcState};
+
+const DEFAULT_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+// BGB and VBA-M append the clock after the RAM, either with a 64-bit or a 32-bit timestamp
+const RTC_BLOCK_SIZE: usize = 48;
+const RTC_BLOCK_SIZE_SHORT: usize = 44;
+
+/**
+ * Persists the external RAM of battery-backed cartridges to a .sav file next to the ROM.
+ * For cartridges with a real-time clock the clock state is appended in the format
+ * used by BGB and VBA-M, so that saves can be moved between emulators.
+ * `GameBoy::attach_save` loads it, then every frame autosaves and `GameBoy::flush_save` writes it on exit.
+ */
+pub struct BatterySave {
+    path: PathBuf,
+    interval: Duration,
+    last_flush: Instant,
+    saved_ram: Vec<u8>
+}
+
+impl BatterySave {
+    /// Returns `None` for cartridges without a battery, whose RAM is lost on power off
+    pub fn for_rom(rom_path: &str, cart: &Cartridge) -> Option<BatterySave> {
+        cart.ty().has_battery()
+            .then(|| BatterySave::new(Path::new(rom_path).with_extension("sav")))
+    }
+
+    pub fn new(path: PathBuf) -> BatterySave {
+        BatterySave {
+            path,
+            interval: DEFAULT_AUTOSAVE_INTERVAL,
+            last_flush: Instant::now(),
+            saved_ram: Vec::new()
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /**
+     * Loads the save file into the mapper, returns false if there is no save file yet.
+     * Files of a different size than the cartridge RAM are loaded as far as they fit.
+     */
+    pub fn load(&mut self, mapper: &mut dyn Mapper) -> io::Result<bool> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e)
+        };
+
+        let ram = mapper.ram_mut();
+        let ram_len = ram.len().min(data.len());
+        ram[..ram_len].copy_from_slice(&data[..ram_len]);
+        self.saved_ram = ram.to_vec();
+
+        if let Some(rtc) = mapper.rtc_mut() {
+            if let Some(state) = decode_rtc(&data[ram_len..]) {
+                rtc.set_state(state);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /**
+     * Writes the save file if the RAM changed since the last write and the autosave interval elapsed.
+     * Meant to be called regularly, e.g. once per frame.
+     */
+    pub fn autosave(&mut self, mapper: &mut dyn Mapper) -> io::Result<()> {
+        if self.last_flush.elapsed() < self.interval {
+            return Ok(());
+        }
+        self.last_flush = Instant::now();
+
+        // The clock keeps running without the game writing anything, so timer cartridges always save
+        if mapper.ram() != self.saved_ram.as_slice() || mapper.rtc_mut().is_some() {
+            self.flush(mapper)?;
+        }
+        Ok(())
+    }
+
+    /// Unconditionally writes the save file, to be called on exit
+    pub fn flush(&mut self, mapper: &mut dyn Mapper) -> io::Result<()> {
+        let mut data = mapper.ram().to_vec();
+        if let Some(rtc) = mapper.rtc_mut() {
+            data.extend_from_slice(&encode_rtc(&rtc.state()));
+        }
+
+        fs::write(&self.path, &data)?;
+
+        self.saved_ram = mapper.ram().to_vec();
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+}
+
+/**
+ * The clock block is made of little-endian 32-bit words:
+ * the live seconds, minutes, hours, day low and day high registers,
+ * the same five latched registers, and the UNIX timestamp of the save as a 64-bit word.
+ */
+fn encode_rtc(state: &RtcState) -> Vec<u8> {
+    let mut block = Vec::with_capacity(RTC_BLOCK_SIZE);
+    for regs in [&state.live, &state.latched] {
+        for reg in [regs.seconds, regs.minutes, regs.hours, regs.day_low, regs.day_high] {
+            block.extend_from_slice(&(reg as u32).to_le_bytes());
+        }
+    }
+    block.extend_from_slice(&state.timestamp.to_le_bytes());
+    block
+}
+
+fn decode_rtc(block: &[u8]) -> Option<RtcState> {
+    if block.len() != RTC_BLOCK_SIZE && block.len() != RTC_BLOCK_SIZE_SHORT {
+        return None;
+    }
+
+    let word = |i: usize| block[i * 4];
+    let regs = |base: usize| RtcRegisters {
+        seconds:  word(base) & 0x3F,
+        minutes:  word(base + 1) & 0x3F,
+        hours:    word(base + 2) & 0x1F,
+        day_low:  word(base + 3),
+        day_high: word(base + 4) & 0xC1
+    };
+
+    let timestamp = if block.len() == RTC_BLOCK_SIZE {
+        u64::from_le_bytes(block[40..48].try_into().unwrap())
+    } else {
+        u32::from_le_bytes(block[40..44].try_into().unwrap()) as u64
+    };
+
+    Some(RtcState {
+        live: regs(0),
+        latched: regs(5),
+        timestamp
+    })
+}
@@ -0,0 +1,120 @@
+use crate::interrupt::{Interrupt, Interrupts};
+
+pub const DIV_ADDR: u16 = 0xFF04;
+pub const TIMA_ADDR: u16 = 0xFF05;
+pub const TMA_ADDR: u16 = 0xFF06;
+pub const TAC_ADDR: u16 = 0xFF07;
+
+const TAC_ENABLE: u8 = 0x04;
+
+/**
+ * The DIV, TIMA, TMA and TAC registers.
+ * Everything is driven by a 16-bit counter incremented every clock cycle, DIV being its upper byte.
+ * TIMA is incremented when the counter bit selected by TAC, ANDed with the enable bit, goes from 1 to 0,
+ * which is why resetting DIV or changing TAC can increment TIMA too.
+ * After overflowing, TIMA reads 0 for a machine cycle before being reloaded from TMA and requesting
+ * the interrupt: writing TIMA during that cycle cancels both, writing it during the reload is ignored.
+ */
+pub struct Timer {
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+    // TIMA overflowed during the last cycle, it is reloaded in the next one
+    overflow: bool,
+    // TIMA was reloaded from TMA during this cycle
+    reloading: bool
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Timer::new()
+    }
+}
+
+impl Timer {
+    /// Creates a timer in the state the DMG boot ROM leaves it in
+    pub fn new() -> Timer {
+        Timer {
+            counter: 0xABCC,
+            tima: 0,
+            tma: 0,
+            tac: 0,
+            overflow: false,
+            reloading: false
+        }
+    }
+
+    /// The counter bit selected by TAC, ANDed with the enable bit
+    fn signal(&self) -> bool {
+        let bit = match self.tac & 0x03 {
+            0 => 9,     // 4096 Hz
+            1 => 3,     // 262144 Hz
+            2 => 5,     // 65536 Hz
+            _ => 7      // 16384 Hz
+        };
+        self.tac & TAC_ENABLE != 0 && self.counter & (1 << bit) != 0
+    }
+
+    /// Increments TIMA if the signal went from 1 to 0
+    fn detect_edge(&mut self, before: bool) {
+        if before && !self.signal() {
+            let (tima, overflow) = self.tima.overflowing_add(1);
+            self.tima = tima;
+            self.overflow = overflow;
+        }
+    }
+
+    /// Advances the timer by a machine cycle
+    pub fn tick(&mut self, interrupts: &mut Interrupts) {
+        self.reloading = false;
+        if self.overflow {
+            self.overflow = false;
+            self.reloading = true;
+            self.tima = self.tma;
+            interrupts.request(Interrupt::Timer);
+        }
+
+        let before = self.signal();
+        self.counter = self.counter.wrapping_add(4);
+        self.detect_edge(before);
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            DIV_ADDR    => (self.counter >> 8) as u8,
+            TIMA_ADDR   => self.tima,
+            TMA_ADDR    => self.tma,
+            TAC_ADDR    => self.tac | 0xF8,
+            _ => 0xFF
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, value: u8) {
+        let before = self.signal();
+
+        match addr {
+            DIV_ADDR => self.counter = 0,
+            TIMA_ADDR if self.reloading => {},
+            TIMA_ADDR => {
+                self.tima = value;
+                self.overflow = false;
+            },
+            TMA_ADDR => {
+                self.tma = value;
+                if self.reloading {
+                    self.tima = value;
+                }
+            },
+            TAC_ADDR => self.tac = value & 0x07,
+            _ => {}
+        }
+
+        self.detect_edge(before);
+    }
+
+    /// The internal counter, DIV is its upper byte
+    pub fn counter(&self) -> u16 {
+        self.counter
+    }
+}
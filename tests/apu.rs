@@ -0,0 +1,279 @@
+use emu::apu::{Apu, CLOCK_RATE, DEFAULT_SAMPLE_RATE, NR10_ADDR, NR11_ADDR, NR12_ADDR, NR13_ADDR, NR14_ADDR,
+    NR21_ADDR, NR24_ADDR, NR30_ADDR, NR34_ADDR, NR41_ADDR, NR44_ADDR, NR50_ADDR, NR51_ADDR, NR52_ADDR, WAVE_RAM_ADDR,
+    WAVE_RAM_END};
+
+const NR22_ADDR: u16 = NR21_ADDR + 1;
+const NR23_ADDR: u16 = NR21_ADDR + 2;
+const NR31_ADDR: u16 = NR30_ADDR + 1;
+const NR42_ADDR: u16 = NR41_ADDR + 1;
+
+const TRIGGER: u8 = 0x80;
+const LENGTH_ENABLE: u8 = 0x40;
+
+fn steps(apu: &mut Apu, count: usize) {
+    for _ in 0..count {
+        apu.step_frame_sequencer();
+    }
+}
+
+fn status(apu: &Apu) -> u8 {
+    apu.read(NR52_ADDR)
+}
+
+/// An APU after power cycling, with every channel off and the frame sequencer at step 0
+fn power_cycled() -> Apu {
+    let mut apu = Apu::new();
+    apu.write(NR52_ADDR, 0x00);
+    apu.write(NR52_ADDR, 0x80);
+    apu
+}
+
+#[test]
+fn nr52_reports_the_channels_which_are_on() {
+    // After the boot sound only the first pulse channel is still on
+    assert_eq!(status(&Apu::new()), 0x81);
+
+    let mut apu = power_cycled();
+    assert_eq!(status(&apu), 0x80);
+
+    apu.write(NR12_ADDR, 0xF0);
+    apu.write(NR14_ADDR, TRIGGER);
+    apu.write(NR22_ADDR, 0xF0);
+    apu.write(NR24_ADDR, TRIGGER);
+    apu.write(NR30_ADDR, 0x80);
+    apu.write(NR34_ADDR, TRIGGER);
+    apu.write(NR42_ADDR, 0xF0);
+    apu.write(NR44_ADDR, TRIGGER);
+    assert_eq!(status(&apu), 0x8F);
+
+    // The status bits are read only
+    apu.write(NR52_ADDR, 0x80);
+    assert_eq!(status(&apu), 0x8F);
+
+    // Turning a DAC off turns its channel off
+    apu.write(NR12_ADDR, 0x00);
+    apu.write(NR30_ADDR, 0x00);
+    assert_eq!(status(&apu), 0x8A);
+
+    // And triggering a channel with its DAC off does not turn it on
+    apu.write(NR14_ADDR, TRIGGER);
+    assert_eq!(status(&apu), 0x8A);
+}
+
+#[test]
+fn power_off_clears_the_registers_but_not_wave_ram() {
+    let mut apu = Apu::new();
+    for addr in WAVE_RAM_ADDR..=WAVE_RAM_END {
+        apu.write(addr, addr as u8);
+    }
+    apu.write(NR50_ADDR, 0x77);
+    apu.write(NR11_ADDR, 0x80);
+    apu.write(NR22_ADDR, 0xF0);
+    apu.write(NR24_ADDR, TRIGGER);
+
+    apu.write(NR52_ADDR, 0x00);
+    assert_eq!(status(&apu), 0x00);
+    for addr in NR10_ADDR..NR52_ADDR {
+        assert_eq!(apu.read(addr), 0x00, "{:#06X}", addr);
+    }
+    for addr in WAVE_RAM_ADDR..=WAVE_RAM_END {
+        assert_eq!(apu.read(addr), addr as u8);
+    }
+
+    // Registers ignore writes until it is powered on again, wave RAM does not
+    apu.write(NR50_ADDR, 0x77);
+    apu.write(NR22_ADDR, 0xF0);
+    apu.write(NR24_ADDR, TRIGGER);
+    apu.write(WAVE_RAM_ADDR, 0xAB);
+    assert_eq!(apu.read(NR50_ADDR), 0x00);
+    assert_eq!(apu.read(WAVE_RAM_ADDR), 0xAB);
+    assert_eq!(status(&apu), 0x00);
+
+    // The frame sequencer does nothing while off
+    steps(&mut apu, 8);
+
+    apu.write(NR52_ADDR, 0x80);
+    assert_eq!(status(&apu), 0x80);
+    apu.write(NR50_ADDR, 0x77);
+    assert_eq!(apu.read(NR50_ADDR), 0x77);
+}
+
+#[test]
+fn length_disables_the_channel_on_even_steps() {
+    let mut apu = power_cycled();
+    // 64 - 62 = 2 length clocks
+    apu.write(NR21_ADDR, 62);
+    apu.write(NR22_ADDR, 0xF0);
+    apu.write(NR24_ADDR, TRIGGER | LENGTH_ENABLE);
+
+    // Step 0 clocks the length, step 1 does not, step 2 runs it out
+    steps(&mut apu, 2);
+    assert_eq!(status(&apu) & 0x02, 0x02);
+    steps(&mut apu, 1);
+    assert_eq!(status(&apu) & 0x02, 0x00);
+
+    // Without the enable bit the counter is not clocked
+    apu.write(NR21_ADDR, 63);
+    apu.write(NR24_ADDR, TRIGGER);
+    steps(&mut apu, 64);
+    assert_eq!(status(&apu) & 0x02, 0x02);
+
+    // The wave channel counts up to 256, a counter which ran out is reloaded with the maximum on trigger
+    apu.write(NR30_ADDR, 0x80);
+    apu.write(NR31_ADDR, 0xFF);
+    apu.write(NR34_ADDR, TRIGGER | LENGTH_ENABLE);
+    steps(&mut apu, 2);
+    assert_eq!(status(&apu) & 0x04, 0x00);
+    apu.write(NR34_ADDR, TRIGGER | LENGTH_ENABLE);
+    steps(&mut apu, 2 * 255);
+    assert_eq!(status(&apu) & 0x04, 0x04);
+    steps(&mut apu, 2);
+    assert_eq!(status(&apu) & 0x04, 0x00);
+}
+
+/**
+ * The volume of the second pulse channel, from its output over a period of its waveform.
+ * At the highest frequency a period lasts 32 clock cycles, producing one sample per machine cycle.
+ * With the 50% duty cycle the output goes between -1 and volume / 7.5 - 1, divided by 4 by NR50 = 0x77.
+ */
+fn pulse2_volume(apu: &mut Apu) -> u8 {
+    apu.take_samples();
+    for _ in 0..8 {
+        apu.tick(4);
+    }
+
+    let samples = apu.take_samples();
+    assert_eq!(samples.len(), 8);
+    let max = samples.iter().map(|sample| sample.0).fold(f32::MIN, f32::max);
+    let min = samples.iter().map(|sample| sample.0).fold(f32::MAX, f32::min);
+    ((max - min) * 30.0).round() as u8
+}
+
+/// An APU playing the second pulse channel alone on the left output, the envelope set up by NR22
+fn pulse2(nr22: u8) -> Apu {
+    let mut apu = power_cycled();
+    apu.set_sample_rate(CLOCK_RATE / 4);
+    apu.write(NR50_ADDR, 0x77);
+    apu.write(NR51_ADDR, 0x20);
+    apu.write(NR21_ADDR, 0x80);
+    apu.write(NR22_ADDR, nr22);
+    apu.write(NR23_ADDR, 0xFF);
+    apu.write(NR24_ADDR, TRIGGER | 0x07);
+    apu
+}
+
+#[test]
+fn envelope_is_clocked_on_step_7() {
+    // Decreasing from 10 every envelope clock
+    let mut apu = pulse2(0xA1);
+    assert_eq!(pulse2_volume(&mut apu), 10);
+    steps(&mut apu, 7);
+    assert_eq!(pulse2_volume(&mut apu), 10);
+    steps(&mut apu, 1);
+    assert_eq!(pulse2_volume(&mut apu), 9);
+    steps(&mut apu, 8 * 20);
+    assert_eq!(pulse2_volume(&mut apu), 0);
+
+    // Increasing from 13 every 2 clocks, up to 15
+    let mut apu = pulse2(0xDA);
+    steps(&mut apu, 8);
+    assert_eq!(pulse2_volume(&mut apu), 13);
+    steps(&mut apu, 8);
+    assert_eq!(pulse2_volume(&mut apu), 14);
+    steps(&mut apu, 8 * 10);
+    assert_eq!(pulse2_volume(&mut apu), 15);
+
+    // A period of 0 stops it
+    let mut apu = pulse2(0x58);
+    steps(&mut apu, 8 * 4);
+    assert_eq!(pulse2_volume(&mut apu), 5);
+}
+
+fn pulse1_on(apu: &Apu) -> bool {
+    status(apu) & 0x01 != 0
+}
+
+/// Triggers the first pulse channel with the sweep in NR10 and an 11-bit frequency
+fn trigger_sweep(apu: &mut Apu, nr10: u8, frequency: u16) {
+    apu.write(NR10_ADDR, nr10);
+    apu.write(NR12_ADDR, 0xF0);
+    apu.write(NR13_ADDR, frequency as u8);
+    apu.write(NR14_ADDR, TRIGGER | (frequency >> 8) as u8);
+}
+
+#[test]
+fn sweep_overflow_disables_the_channel() {
+    // Checked on trigger: 0x700 + 0x700 / 2 does not fit in 11 bits
+    let mut apu = power_cycled();
+    trigger_sweep(&mut apu, 0x11, 0x700);
+    assert!(!pulse1_on(&apu));
+
+    // 0x500 + 0x280 fits and is applied on step 2, then the next one is checked right away and does not
+    let mut apu = power_cycled();
+    trigger_sweep(&mut apu, 0x11, 0x500);
+    steps(&mut apu, 2);
+    assert!(pulse1_on(&apu));
+    steps(&mut apu, 1);
+    assert!(!pulse1_on(&apu));
+
+    // With a period of 2 the sweep only runs every other time, on step 6
+    let mut apu = power_cycled();
+    trigger_sweep(&mut apu, 0x21, 0x500);
+    steps(&mut apu, 3);
+    assert!(pulse1_on(&apu));
+    steps(&mut apu, 4);
+    assert!(!pulse1_on(&apu));
+
+    // Subtracting never overflows
+    let mut apu = power_cycled();
+    trigger_sweep(&mut apu, 0x19, 0x700);
+    steps(&mut apu, 8 * 8);
+    assert!(pulse1_on(&apu));
+}
+
+#[test]
+fn clearing_negate_after_a_subtraction_disables_the_channel() {
+    // No subtraction was calculated with a shift of 0
+    let mut apu = power_cycled();
+    trigger_sweep(&mut apu, 0x18, 0x400);
+    apu.write(NR10_ADDR, 0x10);
+    assert!(pulse1_on(&apu));
+
+    // The overflow check on trigger counts as one
+    let mut apu = power_cycled();
+    trigger_sweep(&mut apu, 0x19, 0x400);
+    apu.write(NR10_ADDR, 0x11);
+    assert!(!pulse1_on(&apu));
+
+    // So does a sweep step, setting negate again does not matter
+    let mut apu = power_cycled();
+    trigger_sweep(&mut apu, 0x18, 0x400);
+    apu.write(NR10_ADDR, 0x19);
+    steps(&mut apu, 3);
+    assert!(pulse1_on(&apu));
+    apu.write(NR10_ADDR, 0x19);
+    apu.write(NR10_ADDR, 0x11);
+    assert!(!pulse1_on(&apu));
+
+    // A trigger starts over
+    let mut apu = power_cycled();
+    trigger_sweep(&mut apu, 0x19, 0x400);
+    trigger_sweep(&mut apu, 0x18, 0x400);
+    apu.write(NR10_ADDR, 0x10);
+    assert!(pulse1_on(&apu));
+}
+
+#[test]
+fn samples_are_produced_at_the_sample_rate() {
+    let mut apu = Apu::new();
+    for _ in 0..CLOCK_RATE / 4 {
+        apu.tick(4);
+    }
+    assert_eq!(apu.take_samples().len(), DEFAULT_SAMPLE_RATE as usize);
+
+    // A long tick produces every sample due without overflowing the sample clock
+    apu.tick(CLOCK_RATE * 2);
+    let samples = apu.take_samples();
+    assert_eq!(samples.len(), 2 * DEFAULT_SAMPLE_RATE as usize);
+    assert!(samples.iter().all(|(left, right)| left.is_finite() && right.is_finite()));
+}
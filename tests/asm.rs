@@ -0,0 +1,156 @@
+use emu::asm::assemble;
+
+/// Assembles the source as a ROM0 section at 0x150 and returns the bytes it emitted there
+fn bytes(source: &str, len: usize) -> Vec<u8> {
+    let program = assemble(&format!("SECTION \"Test\", ROM0[$150]\n{}", source))
+        .unwrap_or_else(|e| panic!("{}", e));
+    program.rom[0x150..0x150 + len].to_vec()
+}
+
+#[test]
+fn resolves_labels_and_local_labels() {
+    let program = assemble("
+SECTION \"Code\", ROM0[$150]
+First:
+    nop
+.loop:
+    jp .loop
+Second::
+.loop:
+    call First.loop
+    jp Third
+Third:
+    ret
+").unwrap();
+
+    assert_eq!(program.symbol("First"), Some(0x150));
+    assert_eq!(program.symbol("First.loop"), Some(0x151));
+    assert_eq!(program.symbol("Second"), Some(0x154));
+    assert_eq!(program.symbol("Second.loop"), Some(0x154));
+    assert_eq!(program.symbol("Third"), Some(0x15A));
+
+    // Local labels resolve within their scope, forward references are resolved in the second pass
+    assert_eq!(&program.rom[0x150..0x15B], &[
+        0x00,
+        0xC3, 0x51, 0x01,
+        0xCD, 0x51, 0x01,
+        0xC3, 0x5A, 0x01,
+        0xC9
+    ]);
+}
+
+#[test]
+fn unknown_and_duplicate_labels_are_errors() {
+    assert!(assemble("jp Nowhere").is_err());
+    assert!(assemble("Here:\nHere:").is_err());
+
+    let error = assemble("nop\nnop\nfoo a").err().unwrap();
+    assert_eq!(error.line, 3);
+}
+
+#[test]
+fn encodes_jr_offsets() {
+    assert_eq!(bytes("
+Back:
+    nop
+    jr Back
+    jr nz, Forward
+    nop
+Forward:
+    jr @
+", 8), [0x00, 0x18, 0xFD, 0x20, 0x01, 0x00, 0x18, 0xFE]);
+
+    // The offset is relative to the instruction after the JR, from -128 to 127
+    assert_eq!(bytes("jr Far\nds 127\nFar:", 2), [0x18, 0x7F]);
+    assert_eq!(bytes("Far:\nds 126\njr Far", 128)[126..], [0x18, 0x80]);
+}
+
+#[test]
+fn jr_out_of_range_is_an_error() {
+    let error = assemble("jr Far\nds 128\nFar:").err().unwrap();
+    assert_eq!(error.line, 1);
+    assert!(error.message.contains("out of range"), "{}", error.message);
+
+    assert!(assemble("Far:\nds 127\njr Far").is_err());
+}
+
+#[test]
+fn encodes_high_page_and_sp_forms() {
+    assert_eq!(bytes("
+    ld a, [c]
+    ld [c], a
+    ldh a, [c]
+    ldh [c], a
+    ld a, [$FF00+c]
+    ld [$FF00+c], a
+", 6), [0xF2, 0xE2, 0xF2, 0xE2, 0xF2, 0xE2]);
+
+    // LDH takes either the full address or its low byte
+    assert_eq!(bytes("
+    ldh [$FF40], a
+    ldh a, [$40]
+    ld [$FF40], a
+", 7), [0xE0, 0x40, 0xF0, 0x40, 0xEA, 0x40, 0xFF]);
+
+    assert_eq!(bytes("
+    ld hl, sp+5
+    ld hl, sp-2
+    ld sp, hl
+    add sp, -1
+", 7), [0xF8, 0x05, 0xF8, 0xFE, 0xF9, 0xE8, 0xFF]);
+
+    assert!(assemble("ldh [$FE00], a").is_err());
+    assert!(assemble("ld hl, sp+128").is_err());
+}
+
+#[test]
+fn encodes_stop_rst_and_data() {
+    assert_eq!(bytes("stop\nstop $12", 4), [0x10, 0x00, 0x10, 0x12]);
+    assert_eq!(bytes("rst $00\nrst $38\nrst 8", 3), [0xC7, 0xFF, 0xCF]);
+    assert!(assemble("rst $01").is_err());
+
+    assert_eq!(bytes("db \"Hi\", 0, $FF\ndw $1234, Label\nLabel:", 8),
+        [b'H', b'i', 0x00, 0xFF, 0x34, 0x12, 0x58, 0x01]);
+    assert_eq!(bytes("dw \"AB\"", 4), [b'A', 0x00, b'B', 0x00]);
+}
+
+#[test]
+fn evaluates_expressions() {
+    assert_eq!(bytes("
+DEF VALUE EQU $1234
+    db HIGH(VALUE), LOW(VALUE)
+    db HIGH(Label), LOW(Label)
+    ld a, ((VALUE >> 4) & $F) + 1
+    ld bc, VALUE * 2 - 1
+Label:
+", 9), [0x12, 0x34, 0x01, 0x59, 0x3E, 0x04, 0x01, 0x67, 0x24]);
+}
+
+#[test]
+fn places_romx_sections() {
+    let program = assemble("
+SECTION \"Home\", ROM0[$150]
+    ld a, BANK_VALUE
+SECTION \"Bank 1\", ROMX[$4000]
+BankOne:
+    db 1
+SECTION \"Bank 3\", ROMX[$4100], BANK[3]
+BankThree:
+    db 3
+SECTION \"More bank 3\", ROMX, BANK[3]
+    db 4
+DEF BANK_VALUE EQU 7
+").unwrap_or_else(|e| panic!("{}", e));
+
+    // ROMX defaults to bank 1, floating sections continue where the last one in their bank ended
+    assert_eq!(program.symbol("BankOne"), Some(0x4000));
+    assert_eq!(program.symbol("BankThree"), Some(0x4100));
+    assert_eq!(program.rom[0x4000], 1);
+    assert_eq!(program.rom[3 * 0x4000 + 0x100], 3);
+    assert_eq!(program.rom[3 * 0x4000 + 0x101], 4);
+    assert_eq!(program.rom.len(), 4 * 0x4000);
+
+    assert!(assemble("SECTION \"Bad\", ROMX[$4000], BANK[0]").is_err());
+    assert!(assemble("SECTION \"Bad\", ROMX[$3000]").is_err());
+    assert!(assemble("SECTION \"Bad\", ROM0[$4000]").is_err());
+}
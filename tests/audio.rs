@@ -0,0 +1,51 @@
+use emu::audio::wav;
+
+fn u16_at(file: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(file[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(file: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(file[offset..offset + 4].try_into().unwrap())
+}
+
+fn samples(file: &[u8]) -> Vec<i16> {
+    file[44..].chunks(2).map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]])).collect()
+}
+
+#[test]
+fn wav_headers_describe_16_bit_stereo_pcm() {
+    let file = wav(&[(0.0, 0.0); 10], 48000);
+    assert_eq!(file.len(), 44 + 10 * 4);
+
+    // The RIFF size covers everything after it
+    assert_eq!(&file[0..4], b"RIFF");
+    assert_eq!(u32_at(&file, 4), 36 + 10 * 4);
+    assert_eq!(&file[8..12], b"WAVE");
+
+    assert_eq!(&file[12..16], b"fmt ");
+    assert_eq!(u32_at(&file, 16), 16);
+    assert_eq!(u16_at(&file, 20), 1);
+    assert_eq!(u16_at(&file, 22), 2);
+    assert_eq!(u32_at(&file, 24), 48000);
+    assert_eq!(u32_at(&file, 28), 48000 * 4);
+    assert_eq!(u16_at(&file, 32), 4);
+    assert_eq!(u16_at(&file, 34), 16);
+
+    assert_eq!(&file[36..40], b"data");
+    assert_eq!(u32_at(&file, 40), 10 * 4);
+
+    // Without samples only the headers are left
+    let empty = wav(&[], 44100);
+    assert_eq!(empty.len(), 44);
+    assert_eq!(u32_at(&empty, 4), 36);
+    assert_eq!(u32_at(&empty, 28), 44100 * 4);
+    assert_eq!(u32_at(&empty, 40), 0);
+}
+
+#[test]
+fn wav_clamps_samples_outside_the_full_range() {
+    let file = wav(&[(0.0, 0.5), (1.0, -1.0), (2.0, -3.0), (f32::INFINITY, f32::NEG_INFINITY)], 48000);
+
+    // Left then right, the full range is symmetric
+    assert_eq!(samples(&file), [0, 16383, 32767, -32767, 32767, -32767, 32767, -32767]);
+}
@@ -0,0 +1,127 @@
+use emu::apu::{NR10_ADDR, NR13_ADDR, NR30_ADDR, NR41_ADDR, NR52_ADDR, WAVE_RAM_ADDR};
+use emu::bus::{Bus, P1_ADDR};
+use emu::cpu::MemoryBus;
+use emu::interrupt::{IE_ADDR, IF_ADDR};
+use emu::mbc::RomOnly;
+use emu::ppu::{Mode, LCDC_ADDR, STAT_ADDR};
+use emu::timer::TAC_ADDR;
+
+fn bus() -> Bus {
+    Bus::with_mapper(Box::new(RomOnly::new(vec![0; 0x8000], vec![])))
+}
+
+#[test]
+fn p1_reads_released_buttons() {
+    let mut bus = bus();
+    assert_eq!(bus.read(P1_ADDR), 0xCF);
+
+    // Selecting the direction or the action buttons, or both, never reports a pressed button
+    for (select, expected) in [(0x20, 0xEF), (0x10, 0xDF), (0x00, 0xCF), (0x30, 0xFF)] {
+        bus.write(P1_ADDR, select);
+        assert_eq!(bus.read(P1_ADDR), expected, "select {:02X}", select);
+    }
+
+    // The button bits and the unused upper bits cannot be written
+    bus.write(P1_ADDR, 0xC0);
+    assert_eq!(bus.read(P1_ADDR), 0xCF);
+}
+
+#[test]
+fn echo_ram_mirrors_wram() {
+    let mut bus = bus();
+    bus.write(0xC000, 0x12);
+    bus.write(0xDDFF, 0x34);
+    assert_eq!(bus.read(0xE000), 0x12);
+    assert_eq!(bus.read(0xFDFF), 0x34);
+
+    bus.write(0xE123, 0x56);
+    assert_eq!(bus.read(0xC123), 0x56);
+    bus.write(0xFD00, 0x78);
+    assert_eq!(bus.read(0xDD00), 0x78);
+
+    // The end of WRAM is not echoed, OAM comes first
+    bus.write(0xDE00, 0x9A);
+    bus.write(LCDC_ADDR, 0x00);
+    assert_eq!(bus.read(0xFE00), 0x00);
+}
+
+#[test]
+fn unusable_area_reads_0_and_ignores_writes() {
+    let mut bus = bus();
+    // The boot ROM hands over in mode 2 of line 0, while OAM is blocked
+    assert_eq!(bus.ppu().mode(), Mode::OamScan);
+    assert_eq!(bus.read(0xFEA0), 0xFF);
+
+    bus.write(LCDC_ADDR, 0x00);
+    for addr in 0xFEA0..=0xFEFF {
+        bus.write(addr, 0x42);
+        assert_eq!(bus.read(addr), 0x00, "{:04X}", addr);
+    }
+}
+
+#[test]
+fn unmapped_io_reads_0xff() {
+    let mut bus = bus();
+    let unmapped = [0xFF03].into_iter()
+        .chain(0xFF08..=0xFF0E)
+        .chain([0xFF15, 0xFF1F])
+        .chain(0xFF27..=0xFF2F)
+        .chain(0xFF4C..=0xFF7F);
+
+    for addr in unmapped {
+        bus.write(addr, 0x00);
+        assert_eq!(bus.read(addr), 0xFF, "{:04X}", addr);
+    }
+}
+
+#[test]
+fn unused_register_bits_read_as_1() {
+    let mut bus = bus();
+    // Register, the bits which do not exist
+    let masks = [(TAC_ADDR, 0xF8), (STAT_ADDR, 0x80), (NR10_ADDR, 0x80), (NR30_ADDR, 0x7F), (NR41_ADDR, 0xFF), (NR52_ADDR, 0x70)];
+
+    bus.write(LCDC_ADDR, 0x00);
+    for (addr, mask) in masks {
+        bus.write(addr, 0x00);
+        assert_eq!(bus.read(addr) & mask, mask, "{:04X}", addr);
+    }
+
+    // The write-only frequency registers read as 0xFF
+    bus.write(NR13_ADDR, 0x00);
+    assert_eq!(bus.read(NR13_ADDR), 0xFF);
+    // Wave RAM has no unused bits
+    bus.write(NR52_ADDR, 0x00);
+    bus.write(WAVE_RAM_ADDR, 0x00);
+    assert_eq!(bus.read(WAVE_RAM_ADDR), 0x00);
+}
+
+#[test]
+fn if_upper_bits_read_as_1() {
+    let mut bus = bus();
+    // The VBlank request of the last boot ROM frame is still pending
+    assert_eq!(bus.read(IF_ADDR), 0xE1);
+
+    bus.write(IF_ADDR, 0x00);
+    assert_eq!(bus.read(IF_ADDR), 0xE0);
+    bus.write(IF_ADDR, 0xFF);
+    assert_eq!(bus.read(IF_ADDR), 0xFF);
+    assert_eq!(bus.interrupts().flags, 0x1F);
+}
+
+#[test]
+fn ie_is_a_full_register_at_0xffff() {
+    let mut bus = bus();
+    assert_eq!(bus.read(IE_ADDR), 0x00);
+
+    // All 8 bits are stored, even though only the lower 5 enable interrupts
+    bus.write(IE_ADDR, 0xFF);
+    assert_eq!(bus.read(IE_ADDR), 0xFF);
+    bus.write(IE_ADDR, 0xA5);
+    assert_eq!(bus.read(IE_ADDR), 0xA5);
+    assert_eq!(bus.interrupts().enable, 0xA5);
+
+    // HRAM ends just before it
+    bus.write(0xFFFE, 0x12);
+    assert_eq!(bus.read(0xFFFE), 0x12);
+    assert_eq!(bus.read(IE_ADDR), 0xA5);
+}
@@ -0,0 +1,166 @@
+use emu::cartridge::{Cartridge, CartridgeError, CartridgeType};
+
+/// A 32 KiB ROM only cartridge with a valid header
+fn rom() -> Vec<u8> {
+    vec![0; 0x8000]
+}
+
+fn error(data: Vec<u8>) -> CartridgeError {
+    match Cartridge::from_bytes(data) {
+        Ok(_) => panic!("the header was accepted"),
+        Err(e) => e
+    }
+}
+
+#[test]
+fn valid_header_is_accepted() {
+    let cart = Cartridge::from_bytes(rom()).unwrap();
+    assert_eq!(cart.ty(), CartridgeType::RomOnly);
+    assert_eq!(cart.rom_size_bytes(), Some(0x8000));
+    assert_eq!(cart.ram_size_bytes(), Some(0));
+}
+
+#[test]
+fn file_shorter_than_the_header_is_rejected() {
+    assert!(matches!(error(vec![0; 0x14F]), CartridgeError::TooShort(0x14F)));
+    assert!(matches!(Cartridge::from_bytes_lenient(vec![]), Err(CartridgeError::TooShort(0))));
+}
+
+#[test]
+fn unsupported_rom_size_is_rejected() {
+    let mut data = rom();
+    data[0x148] = 0x09;
+    assert!(matches!(error(data.clone()), CartridgeError::UnsupportedRomSize(0x09)));
+
+    let cart = Cartridge::from_bytes_lenient(data).unwrap();
+    assert_eq!(cart.rom_size_bytes(), None);
+}
+
+#[test]
+fn unsupported_ram_size_is_rejected() {
+    let mut data = rom();
+    // Code 1 was never used by licensed cartridges
+    data[0x149] = 0x01;
+    assert!(matches!(error(data.clone()), CartridgeError::UnsupportedRamSize(0x01)));
+
+    data[0x149] = 0x06;
+    assert!(matches!(error(data), CartridgeError::UnsupportedRamSize(0x06)));
+}
+
+#[test]
+fn unknown_cartridge_type_is_rejected_unless_lenient() {
+    for code in [0x04, 0x23, 0x80] {
+        let mut data = rom();
+        data[0x147] = code;
+        assert!(matches!(error(data.clone()), CartridgeError::UnknownCartridgeType(ty) if ty == code));
+
+        let cart = Cartridge::from_bytes_lenient(data).unwrap();
+        assert_eq!(cart.ty(), CartridgeType::Unknown(code));
+    }
+}
+
+#[test]
+fn known_cartridge_types_are_decoded() {
+    assert_eq!(CartridgeType::from(0x00), CartridgeType::RomOnly);
+    assert_eq!(CartridgeType::from(0x13), CartridgeType::MBC3RamBattery);
+    assert_eq!(CartridgeType::from(0x22), CartridgeType::MBC7SensorRumbleRamBattery);
+    assert_eq!(CartridgeType::from(0xFF), CartridgeType::HuC1RamBattery);
+}
+
+#[test]
+fn unknown_flags_are_rejected_unless_lenient() {
+    let mut data = rom();
+    data[0x143] = 0x42;
+    assert!(matches!(error(data.clone()), CartridgeError::UnknownCGBFlag(0x42)));
+    assert!(Cartridge::from_bytes_lenient(data).is_ok());
+
+    let mut data = rom();
+    data[0x146] = 0x01;
+    assert!(matches!(error(data.clone()), CartridgeError::UnknownSGBFlag(0x01)));
+    assert!(Cartridge::from_bytes_lenient(data).is_ok());
+}
+
+#[test]
+fn unsupported_mapper_is_reported() {
+    let mut data = rom();
+    data[0x147] = 0xFC;
+    let cart = Cartridge::from_bytes(data).unwrap();
+    assert!(matches!(cart.mapper(), Err(CartridgeError::UnsupportedMapper(CartridgeType::PocketCamera))));
+}
+
+/// A ROM with a title, some code and correct logo and checksums
+fn valid_rom() -> Vec<u8> {
+    let mut data = rom();
+    data[0x134..0x139].copy_from_slice(b"TITLE");
+    data[0x150..0x154].copy_from_slice(&[0x3E, 0x42, 0x18, 0xFE]);
+    Cartridge::from_bytes(data).unwrap().fixed_data()
+}
+
+#[test]
+fn fixed_data_passes_verification() {
+    let cart = Cartridge::from_bytes(rom()).unwrap();
+    let report = cart.verify();
+    assert!(!report.logo_ok);
+    assert!(!report.is_valid());
+
+    let fixed = Cartridge::from_bytes(cart.fixed_data()).unwrap();
+    let report = fixed.verify();
+    assert!(report.logo_ok);
+    assert!(report.header_checksum_ok());
+    assert!(report.global_checksum_ok());
+    assert!(report.is_valid());
+}
+
+#[test]
+fn corrupted_logo_is_reported() {
+    let mut data = valid_rom();
+    // Swapping two bytes keeps both checksums intact
+    data.swap(0x104, 0x105);
+
+    let report = Cartridge::from_bytes(data).unwrap().verify();
+    assert!(!report.logo_ok);
+    assert!(report.header_checksum_ok());
+    assert!(report.global_checksum_ok());
+    assert!(!report.is_valid());
+}
+
+#[test]
+fn corrupted_header_checksum_is_reported() {
+    let mut data = valid_rom();
+    data[0x14D] = data[0x14D].wrapping_add(1);
+    // Compensated outside the header so that the global checksum still matches
+    data[0x150] = data[0x150].wrapping_sub(1);
+
+    let report = Cartridge::from_bytes(data).unwrap().verify();
+    assert!(report.logo_ok);
+    assert!(!report.header_checksum_ok());
+    assert_eq!(report.header_checksum, report.computed_header_checksum.wrapping_add(1));
+    assert!(report.global_checksum_ok());
+    assert!(!report.is_valid());
+}
+
+#[test]
+fn corrupted_global_checksum_is_reported() {
+    let mut data = valid_rom();
+    data[0x14F] ^= 0xFF;
+
+    let report = Cartridge::from_bytes(data).unwrap().verify();
+    assert!(report.logo_ok);
+    assert!(report.header_checksum_ok());
+    assert!(!report.global_checksum_ok());
+    assert!(!report.is_valid());
+}
+
+#[test]
+fn corrupted_rom_data_is_only_caught_by_the_global_checksum() {
+    let mut data = valid_rom();
+    data[0x4000] = 0x01;
+
+    let cart = Cartridge::from_bytes(data).unwrap();
+    let report = cart.verify();
+    assert!(report.header_checksum_ok());
+    assert!(!report.global_checksum_ok());
+    assert_eq!(report.computed_global_checksum, report.global_checksum.wrapping_add(1));
+
+    assert!(Cartridge::from_bytes(cart.fixed_data()).unwrap().verify().is_valid());
+}
@@ -0,0 +1,256 @@
+use emu::bus::{Bus, KEY1_ADDR, SVBK_ADDR};
+use emu::cartridge::Cartridge;
+use emu::cpu::{Cpu, MemoryBus};
+use emu::mbc::RomOnly;
+use emu::model::Model;
+use emu::ppu::{Mode, BCPD_ADDR, BCPS_ADDR, BGP_ADDR, DMG_COLORS, LCDC_ADDR, OBP0_ADDR, OBP1_ADDR, OCPD_ADDR,
+    OCPS_ADDR, LY_ADDR, SCREEN_WIDTH, VBK_ADDR};
+use emu::timer::DIV_ADDR;
+
+fn bus_with(model: Model) -> Bus {
+    Bus::with_model(Box::new(RomOnly::new(vec![0; 0x8000], vec![])), model)
+}
+
+/// A CGB bus with the LCD off, so that VRAM and palette RAM are always accessible
+fn cgb_lcd_off() -> Bus {
+    let mut bus = bus_with(Model::Cgb);
+    bus.write(LCDC_ADDR, 0x00);
+    bus
+}
+
+#[test]
+fn palette_specs_auto_increment_on_data_writes() {
+    for (spec, data) in [(BCPS_ADDR, BCPD_ADDR), (OCPS_ADDR, OCPD_ADDR)] {
+        let mut bus = cgb_lcd_off();
+
+        // Bit 6 reads as 1, the index wraps from 0x3F to 0x00
+        bus.write(spec, 0x80 | 0x3E);
+        assert_eq!(bus.read(spec), 0xFE);
+        for value in [0x11, 0x22, 0x33] {
+            bus.write(data, value);
+        }
+        assert_eq!(bus.read(spec), 0xC1);
+
+        // Reads do not increment
+        bus.write(spec, 0x3E);
+        assert_eq!(bus.read(data), 0x11);
+        assert_eq!(bus.read(data), 0x11);
+        assert_eq!(bus.read(spec), 0x7E);
+
+        // Without bit 7 writes stay on the same byte
+        bus.write(spec, 0x00);
+        bus.write(data, 0x44);
+        bus.write(data, 0x55);
+        assert_eq!(bus.read(spec), 0x40);
+        assert_eq!(bus.read(data), 0x55);
+        bus.write(spec, 0x3F);
+        assert_eq!(bus.read(data), 0x22);
+    }
+}
+
+#[test]
+fn palette_ram_is_blocked_during_the_transfer() {
+    let mut bus = bus_with(Model::Cgb);
+    bus.write(OCPS_ADDR, 0x80);
+    while bus.ppu().mode() != Mode::Transfer {
+        bus.tick();
+    }
+
+    // Writes are lost and reads return 0xFF, but the index still moves on
+    bus.write(OCPD_ADDR, 0x12);
+    assert_eq!(bus.read(OCPD_ADDR), 0xFF);
+    assert_eq!(bus.read(OCPS_ADDR), 0xC1);
+
+    while bus.ppu().mode() != Mode::HBlank {
+        bus.tick();
+    }
+    bus.write(OCPS_ADDR, 0x00);
+    assert_eq!(bus.read(OCPD_ADDR), 0x00);
+    bus.write(OCPD_ADDR, 0x34);
+    assert_eq!(bus.read(OCPD_ADDR), 0x34);
+}
+
+#[test]
+fn vbk_switches_the_vram_bank() {
+    let mut bus = cgb_lcd_off();
+    assert_eq!(bus.read(VBK_ADDR), 0xFE);
+    bus.write(0x8000, 0x11);
+    bus.write(0x9FFF, 0x33);
+
+    // Only bit 0 selects the bank, the others read as 1
+    bus.write(VBK_ADDR, 0xFF);
+    assert_eq!(bus.read(VBK_ADDR), 0xFF);
+    assert_eq!(bus.read(0x8000), 0x00);
+    bus.write(0x8000, 0x22);
+    assert_eq!(bus.read(0x8000), 0x22);
+
+    bus.write(VBK_ADDR, 0x02);
+    assert_eq!(bus.read(VBK_ADDR), 0xFE);
+    assert_eq!(bus.read(0x8000), 0x11);
+    assert_eq!(bus.read(0x9FFF), 0x33);
+
+    // VBK does not exist on a DMG or in compatibility mode
+    for model in [Model::Dmg, Model::CgbCompatibility] {
+        let mut other = bus_with(model);
+        other.write(LCDC_ADDR, 0x00);
+        other.write(0x8000, 0x11);
+        other.write(VBK_ADDR, 0x01);
+        assert_eq!(other.read(VBK_ADDR), 0xFF);
+        assert_eq!(other.read(0x8000), 0x11);
+    }
+}
+
+#[test]
+fn svbk_maps_banks_1_to_7_and_0_selects_1() {
+    let mut bus = bus_with(Model::Cgb);
+    assert_eq!(bus.read(SVBK_ADDR), 0xF9);
+    bus.write(0xC000, 0xAA);
+
+    for bank in 1..8 {
+        bus.write(SVBK_ADDR, bank);
+        bus.write(0xD000, bank * 0x11);
+    }
+    for bank in 1..8 {
+        bus.write(SVBK_ADDR, bank);
+        assert_eq!(bus.read(0xD000), bank * 0x11);
+        assert_eq!(bus.read(SVBK_ADDR), 0xF8 | bank);
+    }
+
+    bus.write(SVBK_ADDR, 0x00);
+    assert_eq!(bus.read(SVBK_ADDR), 0xF9);
+    assert_eq!(bus.read(0xD000), 0x11);
+    // Bank 0 stays at 0xC000-0xCFFF, only the lower 3 bits are used
+    bus.write(SVBK_ADDR, 0xF8);
+    assert_eq!(bus.read(0xD000), 0x11);
+    assert_eq!(bus.read(0xC000), 0xAA);
+    bus.write(SVBK_ADDR, 0x0A);
+    assert_eq!(bus.read(0xD000), 0x22);
+
+    // A DMG has a single bank there
+    let mut dmg = bus_with(Model::Dmg);
+    dmg.write(0xD000, 0x11);
+    dmg.write(SVBK_ADDR, 0x02);
+    assert_eq!(dmg.read(SVBK_ADDR), 0xFF);
+    assert_eq!(dmg.read(0xD000), 0x11);
+}
+
+fn cartridge(cgb_flag: u8) -> Cartridge {
+    let mut rom = vec![0; 0x8000];
+    rom[0x143] = cgb_flag;
+    Cartridge::from_bytes(rom).unwrap()
+}
+
+#[test]
+fn cgb_for_picks_compatibility_mode_for_dmg_games() {
+    assert_eq!(Model::cgb_for(&cartridge(0x00)), Model::CgbCompatibility);
+    assert_eq!(Model::cgb_for(&cartridge(0x80)), Model::Cgb);
+    assert_eq!(Model::cgb_for(&cartridge(0xC0)), Model::Cgb);
+    assert_eq!(Model::for_cartridge(&cartridge(0x00)), Model::Dmg);
+    assert_eq!(Model::for_cartridge(&cartridge(0x80)), Model::Cgb);
+}
+
+/**
+ * The top left pixels of the first frame: the background shows colors 0 to 3 from left to right,
+ * with a sprite of color 1 using OBP0 at (8, 0) and one using OBP1 at (16, 0).
+ */
+fn first_frame(model: Model, bgp: u8) -> Vec<u32> {
+    let mut bus = Bus::new(&cartridge(0x00), model).unwrap();
+    bus.write(LCDC_ADDR, 0x00);
+    for row in 0..8 {
+        bus.write(0x8000 + row * 2, 0x55);
+        bus.write(0x8001 + row * 2, 0x33);
+        bus.write(0x8010 + row * 2, 0xFF);
+    }
+    for (index, (x, attributes)) in [(16, 0x00), (24, 0x10)].into_iter().enumerate() {
+        let addr = 0xFE00 + index as u16 * 4;
+        bus.write(addr, 16);
+        bus.write(addr + 1, x);
+        bus.write(addr + 2, 1);
+        bus.write(addr + 3, attributes);
+    }
+    bus.write(BGP_ADDR, bgp);
+    bus.write(OBP0_ADDR, 0xE4);
+    bus.write(OBP1_ADDR, 0xE4);
+    bus.write(LCDC_ADDR, 0x93);
+
+    while !bus.ppu_mut().take_frame() {
+        bus.tick();
+    }
+    bus.ppu().framebuffer()[..SCREEN_WIDTH].to_vec()
+}
+
+#[test]
+fn compatibility_mode_colors_dmg_games_with_the_boot_rom_palettes() {
+    let model = Model::cgb_for(&cartridge(0x00));
+
+    // 0x7FFF, 0x1BEF, 0x6180 and 0x0000 with the channels scaled to 8 bits
+    let line = first_frame(model, 0xE4);
+    assert_eq!(line[..4], [0xFFFFFF, 0x7BFF31, 0x0063C6, 0x000000]);
+    // BGP still maps the colors to the palette
+    assert_eq!(first_frame(model, 0x1B)[..4], [0x000000, 0x0063C6, 0x7BFF31, 0xFFFFFF]);
+
+    // Both sprite palettes are 0x7FFF, 0x421F, 0x1CF2 and 0x0000
+    assert_eq!(line[8..16], [0xFF8484; 8]);
+    assert_eq!(line[16..24], [0xFF8484; 8]);
+
+    // The compatibility palettes are not accessible
+    let mut bus = Bus::new(&cartridge(0x00), model).unwrap();
+    bus.write(LCDC_ADDR, 0x00);
+    assert_eq!(bus.read(BCPS_ADDR), 0xFF);
+    assert_eq!(bus.read(BCPD_ADDR), 0xFF);
+
+    // A DMG shows the same frame in shades of gray
+    let line = first_frame(Model::Dmg, 0xE4);
+    assert_eq!(line[..4], DMG_COLORS);
+    assert_eq!(line[8..16], [DMG_COLORS[1]; 8]);
+}
+
+/// Machine cycles until LY changes, with the counter advance of the timer over them
+fn line_cycles(bus: &mut Bus) -> (u32, u16) {
+    let ly = bus.read(LY_ADDR);
+    let counter = bus.timer().counter();
+    let mut cycles = 0;
+    while bus.read(LY_ADDR) == ly {
+        bus.tick();
+        cycles += 1;
+    }
+    (cycles, bus.timer().counter().wrapping_sub(counter))
+}
+
+#[test]
+fn double_speed_runs_the_timer_twice_as_fast_as_the_ppu() {
+    // LD A, 1; LDH [KEY1], A; STOP; NOP
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x107].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x4D, 0x10, 0x00, 0x00]);
+    let mut bus = Bus::with_model(Box::new(RomOnly::new(rom, vec![])), Model::Cgb);
+    let mut cpu = Cpu::new();
+
+    // Lines take 114 machine cycles at normal speed
+    line_cycles(&mut bus);
+    let (cycles, normal) = line_cycles(&mut bus);
+    assert_eq!(cycles, 114);
+    assert_eq!(bus.read(KEY1_ADDR), 0x7E);
+
+    cpu.step(&mut bus);
+    cpu.step(&mut bus);
+    assert_eq!(bus.read(KEY1_ADDR), 0x7F);
+    cpu.step(&mut bus);
+    assert!(bus.double_speed());
+    assert_eq!(bus.read(KEY1_ADDR), 0xFE);
+    assert_eq!(bus.read(DIV_ADDR), 0);
+
+    // The CPU is paused while the clock settles, the rest keeps running
+    for _ in 0..2050 {
+        assert_eq!(cpu.step(&mut bus), 1);
+        assert_eq!(cpu.regs.pc, 0x106);
+    }
+    assert_eq!(bus.read(DIV_ADDR), (2050 * 4 / 256) as u8);
+    cpu.step(&mut bus);
+    assert_eq!(cpu.regs.pc, 0x107);
+
+    // Twice as many machine cycles fit in a line, DIV advances the same per cycle so twice as much per line
+    line_cycles(&mut bus);
+    let (cycles, double) = line_cycles(&mut bus);
+    assert_eq!(cycles, 228);
+    assert_eq!(double, 2 * normal);
+}
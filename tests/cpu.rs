@@ -0,0 +1,131 @@
+use emu::cpu::{Cpu, MemoryBus, FLAG_C, FLAG_H, FLAG_N, FLAG_Z};
+
+/// A flat 64 KiB address space counting the machine cycles it is ticked for
+struct Memory {
+    data: Vec<u8>,
+    cycles: u32
+}
+
+impl MemoryBus for Memory {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.data[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.data[addr as usize] = value;
+    }
+
+    fn tick(&mut self) {
+        self.cycles += 1;
+    }
+}
+
+/// Loads a program at the entry point, where the CPU starts
+fn load(program: &[u8]) -> (Cpu, Memory) {
+    let mut data = vec![0; 0x10000];
+    data[0x100..0x100 + program.len()].copy_from_slice(program);
+    (Cpu::new(), Memory { data, cycles: 0 })
+}
+
+/// Runs a number of steps, returning the cycles each one took
+fn run(cpu: &mut Cpu, memory: &mut Memory, steps: usize) -> Vec<u32> {
+    (0..steps).map(|_| {
+        let before = memory.cycles;
+        let cycles = cpu.step(memory);
+        assert_eq!(memory.cycles - before, cycles, "the bus was not ticked once per cycle");
+        cycles
+    }).collect()
+}
+
+/// A and the flags after `LD A, a`, then the ALU instruction with `b`, then DAA
+fn daa(a: u8, alu: u8, b: u8) -> (u8, u8) {
+    let (mut cpu, mut memory) = load(&[0x3E, a, alu, b, 0x27]);
+    run(&mut cpu, &mut memory, 3);
+    (cpu.regs.a, cpu.regs.f)
+}
+
+const ADD_N: u8 = 0xC6;
+const SUB_N: u8 = 0xD6;
+
+#[test]
+fn daa_after_add() {
+    assert_eq!(daa(0x15, ADD_N, 0x27), (0x42, 0));
+    assert_eq!(daa(0x09, ADD_N, 0x08), (0x17, 0));
+    // 99 + 1 wraps to 00 with a carry
+    assert_eq!(daa(0x99, ADD_N, 0x01), (0x00, FLAG_Z | FLAG_C));
+    // A carry out of ADD is kept: 90 + 90 = 180
+    assert_eq!(daa(0x90, ADD_N, 0x90), (0x80, FLAG_C));
+}
+
+#[test]
+fn daa_after_sub() {
+    // 10 - 1 = 09, N stays set
+    assert_eq!(daa(0x10, SUB_N, 0x01), (0x09, FLAG_N));
+    // 0 - 1 borrows: 99 with carry
+    assert_eq!(daa(0x00, SUB_N, 0x01), (0x99, FLAG_N | FLAG_C));
+    assert_eq!(daa(0x42, SUB_N, 0x42), (0x00, FLAG_Z | FLAG_N));
+}
+
+#[test]
+fn add_sp_flags_come_from_the_low_byte() {
+    let cases: [(u16, i8, u16, u8); 6] = [
+        // SP, offset, result, flags
+        (0xFFF8, 8, 0x0000, FLAG_H | FLAG_C),
+        (0x00FF, 1, 0x0100, FLAG_H | FLAG_C),
+        (0x000F, 1, 0x0010, FLAG_H),
+        (0x00F0, 16, 0x0100, FLAG_C),
+        // Negative offsets are added as their unsigned byte
+        (0x1000, -1, 0x0FFF, 0),
+        (0x1001, -1, 0x1000, FLAG_H | FLAG_C)
+    ];
+
+    for (sp, offset, result, flags) in cases {
+        let [low, high] = sp.to_le_bytes();
+
+        // LD SP, nn; ADD SP, e
+        let (mut cpu, mut memory) = load(&[0x31, low, high, 0xE8, offset as u8]);
+        let cycles = run(&mut cpu, &mut memory, 2);
+        assert_eq!((cpu.regs.sp, cpu.regs.f), (result, flags), "ADD SP {:04X} + {}", sp, offset);
+        assert_eq!(cycles[1], 4);
+
+        // LD SP, nn; LD HL, SP+e
+        let (mut cpu, mut memory) = load(&[0x31, low, high, 0xF8, offset as u8]);
+        let cycles = run(&mut cpu, &mut memory, 2);
+        assert_eq!((cpu.regs.hl(), cpu.regs.sp, cpu.regs.f), (result, sp, flags), "LD HL, SP {:04X} + {}", sp, offset);
+        assert_eq!(cycles[1], 3);
+    }
+}
+
+#[test]
+fn conditional_branches_take_longer_when_taken() {
+    // XOR A sets Z, so every NZ branch is not taken and every Z branch is
+    let (mut cpu, mut memory) = load(&[
+        0xAF,               // 0x100 XOR A
+        0x20, 0xFE,         // 0x101 JR NZ, $0101
+        0x28, 0x00,         // 0x103 JR Z, $0105
+        0xC2, 0x00, 0x00,   // 0x105 JP NZ, $0000
+        0xCA, 0x0B, 0x01,   // 0x108 JP Z, $010B
+        0xC4, 0x00, 0x00,   // 0x10B CALL NZ, $0000
+        0xCC, 0x12, 0x01,   // 0x10E CALL Z, $0112
+        0x76,               // 0x111 HALT
+        0xC0,               // 0x112 RET NZ
+        0xC8                // 0x113 RET Z
+    ]);
+    let cycles = run(&mut cpu, &mut memory, 9);
+    assert_eq!(cycles, [1, 2, 3, 3, 4, 3, 6, 2, 5]);
+    assert_eq!(cpu.regs.pc, 0x111);
+
+    // Unconditional forms always take the longer time, JP HL excepted
+    let (mut cpu, mut memory) = load(&[
+        0x21, 0x04, 0x01,   // 0x100 LD HL, $0104
+        0xE9,               // 0x103 JP HL
+        0x18, 0x00,         // 0x104 JR $0106
+        0xC3, 0x09, 0x01,   // 0x106 JP $0109
+        0xCD, 0x0D, 0x01,   // 0x109 CALL $010D
+        0x76,               // 0x10C HALT
+        0xC9                // 0x10D RET
+    ]);
+    let cycles = run(&mut cpu, &mut memory, 6);
+    assert_eq!(cycles, [3, 1, 3, 4, 6, 4]);
+    assert_eq!(cpu.regs.pc, 0x10C);
+}
@@ -0,0 +1,194 @@
+use emu::asm::{assemble, Program};
+use emu::cartridge::Cartridge;
+use emu::disasm::Trace;
+
+const TRACED: &str = "
+SECTION \"Rst00\", ROM0[$00]
+    ret
+SECTION \"Rst08\", ROM0[$08]
+    jp Helper
+SECTION \"Rst10\", ROM0[$10]
+    ret
+SECTION \"Rst18\", ROM0[$18]
+    ret
+SECTION \"Rst20\", ROM0[$20]
+    ret
+SECTION \"Rst28\", ROM0[$28]
+    ret
+SECTION \"Rst30\", ROM0[$30]
+    ret
+SECTION \"Rst38\", ROM0[$38]
+    ret
+SECTION \"VBlank\", ROM0[$40]
+    jp VBlank
+SECTION \"Stat\", ROM0[$48]
+    reti
+SECTION \"Timer\", ROM0[$50]
+    reti
+SECTION \"Serial\", ROM0[$58]
+    reti
+SECTION \"Joypad\", ROM0[$60]
+    reti
+
+SECTION \"Entry\", ROM0[$100]
+    nop
+    jp Main
+
+SECTION \"Main\", ROM0[$150]
+Main:
+    call Sub
+    jr nz, .skip
+    rst $08
+.skip:
+    ld a, 2
+    ld [$2000], a
+    jp Far
+Table:
+    db $12, $34, $56
+Sub:
+    ld a, 1
+    and a
+    ret
+Helper:
+    ret
+VBlank:
+    reti
+Dead:
+    ld b, c
+    jp Dead
+
+SECTION \"Bank1\", ROMX[$4000], BANK[1]
+    db $AA, $BB
+
+SECTION \"Bank2\", ROMX[$4000], BANK[2]
+Far:
+    jr Far
+";
+
+fn cartridge(program: &Program) -> Cartridge {
+    Cartridge::from_bytes_lenient(program.rom.clone()).unwrap()
+}
+
+fn symbol(program: &Program, name: &str) -> usize {
+    program.symbol(name).unwrap_or_else(|| panic!("no symbol {}", name)) as usize
+}
+
+#[test]
+fn trace_follows_control_flow_from_every_entry_point() {
+    let program = assemble(TRACED).unwrap();
+    let trace = Trace::new(&cartridge(&program));
+
+    // The entry point, the RSTs and the interrupt vectors
+    for offset in [0x100, 0x101, 0x00, 0x08, 0x10, 0x38, 0x40, 0x48, 0x60] {
+        assert!(trace.is_instruction(offset), "{:#06X} is not an instruction", offset);
+    }
+    // Reached through JP, CALL, JR and RST
+    for name in ["Main", "Main.skip", "Sub", "Helper", "VBlank"] {
+        assert!(trace.is_instruction(symbol(&program, name)), "{} is not an instruction", name);
+        assert!(trace.targets().contains(&symbol(&program, name)), "{} is not a target", name);
+    }
+    // Execution continues after a CALL and a conditional JR, but not after a RET (0x01-0x07 below)
+    assert!(trace.is_instruction(symbol(&program, "Main") + 3));
+    assert!(trace.is_instruction(symbol(&program, "Main") + 5));
+    assert!(trace.targets().contains(&0x08));
+
+    // The far jump follows the bank switched in just before it
+    assert!(trace.is_instruction(2 * 0x4000));
+    assert!(trace.targets().contains(&(2 * 0x4000)));
+    assert!(!trace.is_code(0x4000));
+}
+
+#[test]
+fn trace_leaves_unreachable_bytes_as_data() {
+    let program = assemble(TRACED).unwrap();
+    let trace = Trace::new(&cartridge(&program));
+
+    let table = symbol(&program, "Table");
+    for offset in (table..table + 3).chain(0x01..0x08).chain(0x104..0x150) {
+        assert!(!trace.is_code(offset), "{:#06X} is code", offset);
+    }
+    // Nothing jumps to Dead, the loop is only reachable from itself
+    assert!(!trace.is_code(symbol(&program, "Dead")));
+
+    let cart = cartridge(&program);
+    let listing = trace.listing(&cart, 0);
+    let line = |addr: usize| listing.iter().find(|line| line.addr as usize == addr).unwrap();
+
+    assert!(line(table).instruction.is_none());
+    assert_eq!(line(table).mnemonic, "db $12, $34, $56");
+    assert_eq!(line(0x01).mnemonic, "db $00, $00, $00, $00, $00, $00, $00");
+    assert_eq!(line(0x100).mnemonic, "NOP");
+    assert!(line(symbol(&program, "Sub")).instruction.is_some());
+
+    let dead = line(symbol(&program, "Dead"));
+    assert!(dead.instruction.is_none());
+    assert!(dead.mnemonic.starts_with("db $41, $C3"));
+
+    assert_eq!(trace.listing(&cart, 1)[0].mnemonic, "db $AA, $BB, $00, $00, $00, $00, $00, $00");
+    assert_eq!(trace.listing(&cart, 2)[0].mnemonic, "JR $4000");
+}
+
+#[test]
+fn rgbds_export_reassembles_to_the_same_rom() {
+    let program = assemble("
+SECTION \"Vectors\", ROM0[$40]
+    jp VBlank
+
+SECTION \"Entry\", ROM0[$100]
+    nop
+    jp Main
+
+SECTION \"Main\", ROM0[$150]
+Main:
+    di
+    ld sp, $FFFE
+    ldh a, [$FF44]
+    ldh [$FF80], a
+    ld [$FF40], a
+    ld a, [$FF0F]
+    ld [c], a
+    ld a, [c]
+    ld hl, Table
+    ld a, [hl+]
+    ld [$C000], a
+    ld hl, sp+-2
+    add sp, 4
+    bit 7, h
+    set 0, [hl]
+    ei
+.wait:
+    halt
+    ld a, 1
+    ld [$2000], a
+    call $4000
+    jr nz, .wait
+    stop
+    jr .wait
+Table:
+    db $01, $02, $10, $CB, $76, $FF
+    dw $1234
+VBlank:
+    push af
+    pop af
+    reti
+
+SECTION \"Bank1\", ROMX[$4000], BANK[1]
+Far:
+    ld a, [$4010]
+    rst $38
+    ret
+    db $DD, $E3
+").unwrap();
+    let cart = cartridge(&program);
+
+    let source = emu::disasm::export_rgbds(&cart, &Trace::new(&cart));
+    // The instructions which older rgbasm versions assemble differently are kept as bytes
+    assert!(source.contains("db $76 ; halt"));
+    assert!(source.contains("db $EA, $40, $FF ; ld [$FF40], a"));
+    assert!(source.contains("ldh a, [$FF44]"));
+
+    let reassembled = assemble(&source).unwrap_or_else(|e| panic!("{}\n{}", e, source));
+    assert_eq!(reassembled.rom.len(), program.rom.len());
+    let diff = reassembled.rom.iter().zip(&program.rom).position(|(a, b)| a != b);
+    assert_eq!(diff, None, "the ROMs differ at {:#06X}", diff.unwrap_or(0));
+}
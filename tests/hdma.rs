@@ -0,0 +1,168 @@
+use emu::bus::{Bus, KEY1_ADDR};
+use emu::cpu::MemoryBus;
+use emu::hdma::{HDMA1_ADDR, HDMA2_ADDR, HDMA3_ADDR, HDMA4_ADDR, HDMA5_ADDR};
+use emu::mbc::RomOnly;
+use emu::model::Model;
+use emu::ppu::{Mode, LCDC_ADDR, LY_ADDR};
+
+/// A CGB bus with 0x80 bytes counting up from 1 at 0xC000
+fn bus() -> Bus {
+    let mut bus = Bus::with_model(Box::new(RomOnly::new(vec![0; 0x8000], vec![])), Model::Cgb);
+    for offset in 0..0x80 {
+        bus.write(0xC000 + offset, offset as u8 + 1);
+    }
+    bus
+}
+
+fn set_addresses(bus: &mut Bus, source: u16, destination: u16) {
+    bus.write(HDMA1_ADDR, (source >> 8) as u8);
+    bus.write(HDMA2_ADDR, source as u8);
+    bus.write(HDMA3_ADDR, (destination >> 8) as u8);
+    bus.write(HDMA4_ADDR, destination as u8);
+}
+
+fn vram(bus: &mut Bus, addr: u16, len: u16) -> Vec<u8> {
+    (addr..addr + len).map(|addr| bus.read(addr)).collect()
+}
+
+fn source(offset: u8, len: u8) -> Vec<u8> {
+    (offset..offset + len).map(|offset| offset + 1).collect()
+}
+
+/// Ticks the bus once, returning the machine cycles it took from the timer counter
+fn timed_tick(bus: &mut Bus) -> u16 {
+    let counter = bus.timer().counter();
+    bus.tick();
+    bus.timer().counter().wrapping_sub(counter) / 4
+}
+
+#[test]
+fn general_purpose_dma_copies_everything_at_once() {
+    let mut bus = bus();
+    bus.write(LCDC_ADDR, 0x00);
+    set_addresses(&mut bus, 0xC000, 0x8100);
+    // 4 blocks of 16 bytes
+    bus.write(HDMA5_ADDR, 0x03);
+
+    // The CPU is stalled for 8 machine cycles per block after the one which started it
+    assert_eq!(timed_tick(&mut bus), 1 + 4 * 8);
+    assert_eq!(vram(&mut bus, 0x8100, 0x40), source(0, 0x40));
+    assert_eq!(vram(&mut bus, 0x8140, 0x10), [0; 0x10]);
+    // Done, bit 7 is set and the length reads 0x7F
+    assert_eq!(bus.read(HDMA5_ADDR), 0xFF);
+    assert_eq!(timed_tick(&mut bus), 1);
+}
+
+#[test]
+fn general_purpose_dma_takes_twice_as_many_cycles_in_double_speed() {
+    let mut bus = bus();
+    bus.write(KEY1_ADDR, 0x01);
+    assert!(bus.stop());
+    bus.write(LCDC_ADDR, 0x00);
+
+    set_addresses(&mut bus, 0xC000, 0x8000);
+    bus.write(HDMA5_ADDR, 0x01);
+    assert_eq!(timed_tick(&mut bus), 1 + 2 * 16);
+    assert_eq!(vram(&mut bus, 0x8000, 0x20), source(0, 0x20));
+}
+
+/// Ticks until the PPU enters HBlank, which copies the next block of an HBlank DMA
+fn next_hblank(bus: &mut Bus) {
+    while bus.ppu().mode() == Mode::HBlank {
+        bus.tick();
+    }
+    while bus.ppu().mode() != Mode::HBlank {
+        bus.tick();
+    }
+}
+
+#[test]
+fn hblank_dma_copies_a_block_per_hblank() {
+    let mut bus = bus();
+    set_addresses(&mut bus, 0xC000, 0x8000);
+    bus.write(HDMA5_ADDR, 0x80 | 0x02);
+
+    // Bit 7 is cleared while the transfer is running, the length counts the blocks left minus 1
+    assert_eq!(bus.read(HDMA5_ADDR), 0x02);
+    bus.tick();
+    assert_eq!(bus.read(HDMA5_ADDR), 0x02);
+
+    for blocks in 1..=3 {
+        next_hblank(&mut bus);
+        assert_eq!(bus.read(LY_ADDR), blocks - 1);
+        let copied = blocks * 0x10;
+        assert_eq!(vram(&mut bus, 0x8000, 0x40), [source(0, copied), vec![0; 0x40 - copied as usize]].concat());
+        let left = 3 - blocks;
+        assert_eq!(bus.read(HDMA5_ADDR), if left > 0 { left - 1 } else { 0xFF });
+    }
+
+    next_hblank(&mut bus);
+    assert_eq!(vram(&mut bus, 0x8030, 0x10), [0; 0x10]);
+}
+
+#[test]
+fn hblank_dma_waits_for_the_visible_lines() {
+    let mut bus = bus();
+    while bus.read(LY_ADDR) != 144 {
+        bus.tick();
+    }
+    set_addresses(&mut bus, 0xC000, 0x8000);
+    bus.write(HDMA5_ADDR, 0x80);
+
+    while bus.read(LY_ADDR) != 0 {
+        bus.tick();
+    }
+    assert_eq!(bus.read(HDMA5_ADDR), 0x00);
+    assert_eq!(vram(&mut bus, 0x8000, 0x10), [0; 0x10]);
+
+    next_hblank(&mut bus);
+    assert_eq!(vram(&mut bus, 0x8000, 0x10), source(0, 0x10));
+    assert_eq!(bus.read(HDMA5_ADDR), 0xFF);
+}
+
+#[test]
+fn hblank_dma_is_cancelled_by_clearing_bit_7() {
+    let mut bus = bus();
+    set_addresses(&mut bus, 0xC000, 0x8000);
+    bus.write(HDMA5_ADDR, 0x80 | 0x03);
+    next_hblank(&mut bus);
+
+    // Bit 7 is set again and the length of what is left is kept
+    bus.write(HDMA5_ADDR, 0x00);
+    assert_eq!(bus.read(HDMA5_ADDR), 0x80 | 0x02);
+    next_hblank(&mut bus);
+    next_hblank(&mut bus);
+    assert_eq!(vram(&mut bus, 0x8000, 0x20), [source(0, 0x10), vec![0; 0x10]].concat());
+
+    // Writing it again starts a new transfer, general purpose with bit 7 cleared
+    bus.write(LCDC_ADDR, 0x00);
+    bus.write(HDMA5_ADDR, 0x00);
+    assert_eq!(timed_tick(&mut bus), 1 + 8);
+    assert_eq!(vram(&mut bus, 0x8010, 0x10), source(0x10, 0x10));
+}
+
+#[test]
+fn addresses_are_masked_to_blocks_in_vram() {
+    let mut bus = bus();
+    bus.write(LCDC_ADDR, 0x00);
+
+    // The lower 4 bits of both are ignored, the destination keeps only 0x1FF0 and wraps within VRAM
+    set_addresses(&mut bus, 0xC01F, 0xFFFF);
+    bus.write(HDMA5_ADDR, 0x01);
+    bus.tick();
+    assert_eq!(vram(&mut bus, 0x9FF0, 0x10), source(0x10, 0x10));
+    assert_eq!(vram(&mut bus, 0x8000, 0x10), source(0x20, 0x10));
+
+    // HDMA1-HDMA4 are write only
+    for addr in HDMA1_ADDR..HDMA5_ADDR {
+        assert_eq!(bus.read(addr), 0xFF);
+    }
+
+    // And do not exist on a DMG
+    let mut dmg = Bus::with_mapper(Box::new(RomOnly::new(vec![0; 0x8000], vec![])));
+    dmg.write(LCDC_ADDR, 0x00);
+    set_addresses(&mut dmg, 0x0000, 0x8000);
+    dmg.write(HDMA5_ADDR, 0x00);
+    assert_eq!(dmg.read(HDMA5_ADDR), 0xFF);
+    assert_eq!(timed_tick(&mut dmg), 1);
+}
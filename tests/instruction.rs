@@ -0,0 +1,112 @@
+use emu::cartridge::Cartridge;
+use emu::disasm;
+use emu::instruction::{self, Condition, Memory, Operand, Operation, Register, RegisterPair};
+
+// The opcodes removed from the SM83
+const ILLEGAL: [u8; 11] = [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD];
+
+#[test]
+fn decodes_every_length_class() {
+    // Single byte
+    let nop = instruction::decode(&[0x00]).unwrap();
+    assert_eq!((nop.operation, nop.length, nop.operands()), (Operation::Nop, 1, &[][..]));
+
+    // Immediate byte, relative offset and high page address
+    let ld = instruction::decode(&[0x06, 0x42]).unwrap();
+    assert_eq!(ld.operands(), [Operand::Register(Register::B), Operand::Immediate8(0x42)]);
+    let jr = instruction::decode(&[0x20, 0xFE]).unwrap();
+    assert_eq!(jr.operands(), [Operand::Condition(Condition::NZ), Operand::Offset(-2)]);
+    assert_eq!(jr.target(0x150), Some(0x150));
+    let ldh = instruction::decode(&[0xE0, 0x44]).unwrap();
+    assert_eq!(ldh.operands(), [Operand::Memory(Memory::HighAddress(0x44)), Operand::Register(Register::A)]);
+    assert_eq!(ldh.mnemonic(), "LDH");
+
+    // Immediate word, little-endian
+    let jp = instruction::decode(&[0xC3, 0x34, 0x12]).unwrap();
+    assert_eq!((jp.length, jp.operands()), (3, &[Operand::Immediate16(0x1234)][..]));
+    let store = instruction::decode(&[0xEA, 0x00, 0xC0]).unwrap();
+    assert_eq!(store.operands(), [Operand::Memory(Memory::Address(0xC000)), Operand::Register(Register::A)]);
+
+    // 0xCB prefix
+    let bit = instruction::decode(&[0xCB, 0x7C]).unwrap();
+    assert_eq!((bit.operation, bit.length), (Operation::Bit, 2));
+    assert_eq!(bit.operands(), [Operand::Bit(7), Operand::Register(Register::H)]);
+    let swap = instruction::decode(&[0xCB, 0x36]).unwrap();
+    assert_eq!((swap.operation, swap.operands()), (Operation::Swap, &[Operand::Memory(Memory::HL)][..]));
+
+    // STOP is followed by a byte which is skipped
+    let stop = instruction::decode(&[0x10, 0x00]).unwrap();
+    assert_eq!((stop.operation, stop.length), (Operation::Stop, 2));
+
+    let ld_hl = instruction::decode(&[0xF8, 0x80]).unwrap();
+    assert_eq!(ld_hl.operands(), [Operand::RegisterPair(RegisterPair::HL), Operand::SpOffset(-128)]);
+}
+
+#[test]
+fn short_input_is_not_decoded() {
+    assert_eq!(instruction::decode(&[]), None);
+    assert_eq!(instruction::decode(&[0xCB]), None);
+    assert_eq!(instruction::decode(&[0x06]), None);
+    assert_eq!(instruction::decode(&[0x10]), None);
+    assert_eq!(instruction::decode(&[0xC3, 0x34]), None);
+    assert_eq!(instruction::decode(&[0xFA]), None);
+
+    // Bytes after the instruction are ignored
+    assert_eq!(instruction::decode(&[0x00, 0xC3]), instruction::decode(&[0x00]));
+    assert_eq!(instruction::decode(&[0xCB, 0x11, 0xFF]).unwrap().length, 2);
+}
+
+#[test]
+fn every_opcode_decodes_to_its_length() {
+    for opcode in (0..=0xFF).filter(|&opcode| opcode != 0xCB) {
+        let instruction = instruction::decode(&[opcode, 0x00, 0x00])
+            .unwrap_or_else(|| panic!("{:#04X} was not decoded", opcode));
+
+        assert_eq!(instruction.length, instruction::length(opcode), "length of {:#04X}", opcode);
+        assert_eq!(instruction.operation == Operation::Illegal, ILLEGAL.contains(&opcode),
+            "{:#04X} decoded as {}", opcode, instruction);
+        if instruction.length > 1 {
+            assert_eq!(instruction::decode(&[opcode]), None);
+        }
+    }
+
+    for opcode in 0..=0xFF {
+        let instruction = instruction::decode(&[0xCB, opcode]).unwrap();
+        assert_eq!(instruction.length, 2);
+        assert_ne!(instruction.operation, Operation::Illegal, "CB {:02X}", opcode);
+    }
+}
+
+#[test]
+fn illegal_opcodes_are_single_bytes() {
+    for opcode in ILLEGAL {
+        let instruction = instruction::decode(&[opcode]).unwrap();
+        assert_eq!((instruction.operation, instruction.length), (Operation::Illegal, 1));
+    }
+}
+
+#[test]
+fn listings_end_with_db_for_cut_instructions() {
+    let mut rom = vec![0; 0x8000];
+    // JP nn missing its last byte at the end of the ROM
+    rom[0x7FFE] = 0xC3;
+    rom[0x7FFF] = 0x34;
+    let cart = Cartridge::from_bytes(rom).unwrap();
+
+    assert!(disasm::decode(&[0xC3, 0x34], 0x7FFE).is_none());
+
+    let lines = disasm::disassemble_bank(&cart, 1);
+    let last = lines.last().unwrap();
+    assert_eq!((last.addr, last.mnemonic.as_str()), (0x7FFE, "db"));
+    assert_eq!(last.bytes, [0xC3, 0x34]);
+    assert!(last.instruction.is_none());
+
+    // The range ends inside LD A, n, which is still decoded whole
+    let mut rom = vec![0; 0x8000];
+    rom[0x200] = 0x3E;
+    rom[0x201] = 0x42;
+    let cart = Cartridge::from_bytes(rom).unwrap();
+    let lines = disasm::disassemble_range(&cart, 0, 0x1FF, 0x201);
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1].mnemonic, "LD A, $42");
+}
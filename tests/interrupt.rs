@@ -0,0 +1,86 @@
+use emu::asm::assemble;
+use emu::cpu::{Cpu, MemoryBus};
+use emu::interrupt::{IE_ADDR, IF_ADDR};
+
+/// A flat 64 KiB address space, IE and IF included
+struct Memory {
+    data: Vec<u8>
+}
+
+impl MemoryBus for Memory {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.data[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.data[addr as usize] = value;
+    }
+}
+
+/// Assembles a program at the entry point, where the CPU starts, with the given interrupts enabled and requested
+fn load(source: &str, enable: u8, flags: u8) -> (Cpu, Memory) {
+    let program = assemble(&format!("SECTION \"Test\", ROM0[$100]\n{}", source))
+        .unwrap_or_else(|e| panic!("{}", e));
+
+    let mut data = vec![0; 0x10000];
+    data[..0x8000].copy_from_slice(&program.rom[..0x8000]);
+    data[IE_ADDR as usize] = enable;
+    data[IF_ADDR as usize] = flags;
+    (Cpu::new(), Memory { data })
+}
+
+fn run(cpu: &mut Cpu, memory: &mut Memory, steps: usize) -> Vec<u32> {
+    (0..steps).map(|_| cpu.step(memory)).collect()
+}
+
+#[test]
+fn ei_takes_effect_after_the_next_instruction() {
+    let (mut cpu, mut memory) = load("ei\ninc a\ninc a", 0x01, 0x01);
+    let a = cpu.regs.a;
+
+    run(&mut cpu, &mut memory, 2);
+    assert!(cpu.ime);
+    assert_eq!((cpu.regs.pc, cpu.regs.a), (0x102, a.wrapping_add(1)));
+
+    // The interrupt is serviced before the second INC, with its address pushed
+    let cycles = run(&mut cpu, &mut memory, 1);
+    assert_eq!((cpu.regs.pc, cycles[0]), (0x40, 5));
+    assert!(!cpu.ime);
+    assert_eq!(memory.data[IF_ADDR as usize], 0x00);
+    assert_eq!((memory.data[0xFFFD], memory.data[0xFFFC]), (0x01, 0x02));
+}
+
+#[test]
+fn ei_followed_by_di_lets_no_interrupt_through() {
+    let (mut cpu, mut memory) = load("ei\ndi\nnop\nnop", 0x01, 0x01);
+    run(&mut cpu, &mut memory, 4);
+    assert_eq!((cpu.regs.pc, cpu.ime), (0x104, false));
+    assert_eq!(memory.data[IF_ADDR as usize], 0x01);
+}
+
+#[test]
+fn halt_bug_reads_the_next_opcode_twice() {
+    // With IME clear and an interrupt pending HALT does not halt, and the INC runs twice
+    let (mut cpu, mut memory) = load("halt\ninc a\nnop", 0x04, 0x04);
+    let a = cpu.regs.a;
+
+    run(&mut cpu, &mut memory, 3);
+    assert!(!cpu.halted);
+    assert_eq!((cpu.regs.pc, cpu.regs.a), (0x102, a.wrapping_add(2)));
+}
+
+#[test]
+fn halt_waits_for_an_interrupt_request() {
+    // Without a pending interrupt it halts until one is requested, then continues after the HALT
+    let (mut cpu, mut memory) = load("halt\ninc a", 0x04, 0x00);
+    let a = cpu.regs.a;
+
+    run(&mut cpu, &mut memory, 10);
+    assert!(cpu.halted);
+    assert_eq!(cpu.regs.pc, 0x101);
+
+    memory.data[IF_ADDR as usize] = 0x04;
+    run(&mut cpu, &mut memory, 2);
+    assert!(!cpu.halted);
+    assert_eq!((cpu.regs.pc, cpu.regs.a), (0x102, a.wrapping_add(1)));
+}
@@ -0,0 +1,269 @@
+use std::{cell::RefCell, rc::Rc};
+
+use emu::mbc::{Mapper, MBC1, MBC2, MBC2_RAM_SIZE, MBC3, MBC5};
+
+const ROM_BANK_SIZE: usize = 0x4000;
+const RAM_BANK_SIZE: usize = 0x2000;
+
+/// A ROM whose banks start with their own bank number, low byte first
+fn rom(banks: usize) -> Vec<u8> {
+    let mut rom = vec![0; banks * ROM_BANK_SIZE];
+    for bank in 0..banks {
+        rom[bank * ROM_BANK_SIZE] = bank as u8;
+        rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+    }
+    rom
+}
+
+/// The number of the bank mapped at `addr`, as stored by `rom`
+fn bank_at(mapper: &impl Mapper, addr: u16) -> usize {
+    mapper.read(addr) as usize | (mapper.read(addr + 1) as usize) << 8
+}
+
+#[test]
+fn mbc1_bank1_selects_the_lower_5_bits() {
+    let mut mbc = MBC1::new(rom(128), vec![]);
+    assert_eq!(bank_at(&mbc, 0x4000), 1);
+
+    mbc.write(0x2000, 0x05);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x05);
+    mbc.write(0x3FFF, 0x1F);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x1F);
+    // Only 5 bits are connected
+    mbc.write(0x2000, 0x25);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x05);
+    assert_eq!(bank_at(&mbc, 0x0000), 0);
+}
+
+#[test]
+fn mbc1_bank_0_is_translated_to_1_before_adding_the_upper_bits() {
+    let mut mbc = MBC1::new(rom(128), vec![]);
+
+    // Banks 0x20, 0x40 and 0x60 can't be mapped at 0x4000, the next bank is mapped instead
+    for bank2 in 0..4 {
+        mbc.write(0x4000, bank2);
+        mbc.write(0x2000, 0x00);
+        assert_eq!(bank_at(&mbc, 0x4000), (bank2 as usize) << 5 | 1);
+        // Only bits 0-4 are checked for zero, not the full 7-bit bank number
+        mbc.write(0x2000, 0x20);
+        assert_eq!(bank_at(&mbc, 0x4000), (bank2 as usize) << 5 | 1);
+    }
+}
+
+#[test]
+fn mbc1_mode_0_maps_bank2_only_at_0x4000() {
+    let mut mbc = MBC1::new(rom(128), vec![]);
+    mbc.write(0x2000, 0x02);
+    mbc.write(0x4000, 0x03);
+
+    assert_eq!(bank_at(&mbc, 0x4000), 0x62);
+    assert_eq!(bank_at(&mbc, 0x0000), 0x00);
+}
+
+#[test]
+fn mbc1_mode_1_maps_bank2_at_0x0000_too() {
+    let mut mbc = MBC1::new(rom(128), vec![]);
+    mbc.write(0x2000, 0x02);
+    mbc.write(0x4000, 0x01);
+    mbc.write(0x6000, 0x01);
+
+    assert_eq!(bank_at(&mbc, 0x0000), 0x20);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x22);
+
+    mbc.write(0x4000, 0x03);
+    assert_eq!(bank_at(&mbc, 0x0000), 0x60);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x62);
+
+    mbc.write(0x6000, 0x00);
+    assert_eq!(bank_at(&mbc, 0x0000), 0x00);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x62);
+}
+
+#[test]
+fn mbc1_bank_numbers_wrap_around_smaller_roms() {
+    let mut mbc = MBC1::new(rom(8), vec![]);
+    mbc.write(0x2000, 0x0B);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x03);
+
+    // Upper bits beyond the ROM size are ignored, in mode 1 bank 0x20 wraps to bank 0
+    mbc.write(0x4000, 0x01);
+    mbc.write(0x6000, 0x01);
+    assert_eq!(bank_at(&mbc, 0x0000), 0x00);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x03);
+}
+
+#[test]
+fn mbc1_ram_needs_to_be_enabled() {
+    let mut mbc = MBC1::new(rom(4), vec![0; RAM_BANK_SIZE]);
+    mbc.write(0xA000, 0x12);
+    assert_eq!(mbc.read(0xA000), 0xFF);
+    assert_eq!(mbc.ram()[0], 0x00);
+
+    // Only the lower nibble has to be 0xA
+    mbc.write(0x0000, 0x3A);
+    mbc.write(0xA000, 0x12);
+    assert_eq!(mbc.read(0xA000), 0x12);
+
+    mbc.write(0x1FFF, 0x00);
+    assert_eq!(mbc.read(0xA000), 0xFF);
+    assert_eq!(mbc.ram()[0], 0x12);
+}
+
+#[test]
+fn mbc1_ram_banks_are_only_switched_in_mode_1() {
+    let mut mbc = MBC1::new(rom(4), vec![0; 4 * RAM_BANK_SIZE]);
+    mbc.write(0x0000, 0x0A);
+    mbc.write(0x4000, 0x02);
+    mbc.write(0xA000, 0x11);
+    assert_eq!(mbc.ram()[0], 0x11);
+
+    mbc.write(0x6000, 0x01);
+    assert_eq!(mbc.read(0xA000), 0x00);
+    mbc.write(0xBFFF, 0x22);
+    assert_eq!(mbc.ram()[2 * RAM_BANK_SIZE + 0x1FFF], 0x22);
+
+    mbc.write(0x6000, 0x00);
+    assert_eq!(mbc.read(0xA000), 0x11);
+}
+
+#[test]
+fn mbc3_rom_bank_is_7_bits_with_bank_0_translated_to_1() {
+    let mut mbc = MBC3::new(rom(128), vec![], None);
+    assert_eq!(bank_at(&mbc, 0x4000), 1);
+
+    mbc.write(0x2000, 0x00);
+    assert_eq!(bank_at(&mbc, 0x4000), 1);
+    // Unlike MBC1, banks 0x20, 0x40 and 0x60 are reachable
+    mbc.write(0x2000, 0x20);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x20);
+    mbc.write(0x3FFF, 0x7F);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x7F);
+    // Bit 7 is not connected, 0x80 selects bank 0 which is translated to 1
+    mbc.write(0x2000, 0x80);
+    assert_eq!(bank_at(&mbc, 0x4000), 1);
+    mbc.write(0x2000, 0xC5);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x45);
+    assert_eq!(bank_at(&mbc, 0x0000), 0);
+}
+
+#[test]
+fn mbc3_ram_banks_are_selected_by_0x4000() {
+    let mut mbc = MBC3::new(rom(4), vec![0; 4 * RAM_BANK_SIZE], None);
+    mbc.write(0x0000, 0x0A);
+    for bank in 0..4 {
+        mbc.write(0x4000, bank);
+        mbc.write(0xA000, 0x10 | bank);
+    }
+    for bank in 0..4 {
+        assert_eq!(mbc.ram()[bank * RAM_BANK_SIZE], 0x10 | bank as u8);
+    }
+
+    // Without a clock the RTC registers read as open bus
+    mbc.write(0x4000, 0x08);
+    assert_eq!(mbc.read(0xA000), 0xFF);
+}
+
+#[test]
+fn mbc5_rom_bank_is_9_bits_including_bank_0() {
+    let mut mbc = MBC5::new(rom(512), vec![], false);
+    assert_eq!(bank_at(&mbc, 0x4000), 1);
+
+    mbc.write(0x2000, 0x00);
+    assert_eq!(bank_at(&mbc, 0x4000), 0);
+    mbc.write(0x2FFF, 0xFF);
+    assert_eq!(bank_at(&mbc, 0x4000), 0xFF);
+
+    // The 9th bit is written separately and keeps the lower 8 bits
+    mbc.write(0x3000, 0x01);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x1FF);
+    mbc.write(0x2000, 0x00);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x100);
+    mbc.write(0x3FFF, 0xFE);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x000);
+    assert_eq!(bank_at(&mbc, 0x0000), 0);
+}
+
+#[test]
+fn mbc5_ram_bank_is_4_bits() {
+    let mut mbc = MBC5::new(rom(4), vec![0; 16 * RAM_BANK_SIZE], false);
+    mbc.write(0x0000, 0x0A);
+    mbc.write(0x4000, 0x0F);
+    mbc.write(0xA000, 0x42);
+    assert_eq!(mbc.ram()[15 * RAM_BANK_SIZE], 0x42);
+    assert!(!mbc.rumble_active());
+}
+
+#[test]
+fn mbc5_rumble_is_driven_by_ram_bank_bit_3() {
+    let mut mbc = MBC5::new(rom(4), vec![0; 8 * RAM_BANK_SIZE], true);
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let log = calls.clone();
+    mbc.set_rumble_callback(Box::new(move |active| log.borrow_mut().push(active)));
+
+    mbc.write(0x0000, 0x0A);
+    mbc.write(0x4000, 0x0B);
+    assert!(mbc.rumble_active());
+    // Bit 3 is not part of the bank number on rumble cartridges
+    mbc.write(0xA000, 0x33);
+    assert_eq!(mbc.ram()[3 * RAM_BANK_SIZE], 0x33);
+
+    // The callback only fires when the motor state changes
+    mbc.write(0x4000, 0x08);
+    mbc.write(0x4000, 0x00);
+    mbc.write(0x4000, 0x03);
+    assert!(!mbc.rumble_active());
+    assert_eq!(*calls.borrow(), vec![true, false]);
+}
+
+#[test]
+fn mbc2_registers_are_selected_by_address_bit_8() {
+    let mut mbc = MBC2::new(rom(16), vec![0; MBC2_RAM_SIZE]);
+
+    // With A8 set the write goes to the ROM bank register, not RAM enable
+    mbc.write(0x0100, 0x0A);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x0A);
+    mbc.write(0xA000, 0x05);
+    assert_eq!(mbc.read(0xA000), 0xFF);
+
+    mbc.write(0x0000, 0x0A);
+    mbc.write(0xA000, 0x05);
+    assert_eq!(mbc.read(0xA000), 0xF5);
+
+    // Both registers are repeated through 0x0000-0x3FFF
+    mbc.write(0x3FFF, 0x03);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x03);
+    mbc.write(0x3EFF, 0x00);
+    assert_eq!(mbc.read(0xA000), 0xFF);
+}
+
+#[test]
+fn mbc2_rom_bank_is_4_bits_with_bank_0_translated_to_1() {
+    let mut mbc = MBC2::new(rom(16), vec![]);
+    mbc.write(0x2100, 0x00);
+    assert_eq!(bank_at(&mbc, 0x4000), 1);
+    mbc.write(0x2100, 0x1F);
+    assert_eq!(bank_at(&mbc, 0x4000), 0x0F);
+    mbc.write(0x2100, 0x10);
+    assert_eq!(bank_at(&mbc, 0x4000), 1);
+}
+
+#[test]
+fn mbc2_ram_is_512_half_bytes_echoed_through_the_area() {
+    let mut mbc = MBC2::new(rom(2), vec![0; MBC2_RAM_SIZE]);
+    mbc.write(0x0000, 0x0A);
+
+    // Only the lower nibble is stored, the upper one reads as 1s
+    mbc.write(0xA000, 0xAB);
+    assert_eq!(mbc.ram()[0], 0x0B);
+    assert_eq!(mbc.read(0xA000), 0xFB);
+
+    mbc.write(0xA1FF, 0x07);
+    assert_eq!(mbc.ram()[0x1FF], 0x07);
+    for echo in [0xA200, 0xA400, 0xBE00] {
+        assert_eq!(mbc.read(echo), 0xFB);
+        assert_eq!(mbc.read(echo + 0x1FF), 0xF7);
+    }
+
+    mbc.write(0xBFFF, 0x0C);
+    assert_eq!(mbc.read(0xA1FF), 0xFC);
+}
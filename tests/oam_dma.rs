@@ -0,0 +1,257 @@
+use emu::asm::assemble;
+use emu::bus::Bus;
+use emu::cpu::{Cpu, MemoryBus};
+use emu::mbc::RomOnly;
+use emu::ppu::LCDC_ADDR;
+
+/**
+ * Assembles a program starting at the entry point and runs it until it halts.
+ * It starts by filling 0xC100-0xC19F with 0x00, 0x01, ... as the DMA source and copying
+ * the code between `Routine` and `RoutineEnd` to HRAM, like games do before using OAM DMA.
+ */
+fn run(source: &str) -> (Cpu, Bus) {
+    let program = assemble(&format!("
+SECTION \"Entry\", ROM0[$100]
+    ld hl, $C100
+    xor a
+.fill:
+    ld [hl+], a
+    inc a
+    cp 160
+    jr nz, .fill
+
+    ld hl, Routine
+    ld de, $FF80
+    ld b, RoutineEnd - Routine
+.copy:
+    ld a, [hl+]
+    ld [de], a
+    inc de
+    dec b
+    jr nz, .copy
+
+    ld a, $42
+    ld [$C000], a
+    ld hl, $C000
+    xor a
+    ld b, a
+{}", source)).unwrap();
+
+    let mut cpu = Cpu::new();
+    let mut bus = Bus::with_mapper(Box::new(RomOnly::new(program.rom, vec![])));
+    for _ in 0..100_000 {
+        if cpu.halted {
+            // Turn the LCD off so that OAM can be read back
+            bus.write(LCDC_ADDR, 0);
+            return (cpu, bus);
+        }
+        cpu.step(&mut bus);
+    }
+    panic!("the program did not halt");
+}
+
+fn assert_oam_copied(bus: &mut Bus) {
+    for index in 0..160 {
+        assert_eq!(bus.read(0xFE00 + index), index as u8, "OAM byte {}", index);
+    }
+}
+
+#[test]
+fn copies_160_bytes_to_oam() {
+    let (_, mut bus) = run("
+    call $FF80
+    halt
+
+Routine:
+    ld a, $C1
+    ldh [$46], a
+    ld a, 40
+.wait:
+    dec a
+    jr nz, .wait
+    ret
+RoutineEnd:
+");
+
+    assert_oam_copied(&mut bus);
+    assert_eq!(bus.read(0xFF46), 0xC1);
+}
+
+#[test]
+fn reads_on_the_dma_bus_return_the_byte_being_copied() {
+    let (cpu, mut bus) = run("
+    call $FF80
+    halt
+
+Routine:
+    ld a, $C1
+    ldh [$46], a
+    ld b, [hl]
+    nop
+    nop
+    ld c, [hl]
+    ldh a, [$80]
+    ld d, a
+    ld a, 40
+.wait:
+    dec a
+    jr nz, .wait
+    ld e, [hl]
+    ret
+RoutineEnd:
+");
+
+    // WRAM reads the bytes copied 2 and 6 cycles after the write, HRAM is not affected
+    assert_eq!(cpu.regs.b, 0x00);
+    assert_eq!(cpu.regs.c, 0x04);
+    assert_eq!(cpu.regs.d, 0x3E);
+    assert_eq!(cpu.regs.e, 0x42);
+    assert_oam_copied(&mut bus);
+}
+
+#[test]
+fn other_bus_is_accessible_during_transfer() {
+    // Copies the source to VRAM with the LCD off, then runs DMA from there while using WRAM and VRAM
+    let (cpu, mut bus) = run("
+    ldh [$40], a
+    ld hl, $C100
+    ld de, $8100
+    ld b, 160
+.vram:
+    ld a, [hl+]
+    ld [de], a
+    inc de
+    dec b
+    jr nz, .vram
+
+    ld hl, $C000
+    ld de, $9000
+    call $FF80
+    halt
+
+Routine:
+    ld a, $81
+    ldh [$46], a
+    ld b, [hl]
+    ld a, [de]
+    ld c, a
+    ld a, $55
+    ld [hl], a
+    ld [de], a
+    ld a, 40
+.wait:
+    dec a
+    jr nz, .wait
+    ret
+RoutineEnd:
+");
+
+    // WRAM is read and written normally, VRAM reads the byte copied 4 cycles after the write and ignores writes
+    assert_eq!(cpu.regs.b, 0x42);
+    assert_eq!(cpu.regs.c, 0x02);
+    assert_eq!(bus.read(0xC000), 0x55);
+    assert_eq!(bus.read(0x9000), 0x00);
+    assert_oam_copied(&mut bus);
+}
+
+#[test]
+fn rom_reads_return_the_byte_being_copied_from_wram() {
+    // The opcode after the write is fetched from ROM during the setup cycle, its read of WRAM already conflicts.
+    // From then on opcodes are fetched from the bytes being copied, NOPs but for LD B, $99 at 0x50.
+    let (cpu, mut bus) = run("
+    ld a, $77
+    ld [$C200], a
+    ld a, $06
+    ld [$C250], a
+    ld a, $99
+    ld [$C251], a
+
+    ld a, $C2
+    ldh [$46], a
+    ld c, [hl]
+    ds 200
+    halt
+
+SECTION \"Routine\", ROM0[$400]
+Routine:
+RoutineEnd:
+");
+
+    assert_eq!(cpu.regs.c, 0x77);
+    assert_eq!(cpu.regs.b, 0x99);
+    assert_eq!(bus.read(0xFE50), 0x06);
+}
+
+#[test]
+fn lasts_160_cycles() {
+    // The read of LD B, [HL] happens 4 * 39 + 3 + NOPs cycles after the write to DMA
+    let blocked = run("
+    call $FF80
+    halt
+
+Routine:
+    ld a, $C1
+    ldh [$46], a
+    ld c, 39
+.wait:
+    dec c
+    jr nz, .wait
+    nop
+    nop
+    ld b, [hl]
+    ret
+RoutineEnd:
+").0;
+    // The last byte copied
+    assert_eq!(blocked.regs.b, 0x9F);
+
+    let done = run("
+    call $FF80
+    halt
+
+Routine:
+    ld a, $C1
+    ldh [$46], a
+    ld c, 39
+.wait:
+    dec c
+    jr nz, .wait
+    nop
+    nop
+    nop
+    ld b, [hl]
+    ret
+RoutineEnd:
+").0;
+    assert_eq!(done.regs.b, 0x42);
+}
+
+#[test]
+fn restart_replaces_running_transfer() {
+    // The second transfer copies from 0xC000, which holds 0x42 and zeros
+    let (_, mut bus) = run("
+    call $FF80
+    halt
+
+Routine:
+    ld a, $C1
+    ldh [$46], a
+    ld a, 20
+.first:
+    dec a
+    jr nz, .first
+    ld a, $C0
+    ldh [$46], a
+    ld a, 40
+.second:
+    dec a
+    jr nz, .second
+    ret
+RoutineEnd:
+");
+
+    assert_eq!(bus.read(0xFE00), 0x42);
+    for index in 1..160 {
+        assert_eq!(bus.read(0xFE00 + index), 0, "OAM byte {}", index);
+    }
+}
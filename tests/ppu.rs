@@ -0,0 +1,444 @@
+use emu::bus::Bus;
+use emu::cpu::MemoryBus;
+use emu::interrupt::{Interrupt, Interrupts, IF_ADDR};
+use emu::mbc::RomOnly;
+use emu::model::Model;
+use emu::ppu::{Mode, Ppu, Renderer, BCPD_ADDR, BCPS_ADDR, BGP_ADDR, DMG_COLORS, LCDC_ADDR, LYC_ADDR, LY_ADDR,
+    OBP0_ADDR, OBP1_ADDR, OCPD_ADDR, OCPS_ADDR, SCREEN_WIDTH, SCX_ADDR, SCY_ADDR, STAT_ADDR, VBK_ADDR, WX_ADDR,
+    WY_ADDR};
+
+// 456 dots of 4 per machine cycle
+const LINE_CYCLES: usize = 114;
+// Mode 2 lasts 80 dots, mode 3 172 dots without scrolling, window or sprites
+const MODE_2_CYCLES: usize = 20;
+const MODE_3_CYCLES: usize = 43;
+
+const STAT_HBLANK_INTERRUPT: u8 = 0x08;
+const STAT_OAM_INTERRUPT: u8 = 0x20;
+const STAT_LYC_INTERRUPT: u8 = 0x40;
+
+/// A DMG bus at the start of line 0, with the LCD on and no interrupt requested
+fn bus() -> Bus {
+    let mut bus = Bus::with_mapper(Box::new(RomOnly::new(vec![0; 0x8000], vec![])));
+    bus.write(IF_ADDR, 0x00);
+    bus
+}
+
+fn run(bus: &mut Bus, cycles: usize) {
+    for _ in 0..cycles {
+        bus.tick();
+    }
+}
+
+fn mode(bus: &mut Bus) -> u8 {
+    bus.read(STAT_ADDR) & 0x03
+}
+
+fn stat_requested(bus: &mut Bus) -> bool {
+    bus.read(IF_ADDR) & Interrupt::Stat.bit() != 0
+}
+
+#[test]
+fn visible_lines_go_through_modes_2_3_and_0() {
+    let mut bus = bus();
+
+    for line in 0..3 {
+        assert_eq!(bus.read(LY_ADDR), line);
+        assert_eq!(mode(&mut bus), 2);
+        run(&mut bus, MODE_2_CYCLES - 1);
+        assert_eq!(mode(&mut bus), 2);
+        run(&mut bus, 1);
+        assert_eq!(mode(&mut bus), 3);
+        run(&mut bus, MODE_3_CYCLES - 1);
+        assert_eq!(mode(&mut bus), 3);
+        run(&mut bus, 1);
+        assert_eq!(mode(&mut bus), 0);
+        run(&mut bus, LINE_CYCLES - MODE_2_CYCLES - MODE_3_CYCLES - 1);
+        assert_eq!((bus.read(LY_ADDR), mode(&mut bus)), (line, 0));
+        run(&mut bus, 1);
+    }
+}
+
+#[test]
+fn vblank_lasts_10_lines() {
+    let mut bus = bus();
+    run(&mut bus, 144 * LINE_CYCLES - 1);
+    assert_eq!((bus.read(LY_ADDR), mode(&mut bus)), (143, 0));
+    assert_eq!(bus.read(IF_ADDR) & Interrupt::VBlank.bit(), 0);
+
+    run(&mut bus, 1);
+    assert_eq!((bus.read(LY_ADDR), mode(&mut bus)), (144, 1));
+    assert_eq!(bus.read(IF_ADDR) & Interrupt::VBlank.bit(), Interrupt::VBlank.bit());
+    // VBlank does not request the STAT interrupt unless it is enabled
+    assert!(!stat_requested(&mut bus));
+
+    run(&mut bus, 9 * LINE_CYCLES);
+    assert_eq!((bus.read(LY_ADDR), mode(&mut bus)), (153, 1));
+    run(&mut bus, LINE_CYCLES - 1);
+    assert_eq!((bus.read(LY_ADDR), mode(&mut bus)), (153, 1));
+    run(&mut bus, 1);
+    assert_eq!((bus.read(LY_ADDR), mode(&mut bus)), (0, 2));
+}
+
+#[test]
+fn stat_reads_mode_0_and_ly_0_with_the_lcd_off() {
+    let mut bus = bus();
+    run(&mut bus, 10 * LINE_CYCLES + MODE_2_CYCLES);
+    assert_eq!(bus.read(STAT_ADDR), 0x80 | 0x03);
+
+    bus.write(LCDC_ADDR, 0x00);
+    assert_eq!(bus.read(LY_ADDR), 0);
+    // Bit 7 reads as 1, LY=LYC=0 so the coincidence bit is set
+    assert_eq!(bus.read(STAT_ADDR), 0x80 | 0x04);
+    run(&mut bus, 1000);
+    assert_eq!(bus.read(LY_ADDR), 0);
+
+    // Turning it back on starts again from line 0
+    bus.write(LCDC_ADDR, 0x91);
+    assert_eq!(mode(&mut bus), 2);
+    run(&mut bus, MODE_2_CYCLES);
+    assert_eq!(mode(&mut bus), 3);
+
+    // The mode and coincidence bits are read only
+    bus.write(STAT_ADDR, 0xFF);
+    assert_eq!(bus.read(STAT_ADDR), 0xFF);
+    bus.write(STAT_ADDR, 0x00);
+    assert_eq!(bus.read(STAT_ADDR), 0x80 | 0x04 | 0x03);
+}
+
+#[test]
+fn lyc_coincidence_requests_stat() {
+    let mut bus = bus();
+    bus.write(LYC_ADDR, 5);
+    bus.write(STAT_ADDR, STAT_LYC_INTERRUPT);
+
+    run(&mut bus, 5 * LINE_CYCLES - 1);
+    assert_eq!(bus.read(LY_ADDR), 4);
+    assert_eq!(bus.read(STAT_ADDR) & 0x04, 0);
+    assert!(!stat_requested(&mut bus));
+
+    run(&mut bus, 1);
+    assert_eq!(bus.read(LY_ADDR), 5);
+    assert_eq!(bus.read(STAT_ADDR) & 0x04, 0x04);
+    assert!(stat_requested(&mut bus));
+
+    // Requested once per match, not on every cycle of the line
+    bus.write(IF_ADDR, 0x00);
+    run(&mut bus, LINE_CYCLES - 1);
+    assert!(!stat_requested(&mut bus));
+    run(&mut bus, 1);
+    assert_eq!(bus.read(STAT_ADDR) & 0x04, 0);
+
+    // Without the enable bit, the coincidence bit is still set
+    bus.write(STAT_ADDR, 0x00);
+    bus.write(LYC_ADDR, 7);
+    run(&mut bus, LINE_CYCLES);
+    assert_eq!(bus.read(STAT_ADDR) & 0x04, 0x04);
+    assert!(!stat_requested(&mut bus));
+}
+
+#[test]
+fn mode_interrupts_are_requested_on_entering_the_mode() {
+    let mut bus = bus();
+    // Enabling it in mode 2 raises the line on the next dot
+    bus.write(STAT_ADDR, STAT_OAM_INTERRUPT);
+    run(&mut bus, 1);
+    assert!(stat_requested(&mut bus));
+
+    bus.write(IF_ADDR, 0x00);
+    run(&mut bus, LINE_CYCLES - 2);
+    assert!(!stat_requested(&mut bus));
+    run(&mut bus, 1);
+    assert!(stat_requested(&mut bus));
+
+    bus.write(STAT_ADDR, STAT_HBLANK_INTERRUPT);
+    bus.write(IF_ADDR, 0x00);
+    run(&mut bus, MODE_2_CYCLES + MODE_3_CYCLES - 1);
+    assert!(!stat_requested(&mut bus));
+    run(&mut bus, 1);
+    assert!(stat_requested(&mut bus));
+}
+
+#[test]
+fn stat_interrupt_is_blocked_while_the_line_stays_high() {
+    let mut bus = bus();
+    bus.write(STAT_ADDR, STAT_HBLANK_INTERRUPT | STAT_OAM_INTERRUPT);
+
+    run(&mut bus, MODE_2_CYCLES + MODE_3_CYCLES);
+    assert!(stat_requested(&mut bus));
+
+    // HBlank is followed by mode 2 without the line dropping, so entering mode 2 requests nothing
+    bus.write(IF_ADDR, 0x00);
+    run(&mut bus, LINE_CYCLES - MODE_2_CYCLES - MODE_3_CYCLES);
+    assert_eq!((bus.read(LY_ADDR), mode(&mut bus)), (1, 2));
+    assert!(!stat_requested(&mut bus));
+
+    // The line drops in mode 3, so the next HBlank requests it again
+    run(&mut bus, MODE_2_CYCLES + MODE_3_CYCLES);
+    assert!(stat_requested(&mut bus));
+
+    // A coincidence during HBlank keeps the line high into the next line too
+    bus.write(STAT_ADDR, STAT_HBLANK_INTERRUPT | STAT_LYC_INTERRUPT);
+    bus.write(LYC_ADDR, 2);
+    bus.write(IF_ADDR, 0x00);
+    run(&mut bus, LINE_CYCLES - MODE_2_CYCLES - MODE_3_CYCLES);
+    assert_eq!(bus.read(LY_ADDR), 2);
+    assert!(!stat_requested(&mut bus));
+}
+
+#[test]
+fn vram_and_oam_are_blocked_during_the_transfer() {
+    let mut bus = bus();
+    bus.write(LCDC_ADDR, 0x00);
+    bus.write(0x8000, 0x12);
+    bus.write(0xFE00, 0x34);
+    bus.write(LCDC_ADDR, 0x91);
+
+    // Mode 2: only OAM is blocked
+    assert_eq!(mode(&mut bus), 2);
+    assert_eq!(bus.read(0x8000), 0x12);
+    assert_eq!(bus.read(0xFE00), 0xFF);
+    bus.write(0xFE00, 0x56);
+
+    // Mode 3: both are blocked, writes are ignored
+    run(&mut bus, MODE_2_CYCLES);
+    assert_eq!(mode(&mut bus), 3);
+    assert_eq!(bus.read(0x8000), 0xFF);
+    assert_eq!(bus.read(0xFE00), 0xFF);
+    bus.write(0x8000, 0x78);
+
+    // Mode 0: both are accessible again
+    run(&mut bus, MODE_3_CYCLES);
+    assert_eq!(mode(&mut bus), 0);
+    assert_eq!(bus.read(0x8000), 0x12);
+    assert_eq!(bus.read(0xFE00), 0x34);
+
+    // Mode 1 too
+    run(&mut bus, 144 * LINE_CYCLES);
+    assert_eq!(mode(&mut bus), 1);
+    bus.write(0x8000, 0x9A);
+    bus.write(0xFE00, 0xBC);
+    assert_eq!(bus.read(0x8000), 0x9A);
+    assert_eq!(bus.read(0xFE00), 0xBC);
+}
+
+// LCD, background, window and sprites on, window map at 0x9C00 and tile data at 0x8000
+const LCDC_ALL: u8 = 0xF3;
+
+/// A PPU rendering with the FIFO, with the LCD off so that VRAM and OAM can be filled
+fn fifo_ppu(model: Model) -> Ppu {
+    let mut ppu = Ppu::with_model(model);
+    ppu.set_renderer(Renderer::Fifo);
+    ppu.write(LCDC_ADDR, 0x00);
+    ppu
+}
+
+/// Runs until the transfer of the current line starts, then returns the number of dots it takes
+fn transfer_dots(ppu: &mut Ppu) -> u32 {
+    let mut interrupts = Interrupts::default();
+    while ppu.mode() != Mode::Transfer {
+        ppu.tick(&mut interrupts, 1);
+    }
+
+    let mut dots = 0;
+    while ppu.mode() == Mode::Transfer {
+        ppu.tick(&mut interrupts, 1);
+        dots += 1;
+    }
+    dots
+}
+
+/// Puts sprites on line 0 at the given X positions
+fn place_sprites(ppu: &mut Ppu, xs: &[u8]) {
+    for (index, &x) in xs.iter().enumerate() {
+        let addr = 0xFE00 + index as u16 * 4;
+        ppu.write_oam(addr, 16);
+        ppu.write_oam(addr + 1, x);
+    }
+}
+
+#[test]
+fn fifo_transfer_is_stretched_by_fine_scrolling() {
+    for scx in [0, 1, 5, 7, 8, 13, 0xFF] {
+        let mut ppu = fifo_ppu(Model::Dmg);
+        ppu.write(SCX_ADDR, scx);
+        ppu.write(LCDC_ADDR, 0x91);
+        assert_eq!(transfer_dots(&mut ppu), 172 + (scx % 8) as u32, "SCX {}", scx);
+    }
+
+    // The scanline renderer always takes 172 dots
+    let mut ppu = fifo_ppu(Model::Dmg);
+    ppu.set_renderer(Renderer::Scanline);
+    ppu.write(SCX_ADDR, 7);
+    ppu.write(LCDC_ADDR, 0x91);
+    assert_eq!(transfer_dots(&mut ppu), 172);
+}
+
+#[test]
+fn fifo_transfer_is_stretched_by_sprites() {
+    // A sprite aligned with the background tiles stalls the fetcher for 6 dots
+    let mut ppu = fifo_ppu(Model::Dmg);
+    place_sprites(&mut ppu, &[8]);
+    ppu.write(LCDC_ADDR, 0x93);
+    assert_eq!(transfer_dots(&mut ppu), 178);
+
+    // Otherwise the fetcher first finishes the tile it is fetching
+    let mut ppu = fifo_ppu(Model::Dmg);
+    place_sprites(&mut ppu, &[12]);
+    ppu.write(LCDC_ADDR, 0x93);
+    let dots = transfer_dots(&mut ppu);
+    assert!((179..=183).contains(&dots), "{} dots", dots);
+
+    // Every sprite on the line is fetched, up to 10
+    let mut ppu = fifo_ppu(Model::Dmg);
+    place_sprites(&mut ppu, &[8; 12]);
+    ppu.write(LCDC_ADDR, 0x93);
+    assert_eq!(transfer_dots(&mut ppu), 172 + 10 * 6);
+
+    // Sprites off the right edge, or with sprites disabled, cost nothing
+    let mut ppu = fifo_ppu(Model::Dmg);
+    place_sprites(&mut ppu, &[168, 200]);
+    ppu.write(LCDC_ADDR, 0x93);
+    assert_eq!(transfer_dots(&mut ppu), 172);
+    let mut ppu = fifo_ppu(Model::Dmg);
+    place_sprites(&mut ppu, &[8, 16, 24]);
+    ppu.write(LCDC_ADDR, 0x91);
+    assert_eq!(transfer_dots(&mut ppu), 172);
+}
+
+#[test]
+fn fifo_transfer_is_stretched_by_the_window() {
+    let mut ppu = fifo_ppu(Model::Dmg);
+    ppu.write(WX_ADDR, 50);
+    ppu.write(LCDC_ADDR, LCDC_ALL & !0x02);
+    assert_eq!(transfer_dots(&mut ppu), 178);
+
+    // Not before LY reached WY
+    let mut ppu = fifo_ppu(Model::Dmg);
+    ppu.write(WX_ADDR, 50);
+    ppu.write(WY_ADDR, 1);
+    ppu.write(LCDC_ADDR, LCDC_ALL & !0x02);
+    assert_eq!(transfer_dots(&mut ppu), 172);
+    assert_eq!(transfer_dots(&mut ppu), 178);
+}
+
+/// Renders the first frame of pseudorandom VRAM and OAM, with scrolling, the window and sprites
+fn random_frame(model: Model, renderer: Renderer, lcdc: u8) -> Vec<u32> {
+    let mut ppu = fifo_ppu(model);
+    ppu.set_renderer(renderer);
+
+    let mut seed = 0x1234_5678u32;
+    let mut random = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as u8
+    };
+    for bank in 0..2 {
+        ppu.write(VBK_ADDR, bank);
+        for addr in 0x8000..0xA000 {
+            ppu.write_vram(addr, random());
+        }
+    }
+    for addr in 0xFE00..0xFEA0 {
+        ppu.write_oam(addr, random());
+    }
+    ppu.write(BCPS_ADDR, 0x80);
+    ppu.write(OCPS_ADDR, 0x80);
+    for _ in 0..64 {
+        ppu.write(BCPD_ADDR, random());
+        ppu.write(OCPD_ADDR, random());
+    }
+
+    ppu.write(SCX_ADDR, 13);
+    ppu.write(SCY_ADDR, 7);
+    ppu.write(WX_ADDR, 50);
+    ppu.write(WY_ADDR, 40);
+    ppu.write(BGP_ADDR, 0xE4);
+    ppu.write(OBP0_ADDR, 0xD2);
+    ppu.write(OBP1_ADDR, 0x1B);
+    ppu.write(LCDC_ADDR, lcdc);
+
+    let mut interrupts = Interrupts::default();
+    while !ppu.take_frame() {
+        ppu.tick(&mut interrupts, 1);
+    }
+    ppu.framebuffer().to_vec()
+}
+
+#[test]
+fn fifo_renders_static_frames_like_the_scanline_renderer() {
+    // Everything on, 8x16 sprites with the other maps, signed tile indices, and background off
+    for model in [Model::Dmg, Model::Cgb, Model::CgbCompatibility] {
+        for lcdc in [LCDC_ALL, 0xE7, 0x93, 0x8B, 0xF2] {
+            let scanline = random_frame(model, Renderer::Scanline, lcdc);
+            let fifo = random_frame(model, Renderer::Fifo, lcdc);
+            let diff = scanline.iter().zip(&fifo).position(|(a, b)| a != b);
+            assert_eq!(diff, None, "{:?} LCDC {:02X} differs at ({}, {})", model, lcdc,
+                diff.unwrap_or(0) % SCREEN_WIDTH, diff.unwrap_or(0) / SCREEN_WIDTH);
+        }
+    }
+}
+
+/**
+ * A DMG PPU drawing line 0 with tile 0, all color 3, ticked into the transfer of line 0 by `dots`.
+ * The columns of the map off screen without scrolling have the blank tile 1.
+ */
+fn mid_line(renderer: Renderer, dots: u32) -> Ppu {
+    let mut ppu = fifo_ppu(Model::Dmg);
+    ppu.set_renderer(renderer);
+    for addr in 0x8000..0x8010 {
+        ppu.write_vram(addr, 0xFF);
+    }
+    for addr in 0x9800 + 21..0x9800 + 32 {
+        ppu.write_vram(addr, 1);
+    }
+    ppu.write(BGP_ADDR, 0xE4);
+    ppu.write(LCDC_ADDR, 0x91);
+
+    let mut interrupts = Interrupts::default();
+    ppu.tick(&mut interrupts, 80 + dots);
+    assert_eq!(ppu.mode(), Mode::Transfer);
+    ppu
+}
+
+fn finish_line(ppu: &mut Ppu) -> Vec<u32> {
+    let mut interrupts = Interrupts::default();
+    while ppu.mode() == Mode::Transfer {
+        ppu.tick(&mut interrupts, 1);
+    }
+    ppu.framebuffer()[..SCREEN_WIDTH].to_vec()
+}
+
+#[test]
+fn fifo_applies_register_writes_mid_line() {
+    // The pixels shifted out after BGP was written use the new palette
+    let mut ppu = mid_line(Renderer::Fifo, 90);
+    ppu.write(BGP_ADDR, 0x00);
+    let line = finish_line(&mut ppu);
+    let split = line.iter().position(|&color| color == DMG_COLORS[0]).unwrap();
+    assert!(split > 0 && split < SCREEN_WIDTH - 8, "split at {}", split);
+    assert!(line[..split].iter().all(|&color| color == DMG_COLORS[3]));
+    assert!(line[split..].iter().all(|&color| color == DMG_COLORS[0]));
+
+    // The scanline renderer drew the whole line at the start of the transfer
+    let mut ppu = mid_line(Renderer::Scanline, 90);
+    ppu.write(BGP_ADDR, 0x00);
+    assert!(finish_line(&mut ppu).iter().all(|&color| color == DMG_COLORS[3]));
+
+    // Disabling the background blanks the rest of the line
+    let mut ppu = mid_line(Renderer::Fifo, 90);
+    ppu.write(LCDC_ADDR, 0x90);
+    let line = finish_line(&mut ppu);
+    assert_eq!(line[0], DMG_COLORS[3]);
+    assert_eq!(line[SCREEN_WIDTH - 1], DMG_COLORS[0]);
+
+    // SCX written mid-line moves the tiles fetched after it to the blank columns
+    let mut ppu = mid_line(Renderer::Fifo, 90);
+    ppu.write(SCX_ADDR, 11 * 8);
+    let line = finish_line(&mut ppu);
+    let split = line.iter().position(|&color| color == DMG_COLORS[0]).unwrap();
+    assert!(split > 0 && split < SCREEN_WIDTH - 8, "split at {}", split);
+    assert!(line[..split].iter().all(|&color| color == DMG_COLORS[3]));
+    assert!(line[split..].iter().all(|&color| color == DMG_COLORS[0]));
+}
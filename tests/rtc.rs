@@ -0,0 +1,129 @@
+use emu::mbc::{FakeClock, Mapper, Rtc, MBC3};
+
+const SECONDS: u8 = 0x08;
+const MINUTES: u8 = 0x09;
+const HOURS: u8 = 0x0A;
+const DAY_LOW: u8 = 0x0B;
+const DAY_HIGH: u8 = 0x0C;
+
+/// An MBC3 with RAM and the RTC enabled, and a handle to advance its clock
+fn mbc3() -> (MBC3, FakeClock) {
+    let clock = FakeClock::new(1_000_000);
+    let mut mbc = MBC3::new(vec![0; 0x8000], vec![0; 0x2000], Some(Rtc::new(Box::new(clock.clone()))));
+    mbc.write(0x0000, 0x0A);
+    (mbc, clock)
+}
+
+fn write_rtc(mbc: &mut MBC3, reg: u8, value: u8) {
+    mbc.write(0x4000, reg);
+    mbc.write(0xA000, value);
+}
+
+fn read_rtc(mbc: &mut MBC3, reg: u8) -> u8 {
+    mbc.write(0x4000, reg);
+    mbc.read(0xA000)
+}
+
+fn latch(mbc: &mut MBC3) {
+    mbc.write(0x6000, 0x00);
+    mbc.write(0x6000, 0x01);
+}
+
+fn set_time(mbc: &mut MBC3, days: u16, hours: u8, minutes: u8, seconds: u8) {
+    write_rtc(mbc, SECONDS, seconds);
+    write_rtc(mbc, MINUTES, minutes);
+    write_rtc(mbc, HOURS, hours);
+    write_rtc(mbc, DAY_LOW, days as u8);
+    write_rtc(mbc, DAY_HIGH, (days >> 8) as u8);
+}
+
+/// The latched time as (day_high, day_low, hours, minutes, seconds)
+fn time(mbc: &mut MBC3) -> (u8, u8, u8, u8, u8) {
+    (
+        read_rtc(mbc, DAY_HIGH),
+        read_rtc(mbc, DAY_LOW),
+        read_rtc(mbc, HOURS),
+        read_rtc(mbc, MINUTES),
+        read_rtc(mbc, SECONDS)
+    )
+}
+
+#[test]
+fn seconds_carry_into_minutes_hours_and_days() {
+    let (mut mbc, clock) = mbc3();
+    set_time(&mut mbc, 0, 23, 59, 58);
+
+    clock.advance(1);
+    latch(&mut mbc);
+    assert_eq!(time(&mut mbc), (0x00, 0, 23, 59, 59));
+
+    clock.advance(1);
+    latch(&mut mbc);
+    assert_eq!(time(&mut mbc), (0x00, 1, 0, 0, 0));
+
+    // Day 255 carries into bit 0 of the upper day register
+    set_time(&mut mbc, 255, 23, 59, 59);
+    clock.advance(61);
+    latch(&mut mbc);
+    assert_eq!(time(&mut mbc), (0x01, 0, 0, 1, 0));
+}
+
+#[test]
+fn day_counter_overflow_sets_carry() {
+    let (mut mbc, clock) = mbc3();
+    set_time(&mut mbc, 511, 23, 59, 59);
+
+    clock.advance(1);
+    latch(&mut mbc);
+    assert_eq!(time(&mut mbc), (0x80, 0, 0, 0, 0));
+
+    // The carry stays set until the game clears it
+    clock.advance(24 * 60 * 60);
+    latch(&mut mbc);
+    assert_eq!(time(&mut mbc), (0x80, 1, 0, 0, 0));
+
+    write_rtc(&mut mbc, DAY_HIGH, 0x00);
+    latch(&mut mbc);
+    assert_eq!(read_rtc(&mut mbc, DAY_HIGH), 0x00);
+}
+
+#[test]
+fn halt_stops_counting() {
+    let (mut mbc, clock) = mbc3();
+    set_time(&mut mbc, 0, 1, 2, 3);
+    write_rtc(&mut mbc, DAY_HIGH, 0x40);
+
+    clock.advance(1000);
+    latch(&mut mbc);
+    assert_eq!(time(&mut mbc), (0x40, 0, 1, 2, 3));
+
+    // Counting resumes from where it stopped, the halted time is lost
+    write_rtc(&mut mbc, DAY_HIGH, 0x00);
+    clock.advance(5);
+    latch(&mut mbc);
+    assert_eq!(time(&mut mbc), (0x00, 0, 1, 2, 8));
+}
+
+#[test]
+fn latch_needs_0x00_then_0x01() {
+    let (mut mbc, clock) = mbc3();
+
+    clock.advance(10);
+    latch(&mut mbc);
+    assert_eq!(read_rtc(&mut mbc, SECONDS), 10);
+
+    // The latched registers stay frozen while the clock keeps running
+    clock.advance(5);
+    assert_eq!(read_rtc(&mut mbc, SECONDS), 10);
+
+    // 0x01 without 0x00 right before it does not latch
+    mbc.write(0x6000, 0x01);
+    assert_eq!(read_rtc(&mut mbc, SECONDS), 10);
+    mbc.write(0x6000, 0x00);
+    mbc.write(0x6000, 0x02);
+    mbc.write(0x6000, 0x01);
+    assert_eq!(read_rtc(&mut mbc, SECONDS), 10);
+
+    latch(&mut mbc);
+    assert_eq!(read_rtc(&mut mbc, SECONDS), 15);
+}
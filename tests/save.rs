@@ -0,0 +1,184 @@
+use std::{fs, path::PathBuf, time::Duration};
+
+use emu::cartridge::Cartridge;
+use emu::cpu::MemoryBus;
+use emu::gameboy::GameBoy;
+use emu::mbc::{FakeClock, Mapper, Rtc, RtcRegisters, MBC3};
+use emu::save::BatterySave;
+
+const RAM_SIZE: usize = 0x2000;
+
+/// A save file path unique to the test, removed if left over from an earlier run
+fn save_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("emu-{}-{}.sav", std::process::id(), name));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn mbc3(clock: &FakeClock) -> MBC3 {
+    MBC3::new(vec![0; 0x8000], vec![0; RAM_SIZE], Some(Rtc::new(Box::new(clock.clone()))))
+}
+
+fn live(mbc: &mut MBC3) -> RtcRegisters {
+    mbc.rtc_mut().unwrap().state().live
+}
+
+fn set_time(mbc: &mut MBC3, days: u16, hours: u8, minutes: u8, seconds: u8) {
+    mbc.write(0x0000, 0x0A);
+    for (reg, value) in [(0x08, seconds), (0x09, minutes), (0x0A, hours), (0x0B, days as u8), (0x0C, (days >> 8) as u8)] {
+        mbc.write(0x4000, reg);
+        mbc.write(0xA000, value);
+    }
+}
+
+#[test]
+fn missing_save_file_is_not_an_error() {
+    let path = save_path("missing");
+    let mut save = BatterySave::new(path);
+    let mut mbc = mbc3(&FakeClock::new(0));
+    assert!(!save.load(&mut mbc).unwrap());
+}
+
+#[test]
+fn clock_round_trips_through_the_48_byte_block() {
+    let path = save_path("rtc48");
+    let clock = FakeClock::new(1_600_000_000);
+    let mut mbc = mbc3(&clock);
+    set_time(&mut mbc, 0x1A5, 13, 37, 42);
+    // Latch so that the latched registers differ from the live ones once the clock moves
+    mbc.write(0x6000, 0x00);
+    mbc.write(0x6000, 0x01);
+    clock.advance(5);
+    mbc.ram_mut()[0x123] = 0x99;
+
+    let mut save = BatterySave::new(path.clone());
+    save.flush(&mut mbc).unwrap();
+    let data = fs::read(&path).unwrap();
+    assert_eq!(data.len(), RAM_SIZE + 48);
+    // The timestamp is the last word, as a 64-bit little-endian value
+    assert_eq!(data[RAM_SIZE + 40..], 1_600_000_005u64.to_le_bytes());
+
+    let mut restored = mbc3(&clock);
+    assert!(BatterySave::new(path.clone()).load(&mut restored).unwrap());
+    assert_eq!(restored.ram()[0x123], 0x99);
+    assert_eq!(restored.rtc_mut().unwrap().state(), mbc.rtc_mut().unwrap().state());
+    assert_eq!(live(&mut restored), RtcRegisters { seconds: 47, minutes: 37, hours: 13, day_low: 0xA5, day_high: 0x01 });
+
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn legacy_44_byte_block_is_loaded() {
+    let path = save_path("rtc44");
+    let mut data = vec![0x55; RAM_SIZE];
+    // Live then latched seconds, minutes, hours, day low and day high, then a 32-bit timestamp
+    for word in [10u32, 20, 3, 0x40, 0x01, 9, 19, 2, 0x3F, 0x00, 1_000_000] {
+        data.extend_from_slice(&word.to_le_bytes());
+    }
+    fs::write(&path, &data).unwrap();
+
+    let clock = FakeClock::new(1_000_000);
+    let mut mbc = mbc3(&clock);
+    assert!(BatterySave::new(path.clone()).load(&mut mbc).unwrap());
+    assert_eq!(mbc.ram()[RAM_SIZE - 1], 0x55);
+
+    let state = mbc.rtc_mut().unwrap().state();
+    assert_eq!(state.live, RtcRegisters { seconds: 10, minutes: 20, hours: 3, day_low: 0x40, day_high: 0x01 });
+    assert_eq!(state.latched, RtcRegisters { seconds: 9, minutes: 19, hours: 2, day_low: 0x3F, day_high: 0x00 });
+    assert_eq!(state.timestamp, 1_000_000);
+
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn clock_catches_up_with_the_time_spent_closed() {
+    let path = save_path("rtc-closed");
+    let clock = FakeClock::new(2_000_000);
+    let mut mbc = mbc3(&clock);
+    set_time(&mut mbc, 0, 23, 59, 30);
+    BatterySave::new(path.clone()).flush(&mut mbc).unwrap();
+
+    // A day, an hour, a minute and a second later
+    let later = FakeClock::new(2_000_000 + 86_400 + 3_661);
+    let mut restored = mbc3(&later);
+    BatterySave::new(path.clone()).load(&mut restored).unwrap();
+    assert_eq!(live(&mut restored), RtcRegisters { seconds: 31, minutes: 0, hours: 1, day_low: 2, day_high: 0 });
+
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn halted_clock_does_not_catch_up() {
+    let path = save_path("rtc-halted");
+    let clock = FakeClock::new(3_000_000);
+    let mut mbc = mbc3(&clock);
+    set_time(&mut mbc, 0, 1, 2, 3);
+    mbc.write(0x4000, 0x0C);
+    mbc.write(0xA000, 0x40);
+    BatterySave::new(path.clone()).flush(&mut mbc).unwrap();
+
+    let mut restored = mbc3(&FakeClock::new(3_100_000));
+    BatterySave::new(path.clone()).load(&mut restored).unwrap();
+    assert_eq!(live(&mut restored), RtcRegisters { seconds: 3, minutes: 2, hours: 1, day_low: 0, day_high: 0x40 });
+
+    let _ = fs::remove_file(path);
+}
+
+/// An MBC1 cartridge with 8 KiB of battery-backed RAM, spinning at the entry point
+fn battery_cartridge() -> Cartridge {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+    rom[0x147] = 0x03;
+    rom[0x149] = 0x02;
+    Cartridge::from_bytes(rom).unwrap()
+}
+
+#[test]
+fn emulation_loop_loads_autosaves_and_flushes() {
+    let path = save_path("gameboy");
+    let cart = battery_cartridge();
+
+    let mut gb = GameBoy::new(&cart).unwrap();
+    let mut save = BatterySave::new(path.clone());
+    save.set_interval(Duration::ZERO);
+    assert!(!gb.attach_save(save).unwrap());
+
+    gb.bus.write(0x0000, 0x0A);
+    gb.bus.write(0xA000, 0x42);
+    assert!(!path.exists());
+    gb.run_frame().unwrap();
+    assert_eq!(fs::read(&path).unwrap()[0], 0x42);
+
+    gb.bus.write(0xA001, 0x43);
+    gb.flush_save().unwrap();
+    assert_eq!(fs::read(&path).unwrap()[..2], [0x42, 0x43]);
+
+    let mut gb = GameBoy::new(&cart).unwrap();
+    assert!(gb.attach_save(BatterySave::new(path.clone())).unwrap());
+    gb.bus.write(0x0000, 0x0A);
+    assert_eq!(gb.bus.read(0xA000), 0x42);
+    assert_eq!(gb.bus.read(0xA001), 0x43);
+
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn autosave_waits_for_the_interval() {
+    let path = save_path("interval");
+    let cart = battery_cartridge();
+
+    let mut gb = GameBoy::new(&cart).unwrap();
+    let mut save = BatterySave::new(path.clone());
+    save.set_interval(Duration::from_secs(3600));
+    gb.attach_save(save).unwrap();
+
+    gb.bus.write(0x0000, 0x0A);
+    gb.bus.write(0xA000, 0x42);
+    gb.run_frame().unwrap();
+    assert!(!path.exists());
+
+    gb.flush_save().unwrap();
+    assert_eq!(fs::read(&path).unwrap().len(), RAM_SIZE);
+
+    let _ = fs::remove_file(path);
+}
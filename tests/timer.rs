@@ -0,0 +1,160 @@
+use emu::interrupt::{Interrupt, Interrupts};
+use emu::timer::{Timer, DIV_ADDR, TAC_ADDR, TIMA_ADDR, TMA_ADDR};
+
+// Enabled, incrementing on the falling edge of counter bit 3, every 4 machine cycles
+const TAC_BIT_3: u8 = 0x05;
+
+/// A timer whose counter was just reset by writing DIV
+fn timer(tac: u8) -> (Timer, Interrupts) {
+    let mut timer = Timer::new();
+    timer.write(DIV_ADDR, 0x00);
+    timer.write(TAC_ADDR, tac);
+    (timer, Interrupts::default())
+}
+
+fn tick(timer: &mut Timer, interrupts: &mut Interrupts, cycles: usize) {
+    for _ in 0..cycles {
+        timer.tick(interrupts);
+    }
+}
+
+#[test]
+fn div_is_the_upper_byte_of_the_counter() {
+    let (mut timer, mut interrupts) = timer(0x00);
+    tick(&mut timer, &mut interrupts, 63);
+    assert_eq!(timer.read(DIV_ADDR), 0x00);
+    tick(&mut timer, &mut interrupts, 1);
+    assert_eq!(timer.read(DIV_ADDR), 0x01);
+    assert_eq!(timer.counter(), 0x100);
+
+    // Any write resets the whole counter
+    timer.write(DIV_ADDR, 0x42);
+    assert_eq!(timer.counter(), 0);
+}
+
+#[test]
+fn tima_increments_on_the_falling_edge_of_the_selected_bit() {
+    let (mut timer, mut interrupts) = timer(TAC_BIT_3);
+
+    // Bit 3 rises after 2 cycles and falls after 4
+    tick(&mut timer, &mut interrupts, 2);
+    assert_eq!(timer.counter(), 0x08);
+    assert_eq!(timer.read(TIMA_ADDR), 0);
+    tick(&mut timer, &mut interrupts, 1);
+    assert_eq!(timer.read(TIMA_ADDR), 0);
+    tick(&mut timer, &mut interrupts, 1);
+    assert_eq!(timer.read(TIMA_ADDR), 1);
+    tick(&mut timer, &mut interrupts, 400);
+    assert_eq!(timer.read(TIMA_ADDR), 101);
+}
+
+#[test]
+fn tac_selects_the_frequency() {
+    // The counter bit and so the number of machine cycles per increment
+    for (tac, cycles) in [(0x04, 256), (0x05, 4), (0x06, 16), (0x07, 64)] {
+        let (mut timer, mut interrupts) = timer(tac);
+        tick(&mut timer, &mut interrupts, cycles - 1);
+        assert_eq!(timer.read(TIMA_ADDR), 0, "TAC {:02X}", tac);
+        tick(&mut timer, &mut interrupts, 1);
+        assert_eq!(timer.read(TIMA_ADDR), 1, "TAC {:02X}", tac);
+        tick(&mut timer, &mut interrupts, cycles);
+        assert_eq!(timer.read(TIMA_ADDR), 2, "TAC {:02X}", tac);
+    }
+
+    // Disabled, the counter keeps running but TIMA does not move
+    let (mut timer, mut interrupts) = timer(0x01);
+    tick(&mut timer, &mut interrupts, 1000);
+    assert_eq!(timer.read(TIMA_ADDR), 0);
+    assert_eq!(timer.read(TAC_ADDR), 0xF9);
+}
+
+#[test]
+fn div_write_increments_tima_if_the_selected_bit_was_set() {
+    let (mut timer, mut interrupts) = timer(TAC_BIT_3);
+    tick(&mut timer, &mut interrupts, 2);
+    timer.write(DIV_ADDR, 0x00);
+    assert_eq!(timer.read(TIMA_ADDR), 1);
+
+    // With the bit clear, resetting the counter only delays the next increment
+    tick(&mut timer, &mut interrupts, 1);
+    timer.write(DIV_ADDR, 0x00);
+    assert_eq!(timer.read(TIMA_ADDR), 1);
+    tick(&mut timer, &mut interrupts, 4);
+    assert_eq!(timer.read(TIMA_ADDR), 2);
+}
+
+#[test]
+fn tac_write_increments_tima_if_the_signal_falls() {
+    let (mut timer, mut interrupts) = timer(TAC_BIT_3);
+    tick(&mut timer, &mut interrupts, 2);
+
+    // Bit 3 is set, bit 5 is not
+    timer.write(TAC_ADDR, 0x06);
+    assert_eq!(timer.read(TIMA_ADDR), 1);
+
+    // Disabling the timer while the selected bit is set increments too
+    timer.write(TAC_ADDR, TAC_BIT_3);
+    assert_eq!(timer.read(TIMA_ADDR), 1);
+    timer.write(TAC_ADDR, 0x01);
+    assert_eq!(timer.read(TIMA_ADDR), 2);
+
+    // Enabling it or switching between two set bits does not
+    tick(&mut timer, &mut interrupts, 8);
+    assert_eq!(timer.counter(), 0x28);
+    timer.write(TAC_ADDR, TAC_BIT_3);
+    timer.write(TAC_ADDR, 0x06);
+    assert_eq!(timer.read(TIMA_ADDR), 2);
+}
+
+/// A timer which overflowed during the last machine cycle, with TMA set to 0x42
+fn overflowed() -> (Timer, Interrupts) {
+    let (mut timer, mut interrupts) = timer(TAC_BIT_3);
+    timer.write(TIMA_ADDR, 0xFF);
+    timer.write(TMA_ADDR, 0x42);
+    tick(&mut timer, &mut interrupts, 4);
+    (timer, interrupts)
+}
+
+#[test]
+fn overflow_reloads_tma_one_cycle_later() {
+    let (mut timer, mut interrupts) = overflowed();
+
+    // TIMA reads 0 for a cycle, before the reload and the interrupt request
+    assert_eq!(timer.read(TIMA_ADDR), 0x00);
+    assert_eq!(interrupts.flags, 0);
+
+    tick(&mut timer, &mut interrupts, 1);
+    assert_eq!(timer.read(TIMA_ADDR), 0x42);
+    assert_eq!(interrupts.flags, Interrupt::Timer.bit());
+
+    // The next increment starts from TMA
+    tick(&mut timer, &mut interrupts, 3);
+    assert_eq!(timer.read(TIMA_ADDR), 0x43);
+}
+
+#[test]
+fn tima_write_during_the_delay_cancels_the_reload() {
+    let (mut timer, mut interrupts) = overflowed();
+    timer.write(TIMA_ADDR, 0x10);
+
+    tick(&mut timer, &mut interrupts, 1);
+    assert_eq!(timer.read(TIMA_ADDR), 0x10);
+    assert_eq!(interrupts.flags, 0);
+}
+
+#[test]
+fn tima_write_during_the_reload_is_ignored() {
+    let (mut timer, mut interrupts) = overflowed();
+    tick(&mut timer, &mut interrupts, 1);
+
+    timer.write(TIMA_ADDR, 0x10);
+    assert_eq!(timer.read(TIMA_ADDR), 0x42);
+
+    // TMA written in the same cycle goes to TIMA as well
+    timer.write(TMA_ADDR, 0x80);
+    assert_eq!(timer.read(TIMA_ADDR), 0x80);
+
+    tick(&mut timer, &mut interrupts, 1);
+    timer.write(TIMA_ADDR, 0x10);
+    assert_eq!(timer.read(TIMA_ADDR), 0x10);
+}